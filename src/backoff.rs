@@ -0,0 +1,126 @@
+//! 扫描超时的目录在之后的扫描中退避，避免反复卡在同一个挂起的挂载点上
+
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::status::DirectoryStatus;
+
+/// 连续超时后最多跳过的扫描次数
+pub const MAX_BACKOFF_SCANS: u32 = 16;
+
+#[derive(Debug)]
+struct TimedOut {
+    /// 连续超时的次数
+    failures: u32,
+    /// 还要跳过的扫描次数
+    remaining: u32,
+}
+
+/// 记录扫描超时的目录
+///
+/// 第 n 次连续超时后跳过之后的 2^(n-1) 次扫描（最多 [`MAX_BACKOFF_SCANS`] 次）。
+/// 超时的扫描线程无法中断，在它结束之前同一目录不会再次扫描，以免占满阻塞线程池。
+#[derive(Debug, Default)]
+pub struct ScanBackoff {
+    timed_out: Mutex<HashMap<PathBuf, TimedOut>>,
+    /// 扫描线程仍在运行的目录，值表示是否因 `scan_deadline` 而放弃等待
+    running: Mutex<HashMap<PathBuf, bool>>,
+}
+
+impl ScanBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 本次扫描是否应跳过该目录，跳过时返回报告的状态
+    ///
+    /// 上次扫描超时的目录报告为不可用；只是被截止时间打断的目录报告为状态未知。
+    pub fn skip(&self, path: &Path) -> Option<DirectoryStatus> {
+        match lock(&self.running).get(path) {
+            Some(true) => {
+                return Some(DirectoryStatus::Unknown(
+                    "超过扫描截止时间，上次扫描仍未结束".to_string(),
+                ))
+            }
+            Some(false) => {
+                return Some(DirectoryStatus::Unavailable(
+                    "上次扫描超时且仍未结束".to_string(),
+                ))
+            }
+            None => {}
+        }
+
+        let mut timed_out = lock(&self.timed_out);
+        let entry = timed_out.get_mut(path)?;
+        if entry.remaining == 0 {
+            return None;
+        }
+        entry.remaining -= 1;
+        Some(DirectoryStatus::Unavailable(format!(
+            "连续 {} 次扫描超时，暂停扫描（还将跳过 {} 次）",
+            entry.failures, entry.remaining
+        )))
+    }
+
+    /// 标记目录正在扫描，返回的守卫在扫描结束（包括超时后最终结束）时释放
+    pub fn start(self: &Arc<Self>, path: &Path) -> Running {
+        lock(&self.running).insert(path.to_path_buf(), false);
+        Running {
+            backoff: Arc::clone(self),
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// 记录一次超时，返回之后跳过的扫描次数
+    pub fn record_timeout(&self, path: &Path) -> u32 {
+        let mut timed_out = lock(&self.timed_out);
+        let entry = timed_out.entry(path.to_path_buf()).or_insert(TimedOut {
+            failures: 0,
+            remaining: 0,
+        });
+        entry.failures += 1;
+        entry.remaining = 2u32
+            .saturating_pow(entry.failures - 1)
+            .min(MAX_BACKOFF_SCANS);
+        warn!(
+            "目录 '{}' 连续 {} 次扫描超时，之后跳过 {} 次扫描",
+            path.display(),
+            entry.failures,
+            entry.remaining
+        );
+        entry.remaining
+    }
+
+    /// 记录目录在扫描截止时间到达时仍未完成；扫描线程结束后自动清除
+    pub fn record_deadline(&self, path: &Path) {
+        if let Some(past_deadline) = lock(&self.running).get_mut(path) {
+            *past_deadline = true;
+        }
+    }
+
+    /// 目录在时限内完成扫描，清除超时记录
+    pub fn record_success(&self, path: &Path) {
+        if lock(&self.timed_out).remove(path).is_some() {
+            info!("目录 '{}' 已恢复正常扫描", path.display());
+        }
+    }
+}
+
+/// 正在扫描的目录，释放时从 [`ScanBackoff`] 中移除
+#[derive(Debug)]
+pub struct Running {
+    backoff: Arc<ScanBackoff>,
+    path: PathBuf,
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        lock(&self.backoff.running).remove(&self.path);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
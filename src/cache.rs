@@ -0,0 +1,224 @@
+//! 增量扫描缓存：记住每个目录的修改时间、其中最新的文件和子目录
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::config::MonitorConfig;
+
+/// 未配置 `full_rescan_minutes` 时强制全量扫描的间隔（分钟）
+pub const DEFAULT_FULL_RESCAN_MINUTES: u64 = 60;
+
+/// 修改时间距读取时不足此时长的目录不写入缓存
+///
+/// 部分文件系统（例如 NFS、ext3）的时间戳精度为 1 秒，同一秒内的后续变化不会改变修改时间。
+const MIN_DIRECTORY_AGE: Duration = Duration::from_secs(2);
+
+/// 目录中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedFile {
+    pub path: PathBuf,
+    /// 按 `time_type` 取得的文件时间
+    pub time: SystemTime,
+    pub size: u64,
+}
+
+/// 上次读取目录时的结果，只包含直接子项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedDirectory {
+    /// 目录自身的修改时间，有子项增删时会变化
+    pub modified: SystemTime,
+    /// 是否按修改时间（而不是创建时间）比较文件
+    pub use_modified: bool,
+    /// 通过过滤规则的文件中最新的一个
+    pub newest: Option<CachedFile>,
+    /// 未被排除的子目录
+    pub subdirs: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheState {
+    /// 影响目录读取结果的配置，变化后缓存作废
+    settings: String,
+    last_full_scan: Option<SystemTime>,
+    directories: HashMap<PathBuf, CachedDirectory>,
+}
+
+/// 在多次扫描之间保存目录读取结果
+///
+/// 目录的修改时间只反映直接子项的增删，因此每次扫描仍会检查每个目录的修改时间，
+/// 但修改时间未变化的目录不再读取其中的文件。已有文件被追加写入不会改变目录的修改时间，
+/// 调用方需要重新检查缓存中最新的文件；其他文件的变化要等到下一次全量扫描才会发现。
+#[derive(Debug)]
+pub struct ScanCache {
+    path: Option<PathBuf>,
+    full_rescan: Duration,
+    state: Mutex<CacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ScanCache {
+    /// 只保存在内存中的缓存
+    pub fn new(config: &MonitorConfig) -> Self {
+        Self {
+            path: None,
+            full_rescan: full_rescan_interval(config),
+            state: Mutex::new(CacheState {
+                settings: settings_key(config),
+                ..CacheState::default()
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 从文件读取缓存，每次扫描后写回；文件不存在、无法解析或配置已变化时从空缓存开始
+    pub fn load(path: impl Into<PathBuf>, config: &MonitorConfig) -> Self {
+        let path = path.into();
+        let cache = Self::new(config);
+        match fs::read(&path) {
+            Ok(content) => match serde_json::from_slice::<CacheState>(&content) {
+                Ok(state) if state.settings == settings_key(config) => {
+                    info!(
+                        "已读取扫描缓存: {}（{} 个目录）",
+                        path.display(),
+                        state.directories.len()
+                    );
+                    *cache.lock() = state;
+                }
+                Ok(_) => info!("过滤规则已变化，重新建立扫描缓存: {}", path.display()),
+                Err(e) => warn!("扫描缓存无效，重新建立: {}: {}", path.display(), e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("无法读取扫描缓存 {}: {}", path.display(), e),
+        }
+        Self {
+            path: Some(path),
+            ..cache
+        }
+    }
+
+    /// 缓存文件路径，只保存在内存中时为 `None`
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 缓存是否适用于该配置：影响目录读取结果的配置和全量扫描间隔都没有变化
+    pub fn is_compatible(&self, config: &MonitorConfig) -> bool {
+        self.full_rescan == full_rescan_interval(config)
+            && self.lock().settings == settings_key(config)
+    }
+
+    /// 缓存的目录数
+    pub fn len(&self) -> usize {
+        self.lock().directories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 开始一次扫描；距上次全量扫描超过 `full_rescan_minutes` 时清空缓存，返回本次是否为全量扫描
+    pub fn begin_scan(&self) -> bool {
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+
+        let mut state = self.lock();
+        let now = SystemTime::now();
+        let due = state.last_full_scan.is_none_or(|last| {
+            now.duration_since(last)
+                .map_or(true, |elapsed| elapsed >= self.full_rescan)
+        });
+        if due {
+            debug!("全量扫描，清空 {} 个缓存目录", state.directories.len());
+            state.directories.clear();
+            state.last_full_scan = Some(now);
+        }
+        due
+    }
+
+    /// 修改时间和时间戳类型都与缓存一致时返回缓存的结果
+    pub fn get(
+        &self,
+        dir_path: &Path,
+        modified: SystemTime,
+        use_modified: bool,
+    ) -> Option<CachedDirectory> {
+        let cached = self
+            .lock()
+            .directories
+            .get(dir_path)
+            .filter(|cached| cached.modified == modified && cached.use_modified == use_modified)
+            .cloned();
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    /// 记录目录的读取结果；刚刚修改过的目录不记录
+    pub fn insert(&self, dir_path: PathBuf, directory: CachedDirectory) {
+        let recent = SystemTime::now()
+            .duration_since(directory.modified)
+            .map_or(true, |age| age < MIN_DIRECTORY_AGE);
+        let mut state = self.lock();
+        if recent {
+            state.directories.remove(&dir_path);
+        } else {
+            state.directories.insert(dir_path, directory);
+        }
+    }
+
+    /// 本次扫描中沿用缓存和重新读取的目录数
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// 写回缓存文件（先写入临时文件再替换）；只保存在内存中时不做任何事
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let content = serde_json::to_vec(&*self.lock())?;
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, content)
+            .with_context(|| format!("无法写入扫描缓存: {}", Path::new(&temp_path).display()))?;
+        fs::rename(&temp_path, path)
+            .with_context(|| format!("无法更新扫描缓存: {}", path.display()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn full_rescan_interval(config: &MonitorConfig) -> Duration {
+    Duration::from_secs(
+        config
+            .full_rescan_minutes
+            .unwrap_or(DEFAULT_FULL_RESCAN_MINUTES)
+            * 60,
+    )
+}
+
+fn settings_key(config: &MonitorConfig) -> String {
+    format!(
+        "include={:?};exclude={:?};follow_links={:?}",
+        config.include, config.exclude, config.follow_links
+    )
+}
@@ -0,0 +1,202 @@
+//! Nagios/Icinga 兼容的检查结果：单行状态、性能数据和退出码
+
+use std::fmt;
+
+use crate::scanner::ScanReport;
+
+/// 检查状态，对应 Nagios 插件的退出码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckState {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl CheckState {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Ok => 0,
+            Self::Warning => 1,
+            Self::Critical => 2,
+            Self::Unknown => 3,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Warning => "WARNING",
+            Self::Critical => "CRITICAL",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// 合并两个状态，取更严重的一个
+    ///
+    /// 确定未在录制（CRITICAL）比无法判断（UNKNOWN）更严重，
+    /// 因此一个目录扫描失败不会掩盖另一个目录确实停止录制。
+    pub fn worst(self, other: Self) -> Self {
+        let severity = |state: Self| match state {
+            Self::Ok => 0,
+            Self::Warning => 1,
+            Self::Unknown => 2,
+            Self::Critical => 3,
+        };
+        if severity(other) > severity(self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for CheckState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 判定检查状态的规则
+#[derive(Debug, Clone, Default)]
+pub struct CheckRules {
+    /// 录制中的目录少于此数量时为 WARNING
+    pub warning_below: Option<usize>,
+    /// 录制中的目录少于此数量时为 CRITICAL
+    pub critical_below: Option<usize>,
+    /// 必须处于录制状态的目录，否则为 CRITICAL（目录或其根目录扫描失败时为 UNKNOWN）
+    pub required: Vec<String>,
+}
+
+/// 一次检查的结果
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub state: CheckState,
+    /// 状态行中 "-" 之后的说明
+    pub summary: String,
+    /// 性能数据（`label=value;warn;crit;min;max`），空时不输出 "|"
+    pub perfdata: Vec<String>,
+}
+
+impl CheckResult {
+    /// 无法完成检查（例如配置错误或扫描失败）
+    pub fn unknown(summary: impl Into<String>) -> Self {
+        Self {
+            state: CheckState::Unknown,
+            summary: summary.into(),
+            perfdata: Vec::new(),
+        }
+    }
+}
+
+/// 输出为 `FILE_MONITOR <STATE> - <summary> | <perfdata>` 形式的单行
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FILE_MONITOR {} - {}", self.state, self.summary)?;
+        if !self.perfdata.is_empty() {
+            write!(f, " | {}", self.perfdata.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl CheckRules {
+    pub fn evaluate(&self, report: &ScanReport) -> CheckResult {
+        let total = report.directories.len();
+        let recording = report.recording_count();
+        let count_status = |status: &str| {
+            report
+                .directories
+                .iter()
+                .filter(|d| d.status.as_str() == status)
+                .count()
+        };
+
+        let mut state = CheckState::Ok;
+        let mut problems = Vec::new();
+
+        // 根目录是否可访问以扫描结果为准，这里不再访问文件系统；
+        // 其余根目录的目录仍照常判定，不被不可访问的根目录掩盖
+        for root in &report.roots {
+            if let Some(error) = &root.error {
+                state = state.worst(CheckState::Unknown);
+                problems.push(format!("根目录 '{}' 不可访问: {}", root.label, error));
+            }
+        }
+        // 单个根目录时目录名不带标签
+        let root_failed = |name: &str| {
+            report.roots.iter().any(|root| {
+                root.error.is_some()
+                    && (report.roots.len() == 1
+                        || name
+                            .strip_prefix(root.label.as_str())
+                            .is_some_and(|rest| rest.starts_with('/')))
+            })
+        };
+
+        for name in &self.required {
+            let (dir_state, problem) = match report.get(name) {
+                Some(dir) if dir.is_recording() => continue,
+                Some(dir) if dir.status.is_failure() => (
+                    CheckState::Unknown,
+                    format!("目录 '{}' 状态未知: {}", name, dir.status),
+                ),
+                Some(dir) => (
+                    CheckState::Critical,
+                    format!("目录 '{}' 未在录制 ({})", name, dir.status),
+                ),
+                None if root_failed(name) => (
+                    CheckState::Unknown,
+                    format!("目录 '{}' 所在的根目录不可访问", name),
+                ),
+                None => (CheckState::Critical, format!("目录 '{}' 不存在", name)),
+            };
+            state = state.worst(dir_state);
+            problems.push(problem);
+        }
+
+        if self.critical_below.is_some_and(|min| recording < min) {
+            state = state.worst(CheckState::Critical);
+        } else if self.warning_below.is_some_and(|min| recording < min) {
+            state = state.worst(CheckState::Warning);
+        }
+
+        let mut summary = format!("{} 个目录中 {} 个正在录制", total, recording);
+        if !problems.is_empty() {
+            summary.push_str("; ");
+            summary.push_str(&problems.join("; "));
+        }
+
+        let threshold = |min: Option<usize>| min.map(|min| format!("{}:", min)).unwrap_or_default();
+        let perfdata = vec![
+            format!(
+                "recording={};{};{};0;{}",
+                recording,
+                threshold(self.warning_below),
+                threshold(self.critical_below),
+                total
+            ),
+            format!("stale={};;;0;{}", count_status("stale"), total),
+            format!("idle={};;;0;{}", count_status("idle"), total),
+            format!(
+                "failed={};;;0;{}",
+                report
+                    .directories
+                    .iter()
+                    .filter(|d| d.status.is_failure())
+                    .count(),
+                total
+            ),
+            format!(
+                "scan_duration={:.3}s;;;0;",
+                report.scan_duration.as_secs_f64()
+            ),
+        ];
+
+        CheckResult {
+            state,
+            summary,
+            perfdata,
+        }
+    }
+}
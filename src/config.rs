@@ -0,0 +1,1029 @@
+//! 配置文件的类型定义与读写
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::filter::PathFilter;
+use crate::overrides::DirectoryOverrides;
+use crate::scanner::DirectoryResult;
+use crate::status::DirectoryStatus;
+use crate::validation::{self, ConfigErrors};
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Config {
+    pub monitor: MonitorConfig,
+    pub output: OutputConfig,
+    pub server: Option<ServerConfig>,
+    pub notify: Option<NotifyConfig>,
+    pub hooks: Option<HooksConfig>,
+    pub history: Option<HistoryConfig>,
+    pub metrics: Option<MetricsConfig>,
+    // 按二级目录名（或通配符）覆盖监控参数
+    pub directories: Option<BTreeMap<String, DirectoryOverride>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MonitorConfig {
+    // 设置了 roots 时可以省略
+    #[serde(default)]
+    pub root_path: String,
+    pub check_hours: u64,
+    pub scan_interval: u64,
+    pub max_depth: Option<usize>,
+    pub follow_links: Option<bool>,
+    pub time_type: Option<String>,
+    pub parallel_mode: Option<String>,
+    pub max_parallel_tasks: Option<usize>,
+    pub search_latest_subdir_only: Option<bool>,
+    // 性能优化选项（不影响精确度）
+    pub use_async_io: Option<bool>,
+    pub batch_size: Option<usize>,
+    // 监控方式：poll（周期扫描）或 events（文件系统事件 + 周期校正扫描）
+    pub watch_mode: Option<String>,
+    // 文件过滤：只计入匹配 include 的文件，跳过匹配 exclude 的文件和目录
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    // 录制检测方式：mtime（默认，时间阈值）或 growth（最新文件在两次扫描之间变大）
+    pub detection_mode: Option<String>,
+    // growth 模式下判定为录制所需的最小增长字节数（默认1）
+    pub min_growth_bytes: Option<u64>,
+    // 通过 /proc 检测以写入方式打开文件的进程（仅 Linux，默认false）
+    pub detect_open_writers: Option<bool>,
+    // 软阈值（分钟）：check_hours 内有新文件但超过此时间没有新文件时显示为 stale
+    pub stale_minutes: Option<u64>,
+    // 遍历全部文件以找到真正最新的文件，而不是在第一个新文件处停止（默认false）
+    pub find_newest: Option<bool>,
+    // 增量扫描：修改时间未变化的目录沿用上次扫描的结果（默认false，增长检测时不生效）
+    pub incremental: Option<bool>,
+    // 增量扫描时强制全量扫描的间隔（分钟，默认60）
+    pub full_rescan_minutes: Option<u64>,
+    // 增量扫描缓存文件，相对路径以配置文件所在目录为基准（默认只保存在内存中）
+    pub scan_cache: Option<String>,
+    // 单个二级目录的扫描时限（秒），超时的目录报告为不可用并在之后的扫描中退避
+    pub scan_timeout: Option<u64>,
+    // 整次扫描的截止时间（秒），届时未完成的目录报告为状态未知，这些目录下次优先扫描
+    pub scan_deadline: Option<u64>,
+    // 遍历顺序：readdir（默认，按文件系统返回的顺序）或 newest_first（每一层先访问最新的条目）
+    pub traversal: Option<String>,
+    // 多个根目录：每个根目录单独扫描，未覆盖的选项沿用上面的设置
+    pub roots: Option<Vec<RootConfig>>,
+}
+
+/// `[[monitor.roots]]` 条目：一个根目录及其覆盖的选项
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RootConfig {
+    pub root_path: String,
+    // 报告中显示的名称，同时作为目录名前缀（默认为 root_path）
+    pub label: Option<String>,
+    pub check_hours: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub search_latest_subdir_only: Option<bool>,
+}
+
+impl RootConfig {
+    pub fn new(root_path: impl Into<String>) -> Self {
+        Self {
+            root_path: root_path.into(),
+            label: None,
+            check_hours: None,
+            max_depth: None,
+            search_latest_subdir_only: None,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.root_path)
+    }
+}
+
+/// `[directories."<名称或通配符>"]` 中针对匹配目录的覆盖设置，未设置的项沿用全局配置
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryOverride {
+    pub check_hours: Option<u64>,
+    pub time_type: Option<String>,
+    pub search_latest_subdir_only: Option<bool>,
+    pub max_depth: Option<usize>,
+    // 覆盖 [output] 中的提示信息
+    pub recording_message: Option<String>,
+    pub not_recording_message: Option<String>,
+    pub stale_message: Option<String>,
+    pub unavailable_message: Option<String>,
+    pub error_message: Option<String>,
+    pub unknown_message: Option<String>,
+}
+
+impl DirectoryOverride {
+    /// 在全局监控配置上应用覆盖设置
+    pub fn apply(&self, config: &MonitorConfig) -> MonitorConfig {
+        MonitorConfig {
+            check_hours: self.check_hours.unwrap_or(config.check_hours),
+            time_type: self.time_type.clone().or_else(|| config.time_type.clone()),
+            search_latest_subdir_only: self
+                .search_latest_subdir_only
+                .or(config.search_latest_subdir_only),
+            max_depth: self.max_depth.or(config.max_depth),
+            ..config.clone()
+        }
+    }
+
+    /// 覆盖的状态提示信息，未设置时返回 `None`
+    pub fn message(&self, status: &DirectoryStatus) -> Option<&str> {
+        match status {
+            DirectoryStatus::Recording => self.recording_message.as_deref(),
+            DirectoryStatus::Idle => self.not_recording_message.as_deref(),
+            DirectoryStatus::Stale => self.stale_message.as_deref(),
+            DirectoryStatus::Unavailable(_) => self.unavailable_message.as_deref(),
+            DirectoryStatus::Error(_) => self.error_message.as_deref(),
+            DirectoryStatus::Unknown(_) => self.unknown_message.as_deref(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutputConfig {
+    pub recording_message: String,
+    pub not_recording_message: String,
+    // 报告格式：text（默认）、json、ndjson 或 csv
+    pub format: Option<String>,
+    // 其他状态的提示信息
+    pub stale_message: Option<String>,
+    pub unavailable_message: Option<String>,
+    pub error_message: Option<String>,
+    pub unknown_message: Option<String>,
+    // 文本报告中各状态的图标
+    pub recording_icon: Option<String>,
+    pub stale_icon: Option<String>,
+    pub idle_icon: Option<String>,
+    pub unavailable_icon: Option<String>,
+    pub error_icon: Option<String>,
+    pub unknown_icon: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ServerConfig {
+    // HTTP 状态服务监听地址，例如 "0.0.0.0:8080"
+    pub listen: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub webhook: Vec<WebhookConfig>,
+}
+
+/// `[[notify.webhook]]` 条目：目录状态变化时发送 HTTP POST
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    // 额外的请求头，例如 Authorization
+    pub headers: Option<BTreeMap<String, String>>,
+    // 请求体模板，支持 {{directory}} {{path}} {{event}} {{status}} {{message}} {{timestamp}}
+    pub payload_template: Option<String>,
+    // 单次请求超时（秒，默认10）
+    pub timeout: Option<u64>,
+    // 失败后的重试次数（默认3）
+    pub retries: Option<u32>,
+    // 重试间隔（秒，默认1）
+    pub retry_delay: Option<u64>,
+}
+
+/// `[hooks]`：目录开始/停止录制时执行的命令
+///
+/// 命令通过系统 shell 执行，可使用环境变量 FM_DIR、FM_PATH、FM_STATUS、
+/// FM_EVENT 和 FM_TIMESTAMP。
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HooksConfig {
+    pub on_start: Option<String>,
+    pub on_stop: Option<String>,
+    // 所有钩子的默认超时（秒，默认30）
+    pub timeout: Option<u64>,
+    // 单个钩子的超时，未设置时使用 timeout
+    pub on_start_timeout: Option<u64>,
+    pub on_stop_timeout: Option<u64>,
+}
+
+/// `[history]`：扫描结果的持久化历史
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct HistoryConfig {
+    // 是否记录扫描历史（默认false）
+    pub enabled: Option<bool>,
+    // 历史数据库路径（默认与配置文件同目录的 file_monitor_history.db）
+    pub path: Option<String>,
+    // 记录保留天数，写入时删除更早的记录（默认不删除）
+    pub retention_days: Option<u64>,
+}
+
+/// `[metrics]`：Prometheus 指标输出
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MetricsConfig {
+    // 每次扫描后原子性写入的 .prom 文件，供 node_exporter textfile collector 读取
+    pub textfile: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: None,
+            payload_template: None,
+            timeout: None,
+            retries: None,
+            retry_delay: None,
+        }
+    }
+}
+
+impl MonitorConfig {
+    /// 使用默认参数创建监控配置，可选项均保持未设置
+    pub fn new(root_path: impl Into<String>) -> Self {
+        Self {
+            root_path: root_path.into(),
+            check_hours: 2,
+            scan_interval: 3600,
+            max_depth: None,
+            follow_links: None,
+            time_type: None,
+            parallel_mode: None,
+            max_parallel_tasks: None,
+            search_latest_subdir_only: None,
+            use_async_io: None,
+            batch_size: None,
+            watch_mode: None,
+            include: None,
+            exclude: None,
+            detection_mode: None,
+            min_growth_bytes: None,
+            detect_open_writers: None,
+            stale_minutes: None,
+            find_newest: None,
+            incremental: None,
+            full_rescan_minutes: None,
+            scan_cache: None,
+            scan_timeout: None,
+            scan_deadline: None,
+            traversal: None,
+            roots: None,
+        }
+    }
+
+    /// 配置的根目录；未设置 `roots` 时为空
+    pub fn roots(&self) -> &[RootConfig] {
+        self.roots.as_deref().unwrap_or_default()
+    }
+
+    /// 是否使用增量扫描
+    ///
+    /// 增量扫描只重新读取缓存中最新文件的时间，修改时间未变化的目录中其他文件的追加写入
+    /// 要到下一次全量扫描才会发现，增长检测据此会把正在录制的目录误判为停滞，因此
+    /// `detection_mode = "growth"` 时不使用增量扫描。
+    pub fn incremental_enabled(&self) -> bool {
+        self.incremental.unwrap_or(false) && self.detection_mode.as_deref() != Some("growth")
+    }
+
+    /// 单个根目录使用的配置：以当前配置为基础，应用该根目录的覆盖项
+    pub fn for_root(&self, root: &RootConfig) -> MonitorConfig {
+        MonitorConfig {
+            root_path: root.root_path.clone(),
+            check_hours: root.check_hours.unwrap_or(self.check_hours),
+            max_depth: root.max_depth.or(self.max_depth),
+            search_latest_subdir_only: root
+                .search_latest_subdir_only
+                .or(self.search_latest_subdir_only),
+            roots: None,
+            ..self.clone()
+        }
+    }
+
+    /// 检查根目录设置：至少有一个根目录，且标签不重复
+    fn validate_roots(&self) -> Result<()> {
+        if self.roots().is_empty() {
+            if self.root_path.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "必须设置 root_path 或至少一个 [[monitor.roots]]"
+                ));
+            }
+            return Ok(());
+        }
+
+        let mut labels = std::collections::HashSet::new();
+        for root in self.roots() {
+            if root.root_path.is_empty() {
+                return Err(anyhow::anyhow!("[[monitor.roots]] 缺少 root_path"));
+            }
+            if !labels.insert(root.label()) {
+                return Err(anyhow::anyhow!("重复的根目录标签: {}", root.label()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            recording_message: "正在录制".to_string(),
+            not_recording_message: "未录制".to_string(),
+            format: None,
+            stale_message: None,
+            unavailable_message: None,
+            error_message: None,
+            unknown_message: None,
+            recording_icon: None,
+            stale_icon: None,
+            idle_icon: None,
+            unavailable_icon: None,
+            error_icon: None,
+            unknown_icon: None,
+        }
+    }
+}
+
+impl OutputConfig {
+    /// 目录状态对应的提示信息
+    pub fn message(&self, status: &DirectoryStatus) -> &str {
+        match status {
+            DirectoryStatus::Recording => &self.recording_message,
+            DirectoryStatus::Idle => &self.not_recording_message,
+            DirectoryStatus::Stale => self.stale_message.as_deref().unwrap_or("录制可能已停止"),
+            DirectoryStatus::Unavailable(_) => self
+                .unavailable_message
+                .as_deref()
+                .unwrap_or("目录不可访问"),
+            DirectoryStatus::Error(_) => self.error_message.as_deref().unwrap_or("扫描出错"),
+            DirectoryStatus::Unknown(_) => self.unknown_message.as_deref().unwrap_or("状态未知"),
+        }
+    }
+
+    /// 目录的提示信息，优先使用匹配该目录的 `[directories]` 覆盖设置
+    pub fn message_for<'a>(&'a self, dir: &'a DirectoryResult) -> &'a str {
+        dir.overrides
+            .as_deref()
+            .and_then(|o| o.message(&dir.status))
+            .unwrap_or_else(|| self.message(&dir.status))
+    }
+
+    /// 目录状态在文本报告中的图标
+    pub fn icon(&self, status: &DirectoryStatus) -> &str {
+        let (icon, default) = match status {
+            DirectoryStatus::Recording => (&self.recording_icon, "[REC]"),
+            DirectoryStatus::Stale => (&self.stale_icon, "[OLD]"),
+            DirectoryStatus::Idle => (&self.idle_icon, "[---]"),
+            DirectoryStatus::Unavailable(_) => (&self.unavailable_icon, "[N/A]"),
+            DirectoryStatus::Error(_) => (&self.error_icon, "[ERR]"),
+            DirectoryStatus::Unknown(_) => (&self.unknown_icon, "[???]"),
+        };
+        icon.as_deref().unwrap_or(default)
+    }
+}
+
+impl Config {
+    /// 从指定路径读取并解析配置文件
+    pub fn from_file(config_path: &str) -> Result<Self> {
+        Self::load(config_path, &[])
+    }
+
+    /// 读取配置文件但不校验取值，用于修改个别配置项后写回文件
+    pub fn from_file_unchecked(config_path: &str) -> Result<Self> {
+        let content = fs::read_to_string(config_path)
+            .with_context(|| format!("无法读取配置文件: {}", config_path))?;
+        let (config, _) = validation::parse(&content)
+            .with_context(|| format!("配置文件格式错误: {}", config_path))?;
+        Ok(config)
+    }
+
+    /// 读取配置文件，应用命令行 `--set key=value` 覆盖后再校验
+    ///
+    /// 未知的配置项、超出范围或无效的取值都会被报告，并尽量指出所在的行和列。
+    pub fn load(config_path: &str, settings: &[String]) -> Result<Self> {
+        let content = fs::read_to_string(config_path)
+            .with_context(|| format!("无法读取配置文件: {}", config_path))?;
+
+        let (mut config, mut issues) = validation::parse(&content)
+            .with_context(|| format!("配置文件格式错误: {}", config_path))?;
+        for setting in settings {
+            config.monitor.apply_setting(setting)?;
+        }
+
+        issues.extend(validation::check_values(&config));
+        let overridden: Vec<String> = settings
+            .iter()
+            .filter_map(|setting| setting.split_once('='))
+            .map(|(key, _)| {
+                let key = key.trim();
+                key.strip_prefix("monitor.").unwrap_or(key).to_string()
+            })
+            .collect();
+        validation::locate(&content, &mut issues, &overridden);
+        if !issues.is_empty() {
+            issues.sort_by_key(|issue| issue.location.unwrap_or((usize::MAX, 0)));
+            return Err(anyhow::Error::new(ConfigErrors(issues)))
+                .with_context(|| format!("配置文件无效: {}", config_path));
+        }
+
+        config
+            .validate()
+            .with_context(|| format!("配置文件格式错误: {}", config_path))?;
+
+        Ok(config)
+    }
+
+    /// 检查取值范围、枚举值，以及过滤规则、根目录和目录覆盖模式
+    pub fn validate(&self) -> Result<()> {
+        let issues = validation::check_values(self);
+        if !issues.is_empty() {
+            return Err(ConfigErrors(issues).into());
+        }
+        PathFilter::try_from_config(&self.monitor)?;
+        self.monitor.validate_roots()?;
+        DirectoryOverrides::new(self.directories.as_ref())?;
+        Ok(())
+    }
+}
+
+impl MonitorConfig {
+    /// 应用 `key=value` 形式的设置，key 为 `[monitor]` 中的配置项（可带 `monitor.` 前缀）
+    ///
+    /// value 按 TOML 值解析（如 `2`、`true`、`["*.ts"]`），无法解析时视为字符串。
+    pub fn apply_setting(&mut self, setting: &str) -> Result<()> {
+        let (key, raw) = setting
+            .split_once('=')
+            .with_context(|| format!("无效的设置 '{}'，格式应为 key=value", setting))?;
+        let key = key.trim();
+        let key = key.strip_prefix("monitor.").unwrap_or(key);
+        let raw = raw.trim();
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+
+        let mut table = toml::Table::try_from(&*self).context("无法序列化监控配置")?;
+        table.insert(key.to_string(), value);
+        let updated: MonitorConfig = table
+            .try_into()
+            .with_context(|| format!("无效的设置 '{}'", setting))?;
+
+        // 未知的配置项在反序列化时被忽略，设置后仍不存在说明 key 无效
+        let known = toml::Table::try_from(&updated).context("无法序列化监控配置")?;
+        if !known.contains_key(key) {
+            return Err(match validation::suggest_monitor_field(key) {
+                Some(suggestion) => {
+                    anyhow::anyhow!("未知的配置项 '{}'，是否应为 '{}'？", key, suggestion)
+                }
+                None => anyhow::anyhow!("未知的配置项 '{}'", key),
+            });
+        }
+
+        *self = updated;
+        Ok(())
+    }
+}
+
+/// 以 `[[monitor.roots]]` 表的形式输出单个根目录
+fn format_root(root: &RootConfig) -> String {
+    let mut lines = vec![
+        "[[monitor.roots]]".to_string(),
+        format!("root_path = {}", toml::Value::from(root.root_path.as_str())),
+    ];
+    if let Some(label) = &root.label {
+        lines.push(format!("label = {}", toml::Value::from(label.as_str())));
+    }
+    if let Some(check_hours) = root.check_hours {
+        lines.push(format!("check_hours = {}", check_hours));
+    }
+    if let Some(max_depth) = root.max_depth {
+        lines.push(format!("max_depth = {}", max_depth));
+    }
+    if let Some(search_latest) = root.search_latest_subdir_only {
+        lines.push(format!("search_latest_subdir_only = {}", search_latest));
+    }
+    lines.join("\n")
+}
+
+/// 把配置渲染为带注释的配置文件内容
+pub fn render_config(config: &Config) -> Result<String> {
+    let config_content = format!(
+        r#"# 文件监控配置
+[monitor]
+# 监控的根目录路径
+root_path = {}
+# 检查新文件的时间范围（小时）
+check_hours = {}
+# 扫描间隔（秒）
+scan_interval = {}
+# 最大扫描深度（可选）
+# 注释掉或删除此行表示无限制深度
+# 设置具体数值可限制扫描深度，例如: max_depth = 10
+{}
+# 是否跟随符号链接（可选，默认false）
+# 启用此选项会跟随符号链接到其目标位置
+{}
+# 时间戳类型（可选，默认modified）
+# modified: 使用文件修改时间（跨平台兼容性更好）
+# created: 使用文件创建时间（Windows上更准确，但Linux可能不支持）
+{}
+# 并行模式（可选，默认sync）
+# sync: 同步模式，等待所有任务完成
+# async: 异步模式，不等待任务完成
+# parallel: 并行模式，同时执行多个任务
+{}
+# 最大并行任务数（可选，默认CPU核心数）
+# async 模式下同时扫描的目录数上限，parallel 模式专用线程池的线程数
+# 网络存储上建议设置较小的值，例如: max_parallel_tasks = 4
+{}
+# 是否只搜索最新子目录（可选，默认false）
+# 启用此选项可大大节省扫描时间，但只会检查最新修改的子目录
+{}
+# 性能优化选项（不影响精确度）
+{}
+{}
+# 监控方式（可选，默认poll）
+# poll: 每隔 scan_interval 秒全量扫描
+# events: 订阅文件系统事件实时更新状态，周期扫描作为校正
+{}
+# 文件过滤（可选，通配符，同时作用于文件和目录）
+# include: 只计入匹配的文件；exclude: 跳过匹配的文件和目录
+{}
+{}
+# 录制检测方式（可选，默认mtime）
+# mtime: 时间范围内有新文件即视为录制
+# growth: 还要求最新文件在两次扫描之间至少增长 min_growth_bytes 字节
+{}
+{}
+# 检测正在写入文件的进程（可选，仅 Linux，默认false）
+# 有进程以写入方式打开目录中的文件时视为录制中，并在报告中显示进程
+{}
+# 软阈值（可选，分钟）
+# check_hours 内有新文件、但超过此时间没有新文件的目录显示为"录制可能已停止"
+{}
+# 查找真正最新的文件（可选，默认false）
+# 默认在第一个新文件处停止遍历；启用后遍历全部文件，报告中的最新文件更准确但扫描更慢
+{}
+# 增量扫描（可选，默认false）
+# 修改时间未变化的目录不再读取其中的文件，沿用上次扫描的结果
+# 每隔 full_rescan_minutes 分钟（默认60）强制全量扫描一次
+# scan_cache 设置后缓存会保存到文件，重启后继续使用
+{}
+{}
+{}
+# 单个目录的扫描时限（可选，秒，默认不限制）
+# 超时的目录（例如位于挂起的 NFS 挂载点上）报告为不可用，其余目录照常输出报告；
+# 连续超时的目录在之后的扫描中暂停扫描，暂停次数逐次加倍
+{}
+# 整次扫描的截止时间（可选，秒，默认不限制）
+# 到时仍未完成的目录报告为状态未知，报告立即输出；下次扫描时优先扫描这些目录
+{}
+# 遍历顺序（可选，默认readdir）
+# readdir: 按文件系统返回的顺序遍历
+# newest_first: 每一层先访问以日期命名的目录（按日期从新到旧）和最近修改的条目，
+#   在 2024/05/17 这类按日期分层的目录中能很快找到新文件
+{}
+# 多个根目录（可选）：每个根目录单独扫描，报告按根目录分组，设置后忽略上面的 root_path
+# 每个根目录可单独覆盖 check_hours、max_depth 和 search_latest_subdir_only
+{}
+
+[output]
+# 有新文件时的提示信息
+recording_message = {}
+# 没有新文件时的提示信息
+not_recording_message = {}
+# 报告格式（可选，默认text）
+# text: 文本报告；json/ndjson: 每次扫描输出一个 JSON 对象；csv: 每个目录一行
+{}
+# 其他状态的提示信息与文本报告图标（可选）
+{}
+
+[server]
+# HTTP 状态服务监听地址（可选，默认127.0.0.1:8080，使用 serve 子命令启动）
+{}
+"#,
+        toml::Value::from(config.monitor.root_path.as_str()),
+        config.monitor.check_hours,
+        config.monitor.scan_interval,
+        if let Some(depth) = config.monitor.max_depth {
+            format!("max_depth = {}", depth)
+        } else {
+            "# max_depth = 10".to_string()
+        },
+        if let Some(follow) = config.monitor.follow_links {
+            format!("follow_links = {}", follow)
+        } else {
+            "# follow_links = true".to_string()
+        },
+        if let Some(time_type) = &config.monitor.time_type {
+            format!("time_type = {}", toml::Value::from(time_type.as_str()))
+        } else {
+            "# time_type = \"created\"".to_string()
+        },
+        if let Some(parallel_mode) = &config.monitor.parallel_mode {
+            format!(
+                "parallel_mode = {}",
+                toml::Value::from(parallel_mode.as_str())
+            )
+        } else {
+            "# parallel_mode = \"sync\"".to_string()
+        },
+        if let Some(max_parallel_tasks) = config.monitor.max_parallel_tasks {
+            format!("max_parallel_tasks = {}", max_parallel_tasks)
+        } else {
+            "# max_parallel_tasks = 4".to_string()
+        },
+        if let Some(search_latest) = config.monitor.search_latest_subdir_only {
+            format!("search_latest_subdir_only = {}", search_latest)
+        } else {
+            "# search_latest_subdir_only = true".to_string()
+        },
+        if let Some(use_async_io) = config.monitor.use_async_io {
+            format!("use_async_io = {}", use_async_io)
+        } else {
+            "# use_async_io = false".to_string()
+        },
+        if let Some(batch_size) = config.monitor.batch_size {
+            format!("batch_size = {}", batch_size)
+        } else {
+            "# batch_size = 1000".to_string()
+        },
+        if let Some(watch_mode) = &config.monitor.watch_mode {
+            format!("watch_mode = {}", toml::Value::from(watch_mode.as_str()))
+        } else {
+            "# watch_mode = \"events\"".to_string()
+        },
+        if let Some(include) = &config.monitor.include {
+            format!("include = {}", toml::Value::from(include.clone()))
+        } else {
+            "# include = [\"*.ts\", \"*.flv\", \"*.mp4\"]".to_string()
+        },
+        if let Some(exclude) = &config.monitor.exclude {
+            format!("exclude = {}", toml::Value::from(exclude.clone()))
+        } else {
+            "# exclude = [\".DS_Store\", \"Thumbs.db\", \"*.tmp\", \"*.log\"]".to_string()
+        },
+        if let Some(detection_mode) = &config.monitor.detection_mode {
+            format!(
+                "detection_mode = {}",
+                toml::Value::from(detection_mode.as_str())
+            )
+        } else {
+            "# detection_mode = \"growth\"".to_string()
+        },
+        if let Some(min_growth_bytes) = config.monitor.min_growth_bytes {
+            format!("min_growth_bytes = {}", min_growth_bytes)
+        } else {
+            "# min_growth_bytes = 1024".to_string()
+        },
+        if let Some(detect) = config.monitor.detect_open_writers {
+            format!("detect_open_writers = {}", detect)
+        } else {
+            "# detect_open_writers = true".to_string()
+        },
+        if let Some(stale_minutes) = config.monitor.stale_minutes {
+            format!("stale_minutes = {}", stale_minutes)
+        } else {
+            "# stale_minutes = 10".to_string()
+        },
+        if let Some(find_newest) = config.monitor.find_newest {
+            format!("find_newest = {}", find_newest)
+        } else {
+            "# find_newest = true".to_string()
+        },
+        if let Some(incremental) = config.monitor.incremental {
+            format!("incremental = {}", incremental)
+        } else {
+            "# incremental = true".to_string()
+        },
+        if let Some(minutes) = config.monitor.full_rescan_minutes {
+            format!("full_rescan_minutes = {}", minutes)
+        } else {
+            "# full_rescan_minutes = 60".to_string()
+        },
+        if let Some(scan_cache) = &config.monitor.scan_cache {
+            format!("scan_cache = {}", toml::Value::from(scan_cache.as_str()))
+        } else {
+            "# scan_cache = \"file_monitor_scan_cache.json\"".to_string()
+        },
+        if let Some(scan_timeout) = config.monitor.scan_timeout {
+            format!("scan_timeout = {}", scan_timeout)
+        } else {
+            "# scan_timeout = 30".to_string()
+        },
+        if let Some(scan_deadline) = config.monitor.scan_deadline {
+            format!("scan_deadline = {}", scan_deadline)
+        } else {
+            "# scan_deadline = 50".to_string()
+        },
+        if let Some(traversal) = &config.monitor.traversal {
+            format!("traversal = {}", toml::Value::from(traversal.as_str()))
+        } else {
+            "# traversal = \"newest_first\"".to_string()
+        },
+        if config.monitor.roots().is_empty() {
+            "# [[monitor.roots]]\n# root_path = \"/mnt/disk1/recordings\"\n# label = \"disk1\"\n# check_hours = 1"
+                .to_string()
+        } else {
+            config
+                .monitor
+                .roots()
+                .iter()
+                .map(format_root)
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        toml::Value::from(config.output.recording_message.as_str()),
+        toml::Value::from(config.output.not_recording_message.as_str()),
+        if let Some(format) = &config.output.format {
+            format!("format = {}", toml::Value::from(format.as_str()))
+        } else {
+            "# format = \"json\"".to_string()
+        },
+        [
+            (
+                "stale_message",
+                &config.output.stale_message,
+                "录制可能已停止"
+            ),
+            (
+                "unavailable_message",
+                &config.output.unavailable_message,
+                "目录不可访问"
+            ),
+            ("error_message", &config.output.error_message, "扫描出错"),
+            (
+                "unknown_message",
+                &config.output.unknown_message,
+                "状态未知"
+            ),
+            ("recording_icon", &config.output.recording_icon, "[REC]"),
+            ("stale_icon", &config.output.stale_icon, "[OLD]"),
+            ("idle_icon", &config.output.idle_icon, "[---]"),
+            ("unavailable_icon", &config.output.unavailable_icon, "[N/A]"),
+            ("error_icon", &config.output.error_icon, "[ERR]"),
+            ("unknown_icon", &config.output.unknown_icon, "[???]"),
+        ]
+        .iter()
+        .map(|(key, value, example)| match value {
+            Some(value) => format!("{} = {}", key, toml::Value::from(value.as_str())),
+            None => format!("# {} = \"{}\"", key, example),
+        })
+        .collect::<Vec<_>>()
+        .join("\n"),
+        if let Some(listen) = config.server.as_ref().and_then(|s| s.listen.as_ref()) {
+            format!("listen = {}", toml::Value::from(listen.as_str()))
+        } else {
+            "# listen = \"0.0.0.0:8080\"".to_string()
+        }
+    );
+
+    // 保留已配置的历史、指标、钩子和 Webhook 通知
+    #[derive(Serialize)]
+    struct ExtraSections<'a> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        history: Option<&'a HistoryConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metrics: Option<&'a MetricsConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        hooks: Option<&'a HooksConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        notify: Option<&'a NotifyConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        directories: Option<&'a BTreeMap<String, DirectoryOverride>>,
+    }
+
+    let extra = ExtraSections {
+        history: config.history.as_ref(),
+        metrics: config.metrics.as_ref(),
+        hooks: config.hooks.as_ref(),
+        notify: config.notify.as_ref().filter(|n| !n.webhook.is_empty()),
+        directories: config.directories.as_ref().filter(|d| !d.is_empty()),
+    };
+    let mut config_content = config_content;
+    if extra.history.is_some()
+        || extra.metrics.is_some()
+        || extra.hooks.is_some()
+        || extra.notify.is_some()
+        || extra.directories.is_some()
+    {
+        config_content.push('\n');
+        config_content.push_str(&toml::to_string(&extra).context("无法序列化附加配置")?);
+    }
+    Ok(config_content)
+}
+
+/// 配置中的文件路径的实际位置，相对路径以配置文件所在目录为基准
+pub fn resolve_path(config_path: &str, path: &str) -> PathBuf {
+    Path::new(config_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(path)
+}
+
+pub fn save_config_safely(config_path: &str, config: &Config) -> Result<()> {
+    let config_content = render_config(config)?;
+
+    // 原子性写入：先写入临时文件，然后重命名
+    let temp_path = format!("{}.tmp", config_path);
+    let backup_path = format!("{}.backup", config_path);
+
+    // 如果配置文件已存在，先创建备份
+    if Path::new(config_path).exists() {
+        fs::copy(config_path, &backup_path)
+            .with_context(|| format!("无法创建配置文件备份: {}", backup_path))?;
+    }
+
+    // 写入临时文件
+    fs::write(&temp_path, &config_content)
+        .with_context(|| format!("无法写入临时配置文件: {}", temp_path))?;
+
+    // 验证临时文件内容
+    let verification_content = fs::read_to_string(&temp_path)
+        .with_context(|| format!("无法验证临时配置文件: {}", temp_path))?;
+
+    if verification_content != config_content {
+        // 清理临时文件
+        let _ = fs::remove_file(&temp_path);
+        return Err(anyhow::anyhow!("配置文件写入验证失败"));
+    }
+
+    // 原子性重命名
+    fs::rename(&temp_path, config_path)
+        .with_context(|| format!("无法完成配置文件更新: {}", config_path))?;
+
+    println!("[配置] 配置文件已安全保存，备份文件: {}", backup_path);
+    Ok(())
+}
+
+pub fn create_default_config_safely(config_path: &str, monitor_path: &str) -> Result<()> {
+    let default_config = format!(
+        r#"# 文件监控配置
+[monitor]
+# 监控的根目录路径
+root_path = {}
+# 检查新文件的时间范围（小时）
+check_hours = 2
+# 扫描间隔（秒）
+scan_interval = 3600
+# 最大扫描深度（可选）
+# 注释掉或删除此行表示无限制深度
+# 设置具体数值可限制扫描深度，例如: max_depth = 10
+# max_depth = 10
+# 是否跟随符号链接（可选，默认false）
+# 启用此选项会跟随符号链接到其目标位置
+# follow_links = true
+# 时间戳类型（可选，默认modified）
+# modified: 使用文件修改时间（跨平台兼容性更好）
+# created: 使用文件创建时间（Windows上更准确，但Linux可能不支持）
+# time_type = "created"
+# 并行模式（可选，默认sync）
+# sync: 同步模式，等待所有任务完成
+# async: 异步模式，不等待任务完成
+# parallel: 并行模式，同时执行多个任务
+parallel_mode = "async"
+# 最大并行任务数（可选，默认CPU核心数）
+# async 模式下同时扫描的目录数上限，parallel 模式专用线程池的线程数
+# 网络存储上建议设置较小的值，例如: max_parallel_tasks = 4
+# max_parallel_tasks = 4
+# 是否只搜索最新子目录（可选，默认false）
+# 启用此选项可大大节省扫描时间，但只会检查最新修改的子目录
+search_latest_subdir_only = true
+# 性能优化选项（不影响精确度）
+use_async_io = true
+batch_size = 100
+# 监控方式（可选，默认poll）
+# poll: 每隔 scan_interval 秒全量扫描
+# events: 订阅文件系统事件实时更新状态，周期扫描作为校正
+# watch_mode = "events"
+# 文件过滤（可选，通配符，同时作用于文件和目录）
+# include: 只计入匹配的文件；exclude: 跳过匹配的文件和目录
+# include = ["*.ts", "*.flv", "*.mp4"]
+# exclude = [".DS_Store", "Thumbs.db", "*.tmp", "*.log"]
+# 录制检测方式（可选，默认mtime）
+# mtime: 时间范围内有新文件即视为录制
+# growth: 还要求最新文件在两次扫描之间至少增长 min_growth_bytes 字节
+# detection_mode = "growth"
+# min_growth_bytes = 1024
+# 检测正在写入文件的进程（可选，仅 Linux，默认false）
+# 有进程以写入方式打开目录中的文件时视为录制中，并在报告中显示进程
+# detect_open_writers = true
+# 软阈值（可选，分钟）
+# check_hours 内有新文件、但超过此时间没有新文件的目录显示为"录制可能已停止"
+# stale_minutes = 10
+# 查找真正最新的文件（可选，默认false）
+# 默认在第一个新文件处停止遍历；启用后遍历全部文件，报告中的最新文件更准确但扫描更慢
+# find_newest = true
+# 增量扫描（可选，默认false）
+# 修改时间未变化的目录不再读取其中的文件，沿用上次扫描的结果
+# 每隔 full_rescan_minutes 分钟（默认60）强制全量扫描一次
+# scan_cache 设置后缓存会保存到文件，重启后继续使用
+# detection_mode = "growth" 时不使用增量扫描
+# incremental = true
+# full_rescan_minutes = 60
+# scan_cache = "file_monitor_scan_cache.json"
+# 单个目录的扫描时限（可选，秒，默认不限制）
+# 超时的目录（例如位于挂起的 NFS 挂载点上）报告为不可用，其余目录照常输出报告；
+# 连续超时的目录在之后的扫描中暂停扫描，暂停次数逐次加倍
+# scan_timeout = 30
+# 整次扫描的截止时间（可选，秒，默认不限制）
+# 到时仍未完成的目录报告为状态未知，报告立即输出；下次扫描时优先扫描这些目录
+# scan_deadline = 50
+# 遍历顺序（可选，默认readdir）
+# readdir: 按文件系统返回的顺序遍历
+# newest_first: 每一层先访问以日期命名的目录（按日期从新到旧）和最近修改的条目，
+#   在 2024/05/17 这类按日期分层的目录中能很快找到新文件
+# traversal = "newest_first"
+# 多个根目录（可选）：每个根目录单独扫描，报告按根目录分组，设置后忽略上面的 root_path
+# 每个根目录可单独覆盖 check_hours、max_depth 和 search_latest_subdir_only
+# [[monitor.roots]]
+# root_path = "/mnt/disk1/recordings"
+# label = "disk1"
+# check_hours = 1
+
+[output]
+# 有新文件时的提示信息
+recording_message = "正在录制"
+# 没有新文件时的提示信息
+not_recording_message = "未录制"
+# 报告格式（可选，默认text）
+# text: 文本报告；json/ndjson: 每次扫描输出一个 JSON 对象；csv: 每个目录一行
+# format = "json"
+# 其他状态的提示信息与文本报告图标（可选）
+# stale_message = "录制可能已停止"
+# unavailable_message = "目录不可访问"
+# error_message = "扫描出错"
+# unknown_message = "状态未知"
+# recording_icon = "[REC]"
+# stale_icon = "[OLD]"
+# idle_icon = "[---]"
+# unavailable_icon = "[N/A]"
+# error_icon = "[ERR]"
+# unknown_icon = "[???]"
+
+[server]
+# HTTP 状态服务监听地址（可选，默认127.0.0.1:8080，使用 serve 子命令启动）
+# listen = "0.0.0.0:8080"
+
+# 扫描历史（可选，默认关闭，使用 history 子命令查看统计）
+# [history]
+# enabled = true
+# path = "file_monitor_history.db"
+# retention_days = 90
+
+# Prometheus 指标（可选，serve 子命令启动时也可通过 /metrics 获取）
+# 每次扫描后原子性写入 .prom 文件，供 node_exporter textfile collector 读取
+# [metrics]
+# textfile = "/var/lib/node_exporter/textfile_collector/file_monitor.prom"
+
+# 状态变化时的 Webhook 通知（可选，可配置多个）
+# [[notify.webhook]]
+# url = "http://127.0.0.1:9000/hook"
+# headers = {{ Authorization = "Bearer token" }}
+# payload_template = '{{"text": "{{{{directory}}}}: {{{{message}}}}"}}'
+# timeout = 10
+# retries = 3
+# retry_delay = 1
+
+# 状态变化时执行的命令（可选）
+# 可用环境变量: FM_DIR FM_PATH FM_STATUS FM_EVENT FM_TIMESTAMP
+# [hooks]
+# on_start = "/usr/local/bin/recording-started.sh"
+# on_stop = "/usr/local/bin/recording-stopped.sh"
+# timeout = 30
+
+# 按二级目录覆盖监控参数（可选，键为目录名或通配符，完全相同的目录名优先）
+# 可覆盖 check_hours、time_type、search_latest_subdir_only、max_depth 和各状态的提示信息
+# [directories."hls_*"]
+# check_hours = 1
+# [directories."archive"]
+# check_hours = 24
+# not_recording_message = "今日未归档"
+"#,
+        toml::Value::from(monitor_path)
+    );
+
+    // 检查文件是否已存在，避免意外覆盖
+    if Path::new(config_path).exists() {
+        return Err(anyhow::anyhow!("配置文件已存在，拒绝覆盖: {}", config_path));
+    }
+
+    // 原子性写入
+    let temp_path = format!("{}.tmp", config_path);
+
+    fs::write(&temp_path, &default_config)
+        .with_context(|| format!("无法创建临时配置文件: {}", temp_path))?;
+
+    // 验证文件内容
+    let verification_content = fs::read_to_string(&temp_path)
+        .with_context(|| format!("无法验证临时配置文件: {}", temp_path))?;
+
+    if verification_content != default_config {
+        // 清理临时文件
+        let _ = fs::remove_file(&temp_path);
+        return Err(anyhow::anyhow!("配置文件创建验证失败"));
+    }
+
+    // 原子性重命名
+    fs::rename(&temp_path, config_path)
+        .with_context(|| format!("无法完成配置文件创建: {}", config_path))?;
+
+    Ok(())
+}
@@ -0,0 +1,274 @@
+//! `doctor` 子命令：检查配置和运行环境中的常见问题
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::{resolve_path, Config};
+use crate::history;
+use crate::scanner::Scanner;
+use crate::server;
+use crate::watcher::EventWatcher;
+
+/// 单项诊断的结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "[成功]",
+            Self::Warning => "[警告]",
+            Self::Error => "[错误]",
+        })
+    }
+}
+
+/// 一项诊断结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub severity: Severity,
+    /// 检查项名称
+    pub item: String,
+    pub detail: String,
+}
+
+impl Diagnosis {
+    fn new(severity: Severity, item: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            severity,
+            item: item.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// 依次检查配置文件、根目录、时间戳类型、事件监控、写入进程检测、历史数据库、
+/// 扫描缓存、指标文件和状态服务地址，最后实际扫描一次
+///
+/// 只读取文件系统，不会创建历史数据库或指标文件。
+pub async fn diagnose(config_path: &str, settings: &[String]) -> Vec<Diagnosis> {
+    let mut results = Vec::new();
+
+    let config = match Config::load(config_path, settings) {
+        Ok(config) => {
+            results.push(Diagnosis::new(
+                Severity::Ok,
+                "配置文件",
+                format!("已读取 {}", config_path),
+            ));
+            config
+        }
+        Err(e) => {
+            results.push(Diagnosis::new(
+                Severity::Error,
+                "配置文件",
+                format!("{:#}", e),
+            ));
+            return results;
+        }
+    };
+
+    let scanner = Scanner::from_config(&config);
+    for root in scanner.root_paths() {
+        results.push(check_root(root));
+    }
+
+    if config.monitor.time_type.as_deref() == Some("created") {
+        for root in scanner.root_paths() {
+            results.push(match fs::metadata(root).and_then(|m| m.created()) {
+                Ok(_) => Diagnosis::new(Severity::Ok, "创建时间", "文件系统支持文件创建时间"),
+                Err(e) => Diagnosis::new(
+                    Severity::Error,
+                    "创建时间",
+                    format!(
+                        "{} 不支持文件创建时间（{}），请改用 time_type = \"modified\"",
+                        root.display(),
+                        e
+                    ),
+                ),
+            });
+        }
+    }
+
+    if config.monitor.watch_mode.as_deref() == Some("events") {
+        results.push(check_event_watcher(&config));
+    }
+
+    if config.monitor.detect_open_writers.unwrap_or(false) {
+        results.push(check_open_writers());
+    }
+
+    let history_config = config.history.clone().unwrap_or_default();
+    if history_config.enabled.unwrap_or(false) {
+        let path = history::history_path(config_path, history_config.path.as_deref());
+        results.push(check_writable_file("扫描历史", &path));
+    }
+
+    if let Some(scan_cache) = config
+        .monitor
+        .scan_cache
+        .as_deref()
+        .filter(|_| config.monitor.incremental_enabled())
+    {
+        let path = resolve_path(config_path, scan_cache);
+        results.push(check_writable_file("扫描缓存", &path));
+    }
+
+    if let Some(textfile) = config.metrics.as_ref().and_then(|m| m.textfile.as_deref()) {
+        let path = resolve_path(config_path, textfile);
+        results.push(check_writable_file("指标文件", &path));
+    }
+
+    let listen = config
+        .server
+        .as_ref()
+        .and_then(|s| s.listen.as_deref())
+        .unwrap_or(server::DEFAULT_LISTEN);
+    results.push(match listen.to_socket_addrs() {
+        Ok(_) => Diagnosis::new(Severity::Ok, "状态服务", format!("监听地址 {}", listen)),
+        Err(e) => Diagnosis::new(
+            Severity::Error,
+            "状态服务",
+            format!("无效的监听地址 {}: {}", listen, e),
+        ),
+    });
+
+    results.push(match scanner.scan().await {
+        Ok(report) => Diagnosis::new(
+            Severity::Ok,
+            "扫描",
+            format!(
+                "{} 个目录中 {} 个正在录制，读取了 {} 个文件，耗时 {:.2}秒",
+                report.directories.len(),
+                report.recording_count(),
+                report.files_examined(),
+                report.scan_duration.as_secs_f64()
+            ),
+        ),
+        Err(e) => Diagnosis::new(Severity::Error, "扫描", format!("{:#}", e)),
+    });
+
+    results
+}
+
+/// 输出诊断结果和汇总
+pub fn write_diagnostics(out: &mut impl Write, results: &[Diagnosis]) -> io::Result<()> {
+    for diagnosis in results {
+        writeln!(
+            out,
+            "{} {}: {}",
+            diagnosis.severity, diagnosis.item, diagnosis.detail
+        )?;
+    }
+
+    let count = |severity| results.iter().filter(|d| d.severity == severity).count();
+    writeln!(out)?;
+    writeln!(
+        out,
+        "诊断完成: {} 项正常, {} 项警告, {} 项错误",
+        count(Severity::Ok),
+        count(Severity::Warning),
+        count(Severity::Error)
+    )
+}
+
+fn check_root(root: &Path) -> Diagnosis {
+    let item = format!("监控目录 {}", root.display());
+    if !root.is_dir() {
+        return Diagnosis::new(Severity::Error, item, "目录不存在或不是目录");
+    }
+
+    let start = Instant::now();
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) => return Diagnosis::new(Severity::Error, item, format!("无法读取: {}", e)),
+    };
+    let directories = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .count();
+    let elapsed = start.elapsed();
+
+    let detail = format!(
+        "{} 个二级目录，读取耗时 {:.2}毫秒",
+        directories,
+        elapsed.as_secs_f64() * 1000.0
+    );
+    if elapsed.as_secs() > 1 {
+        Diagnosis::new(
+            Severity::Warning,
+            item,
+            format!("{}，可能存在网络延迟或挂载问题", detail),
+        )
+    } else if directories == 0 {
+        Diagnosis::new(Severity::Warning, item, "未找到任何二级目录")
+    } else {
+        Diagnosis::new(Severity::Ok, item, detail)
+    }
+}
+
+fn check_event_watcher(config: &Config) -> Diagnosis {
+    if !config.monitor.roots().is_empty() {
+        return Diagnosis::new(
+            Severity::Warning,
+            "事件监控",
+            "暂不支持多个根目录，将回退到周期扫描",
+        );
+    }
+    match EventWatcher::new(Path::new(&config.monitor.root_path)) {
+        Ok(_) => Diagnosis::new(Severity::Ok, "事件监控", "可以订阅文件系统事件"),
+        Err(e) => Diagnosis::new(
+            Severity::Warning,
+            "事件监控",
+            format!("{:#}，将回退到周期扫描", e),
+        ),
+    }
+}
+
+fn check_open_writers() -> Diagnosis {
+    if !cfg!(target_os = "linux") {
+        return Diagnosis::new(Severity::Warning, "写入进程检测", "仅支持 Linux");
+    }
+    match fs::read_dir("/proc") {
+        Ok(_) => Diagnosis::new(Severity::Ok, "写入进程检测", "可以读取 /proc"),
+        Err(e) => Diagnosis::new(
+            Severity::Warning,
+            "写入进程检测",
+            format!("无法读取 /proc: {}", e),
+        ),
+    }
+}
+
+/// 文件已存在时检查是否可写，否则检查所在目录是否存在且可写
+fn check_writable_file(item: &str, path: &Path) -> Diagnosis {
+    let target = if path.exists() {
+        path
+    } else {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    };
+
+    match fs::metadata(target) {
+        Ok(metadata) if metadata.permissions().readonly() => Diagnosis::new(
+            Severity::Warning,
+            item,
+            format!("{} 为只读", target.display()),
+        ),
+        Ok(_) => Diagnosis::new(Severity::Ok, item, path.display().to_string()),
+        Err(e) => Diagnosis::new(
+            Severity::Warning,
+            item,
+            format!("{} 不可用: {}", target.display(), e),
+        ),
+    }
+}
@@ -0,0 +1,97 @@
+//! `include` / `exclude` 通配符过滤
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use log::warn;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::MonitorConfig;
+
+/// 按 `[monitor] include` / `exclude` 决定哪些文件和目录参与新文件检测
+///
+/// 模式同时与每一级名称和相对于二级目录的路径匹配，例如 `*.tmp`、
+/// `.DS_Store`、`logs` 或 `session/**/*.log`。被排除的目录整体跳过；
+/// 设置了 `include` 时，只有自身或上级目录匹配的文件才会被计入。
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: build_set(include).context("无效的 include 模式")?,
+            exclude: build_set(exclude).context("无效的 exclude 模式")?,
+        })
+    }
+
+    /// 根据监控配置创建过滤器；模式无效时记录警告并忽略过滤
+    pub fn from_config(config: &MonitorConfig) -> Self {
+        Self::try_from_config(config).unwrap_or_else(|e| {
+            warn!("{:#}，已忽略文件过滤设置", e);
+            Self::default()
+        })
+    }
+
+    pub fn try_from_config(config: &MonitorConfig) -> Result<Self> {
+        Self::new(
+            config.include.as_deref().unwrap_or_default(),
+            config.exclude.as_deref().unwrap_or_default(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /// `path` 本身或其位于 `base` 之下的任一上级目录是否被排除
+    pub fn is_excluded(&self, base: &Path, path: &Path) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|set| any_level_matches(set, base, path))
+    }
+
+    /// 文件是否应参与新文件检测
+    pub fn matches_file(&self, base: &Path, path: &Path) -> bool {
+        if self.is_excluded(base, path) {
+            return false;
+        }
+        self.include
+            .as_ref()
+            .is_none_or(|set| any_level_matches(set, base, path))
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob: Glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("'{}'", pattern))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// 依次检查相对路径的每一级名称和每一级前缀路径
+fn any_level_matches(set: &GlobSet, base: &Path, path: &Path) -> bool {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    let mut prefix = PathBuf::new();
+
+    for component in relative.components() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        prefix.push(name);
+        if set.is_match(name) || set.is_match(&prefix) {
+            return true;
+        }
+    }
+    false
+}
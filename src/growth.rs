@@ -0,0 +1,95 @@
+//! 基于文件增长的录制检测
+
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// 每个目录跟踪的最新文件数量
+pub const GROWTH_SAMPLE_FILES: usize = 8;
+
+/// 未配置 `min_growth_bytes` 时的最小增长字节数
+pub const DEFAULT_MIN_GROWTH_BYTES: u64 = 1;
+
+/// 一次扫描中观察到的文件大小与修改时间
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSample {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    taken_at: SystemTime,
+    sizes: HashMap<PathBuf, u64>,
+}
+
+/// 记住每个目录中最新文件的大小，判断两次扫描之间是否有文件在增长
+#[derive(Debug)]
+pub struct GrowthTracker {
+    min_growth_bytes: u64,
+    snapshots: Mutex<HashMap<PathBuf, Snapshot>>,
+}
+
+impl GrowthTracker {
+    pub fn new(min_growth_bytes: u64) -> Self {
+        Self {
+            min_growth_bytes,
+            snapshots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn min_growth_bytes(&self) -> u64 {
+        self.min_growth_bytes
+    }
+
+    /// 记录目录的最新文件，并与上一次扫描比较
+    ///
+    /// 有文件增长至少 `min_growth_bytes` 字节时返回 `Some(true)`；上一次扫描之后
+    /// 新出现的文件从 0 字节算起。目录第一次被观察时没有基线，返回 `None`。
+    pub fn observe(&self, dir_path: &Path, samples: Vec<FileSample>) -> Option<bool> {
+        let snapshot = Snapshot {
+            taken_at: SystemTime::now(),
+            sizes: samples
+                .iter()
+                .map(|sample| (sample.path.clone(), sample.size))
+                .collect(),
+        };
+
+        let previous = self
+            .snapshots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(dir_path.to_path_buf(), snapshot)?;
+
+        let grown = samples.iter().find(|sample| {
+            let previous_size = match previous.sizes.get(&sample.path) {
+                Some(&size) => size,
+                // 上次扫描之后才写入的新文件
+                None if sample.modified > previous.taken_at => 0,
+                None => return false,
+            };
+            sample.size >= previous_size.saturating_add(self.min_growth_bytes)
+        });
+
+        if let Some(sample) = grown {
+            debug!(
+                "文件仍在增长: {} ({} 字节)",
+                sample.path.display(),
+                sample.size
+            );
+        }
+        Some(grown.is_some())
+    }
+
+    /// 只保留指定目录的基线，丢弃已被删除或改名的目录，避免长期运行时不断累积
+    pub fn retain<'a>(&self, directories: impl IntoIterator<Item = &'a Path>) {
+        let directories: HashSet<_> = directories.into_iter().collect();
+        self.snapshots
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|path, _| directories.contains(path.as_path()));
+    }
+}
@@ -0,0 +1,344 @@
+//! 扫描结果的持久化历史与录制时长统计
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config;
+use crate::report::format_duration;
+use crate::scanner::ScanReport;
+
+/// 历史数据库的默认文件名（位于配置文件所在目录）
+pub const DEFAULT_HISTORY_FILE: &str = "file_monitor_history.db";
+
+/// 相邻两次扫描的间隔超过记录时扫描间隔的这个倍数时，视为监控中断
+pub const GAP_FACTOR: i64 = 2;
+
+/// 计算历史数据库路径，未配置时使用 [`DEFAULT_HISTORY_FILE`]
+pub fn history_path(config_path: &str, configured: Option<&str>) -> PathBuf {
+    config::resolve_path(config_path, configured.unwrap_or(DEFAULT_HISTORY_FILE))
+}
+
+/// 一段连续录制
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingSession {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// 截至最后一次扫描仍在录制
+    pub ongoing: bool,
+}
+
+impl RecordingSession {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// 单个目录在统计范围内的录制历史
+#[derive(Debug, Clone)]
+pub struct DirectoryHistory {
+    pub directory: String,
+    pub sessions: Vec<RecordingSession>,
+    /// 录制总时长
+    pub recorded: Duration,
+    /// 统计范围内实际有扫描数据覆盖的时长
+    pub observed: Duration,
+}
+
+impl DirectoryHistory {
+    /// 录制时长占有效观测时长的百分比
+    pub fn uptime_percent(&self) -> f64 {
+        if self.observed <= Duration::zero() {
+            return 0.0;
+        }
+        self.recorded.num_milliseconds() as f64 / self.observed.num_milliseconds() as f64 * 100.0
+    }
+}
+
+/// 基于 SQLite 的扫描历史存储，每次扫描为每个目录记录一行
+#[derive(Debug)]
+pub struct HistoryStore {
+    conn: Mutex<Connection>,
+    /// 记录保留时长，超过的记录在写入时删除
+    retention: Option<Duration>,
+    /// 与每条记录一起保存的扫描间隔（秒），用于识别监控中断
+    scan_interval: Option<u64>,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("无法打开历史数据库: {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    /// 打开仅存在于内存中的历史存储
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS scan_results (
+                 observed_at INTEGER NOT NULL,
+                 directory   TEXT    NOT NULL,
+                 recording   INTEGER NOT NULL,
+                 scan_interval INTEGER
+             );
+             CREATE INDEX IF NOT EXISTS idx_scan_results_dir_time
+                 ON scan_results (directory, observed_at);
+             CREATE INDEX IF NOT EXISTS idx_scan_results_time
+                 ON scan_results (observed_at);",
+        )
+        .context("无法初始化历史数据库")?;
+
+        // 早期版本的数据库没有 scan_interval 列，这些记录不做中断检测
+        let has_interval: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('scan_results') WHERE name = 'scan_interval'",
+                [],
+                |row| row.get(0),
+            )
+            .context("无法初始化历史数据库")?;
+        if !has_interval {
+            conn.execute_batch("ALTER TABLE scan_results ADD COLUMN scan_interval INTEGER")
+                .context("无法升级历史数据库")?;
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            retention: None,
+            scan_interval: None,
+        })
+    }
+
+    /// 只保留最近 `days` 天的记录；`None` 表示不删除
+    pub fn with_retention_days(mut self, days: Option<u64>) -> Self {
+        self.retention = days.map(|days| Duration::days(days as i64));
+        self
+    }
+
+    /// 与之后的记录一起保存扫描间隔；相邻记录相隔超过 `GAP_FACTOR` 倍间隔时不计入统计
+    pub fn with_scan_interval(mut self, seconds: u64) -> Self {
+        self.scan_interval = Some(seconds);
+        self
+    }
+
+    /// 记录一次扫描中每个目录的状态
+    ///
+    /// 同一次扫描再次记录时（例如事件模式下报告更新后重新发布）替换之前的记录。
+    pub fn record(&self, report: &ScanReport) -> Result<()> {
+        let observed_at = report.scanned_at.timestamp();
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM scan_results WHERE observed_at = ?1",
+            params![observed_at],
+        )?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO scan_results (observed_at, directory, recording, scan_interval)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            // 扫描失败的目录无法判断是否在录制，不计入历史
+            for dir in report.directories.iter().filter(|d| !d.status.is_failure()) {
+                stmt.execute(params![
+                    observed_at,
+                    dir.name,
+                    dir.is_recording(),
+                    self.scan_interval
+                ])?;
+            }
+        }
+        if let Some(retention) = self.retention {
+            tx.execute(
+                "DELETE FROM scan_results WHERE observed_at < ?1",
+                params![observed_at - retention.num_seconds()],
+            )?;
+        }
+        tx.commit().context("无法写入扫描历史")
+    }
+
+    /// 统计 `[since, until]` 范围内各目录的录制会话、总时长和在线率
+    pub fn summarize(
+        &self,
+        since: DateTime<Local>,
+        until: DateTime<Local>,
+        directory: Option<&str>,
+    ) -> Result<Vec<DirectoryHistory>> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut samples: BTreeMap<String, Vec<Sample>> = BTreeMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT directory, observed_at, recording, scan_interval FROM scan_results
+                 WHERE observed_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR directory = ?3)
+                 ORDER BY directory, observed_at",
+            )?;
+            let rows = stmt.query_map(
+                params![since.timestamp(), until.timestamp(), directory],
+                |row| {
+                    let sample = Sample {
+                        at: row.get(1)?,
+                        recording: row.get(2)?,
+                        interval: row.get(3)?,
+                    };
+                    Ok((row.get::<_, String>(0)?, sample))
+                },
+            )?;
+            for row in rows {
+                let (dir, sample) = row?;
+                samples.entry(dir).or_default().push(sample);
+            }
+        }
+
+        let mut previous_stmt = conn.prepare(
+            "SELECT observed_at, recording, scan_interval FROM scan_results
+             WHERE directory = ?1 AND observed_at < ?2
+             ORDER BY observed_at DESC LIMIT 1",
+        )?;
+
+        let mut histories = Vec::new();
+        for (dir, mut dir_samples) in samples {
+            // 范围开始之前的最后一次状态决定起点是否处于录制中
+            let previous = previous_stmt
+                .query_row(params![dir, since.timestamp()], |row| {
+                    Ok(Sample {
+                        at: row.get(0)?,
+                        recording: row.get(1)?,
+                        interval: row.get(2)?,
+                    })
+                })
+                .optional()?;
+            if let Some(previous) = previous {
+                dir_samples.insert(0, previous);
+            }
+
+            histories.push(build_history(dir, &dir_samples, since.timestamp()));
+        }
+
+        Ok(histories)
+    }
+}
+
+/// 某个目录的一条扫描记录
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: i64,
+    recording: bool,
+    /// 记录时的扫描间隔（秒）
+    interval: Option<i64>,
+}
+
+impl Sample {
+    /// 与下一条记录之间是否出现了监控中断（程序未运行或扫描失败）
+    fn has_gap_before(&self, next: &Sample) -> bool {
+        self.interval
+            .is_some_and(|interval| next.at - self.at > interval * GAP_FACTOR)
+    }
+}
+
+/// 按相邻记录之间的区间统计录制会话；`since` 之前的部分不计入，监控中断的区间既不算
+/// 录制也不算观测，中断前的录制会话在最后一条记录处结束
+fn build_history(directory: String, samples: &[Sample], since: i64) -> DirectoryHistory {
+    let mut sessions = Vec::new();
+    let mut open: Option<i64> = None;
+    let mut observed = 0;
+
+    for pair in samples.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        let start = prev.at.max(since);
+        if prev.has_gap_before(next) {
+            if let Some(session_start) = open.take() {
+                sessions.push(session(session_start, start, false));
+            }
+            continue;
+        }
+
+        observed += next.at - start;
+        if prev.recording {
+            let session_start = *open.get_or_insert(start);
+            if !next.recording {
+                sessions.push(session(session_start, next.at, false));
+                open = None;
+            }
+        }
+    }
+
+    if let Some(last) = samples.last() {
+        if last.recording {
+            let session_start = *open.get_or_insert(last.at.max(since));
+            sessions.push(session(session_start, last.at, true));
+        }
+    }
+
+    let recorded = sessions
+        .iter()
+        .fold(Duration::zero(), |total, s| total + s.duration());
+
+    DirectoryHistory {
+        directory,
+        sessions,
+        recorded,
+        observed: Duration::seconds(observed),
+    }
+}
+
+fn session(start: i64, end: i64, ongoing: bool) -> RecordingSession {
+    let to_local = |ts: i64| Local.timestamp_opt(ts, 0).single().unwrap_or_default();
+    RecordingSession {
+        start: to_local(start),
+        end: to_local(end),
+        ongoing,
+    }
+}
+
+/// 以文本形式输出历史统计
+pub fn write_history_report(
+    out: &mut impl Write,
+    histories: &[DirectoryHistory],
+    since: DateTime<Local>,
+    until: DateTime<Local>,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "\n=== [历史] 录制统计 [{} ~ {}] ===",
+        since.format("%Y-%m-%d %H:%M:%S"),
+        until.format("%Y-%m-%d %H:%M:%S")
+    )?;
+
+    if histories.is_empty() {
+        writeln!(out, "[警告] 该时间范围内没有扫描记录")?;
+    }
+
+    for history in histories {
+        writeln!(
+            out,
+            "目录 '{}': 录制 {} 次，共 {}，在线率 {:.1}%",
+            history.directory,
+            history.sessions.len(),
+            format_duration(history.recorded),
+            history.uptime_percent()
+        )?;
+        for session in &history.sessions {
+            let end = if session.ongoing {
+                "进行中".to_string()
+            } else {
+                session.end.format("%Y-%m-%d %H:%M:%S").to_string()
+            };
+            writeln!(
+                out,
+                "  - {} ~ {} ({})",
+                session.start.format("%Y-%m-%d %H:%M:%S"),
+                end,
+                format_duration(session.duration())
+            )?;
+        }
+    }
+
+    writeln!(out, "=======================================\n")
+}
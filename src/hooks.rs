@@ -0,0 +1,127 @@
+//! 状态变化时执行的外部命令（钩子脚本）
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::config::HooksConfig;
+use crate::transition::{StatusTransition, TransitionKind};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// 根据 `[hooks]` 配置在目录开始/停止录制时执行命令
+#[derive(Debug, Clone, Default)]
+pub struct HookRunner {
+    config: Arc<HooksConfig>,
+}
+
+impl HookRunner {
+    pub fn new(config: HooksConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.config.on_start.is_none() && self.config.on_stop.is_none()
+    }
+
+    /// 在后台依次执行钩子，不阻塞扫描循环
+    pub fn spawn_run(&self, transitions: Vec<StatusTransition>) {
+        if self.is_empty() || transitions.is_empty() {
+            return;
+        }
+
+        let runner = self.clone();
+        tokio::spawn(async move {
+            for transition in &transitions {
+                if let Err(e) = runner.run(transition).await {
+                    error!("钩子执行失败: {:#}", e);
+                }
+            }
+        });
+    }
+
+    /// 执行与状态变化对应的钩子；未配置该钩子时返回 `Ok(None)`
+    pub async fn run(&self, transition: &StatusTransition) -> Result<Option<ExitStatus>> {
+        let (name, command, timeout) = match transition.kind {
+            TransitionKind::RecordingStarted => (
+                "on_start",
+                &self.config.on_start,
+                self.config.on_start_timeout,
+            ),
+            TransitionKind::RecordingStopped => {
+                ("on_stop", &self.config.on_stop, self.config.on_stop_timeout)
+            }
+        };
+        let Some(command) = command else {
+            return Ok(None);
+        };
+        let timeout = Duration::from_secs(
+            timeout
+                .or(self.config.timeout)
+                .unwrap_or(DEFAULT_TIMEOUT_SECS),
+        );
+
+        info!(
+            "执行钩子 {} (目录 '{}'): {}",
+            name, transition.directory, command
+        );
+
+        let child = shell_command(command)
+            .env("FM_DIR", &transition.directory)
+            .env("FM_PATH", &transition.path)
+            .env("FM_STATUS", transition.status.as_str())
+            .env("FM_EVENT", transition.kind.as_str())
+            .env("FM_TIMESTAMP", transition.at.to_rfc3339())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("无法启动钩子 {}: {}", name, command))?;
+
+        // 超时后丢弃 future 会通过 kill_on_drop 结束子进程
+        let output = tokio::time::timeout(timeout, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "钩子 {} 执行超时（{} 秒）: {}",
+                    name,
+                    timeout.as_secs(),
+                    command
+                )
+            })?
+            .with_context(|| format!("等待钩子 {} 结束失败", name))?;
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            info!("[钩子 {}] {}", name, line);
+        }
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            warn!("[钩子 {}] {}", name, line);
+        }
+
+        if output.status.success() {
+            info!("钩子 {} 执行完成", name);
+        } else {
+            warn!("钩子 {} 以非零状态退出: {}", name, output.status);
+        }
+
+        Ok(Some(output.status))
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
@@ -0,0 +1,59 @@
+//! 文件监控库：扫描根目录下的二级目录，判断其中是否有新文件
+//!
+//! ```no_run
+//! use file_monitor::{MonitorConfig, Scanner};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let scanner = Scanner::new(MonitorConfig::new("/path/to/recordings"));
+//! let report = scanner.scan().await?;
+//! for dir in &report.directories {
+//!     println!("{}: {}", dir.name, dir.status);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod backoff;
+pub mod cache;
+pub mod check;
+pub mod config;
+pub mod doctor;
+pub mod filter;
+pub mod growth;
+pub mod history;
+pub mod hooks;
+pub mod metrics;
+pub mod overrides;
+pub mod reload;
+pub mod report;
+pub mod scanner;
+pub mod server;
+pub mod status;
+pub mod transition;
+pub mod traversal;
+pub mod validation;
+pub mod watcher;
+pub mod webhook;
+pub mod writers;
+
+pub use cache::ScanCache;
+pub use check::{CheckResult, CheckRules, CheckState};
+pub use config::{
+    Config, DirectoryOverride, HistoryConfig, HooksConfig, MetricsConfig, MonitorConfig,
+    OutputConfig, RootConfig, WebhookConfig,
+};
+pub use filter::PathFilter;
+pub use growth::{FileSample, GrowthTracker};
+pub use history::{DirectoryHistory, HistoryStore, RecordingSession};
+pub use hooks::HookRunner;
+pub use metrics::MetricsRegistry;
+pub use overrides::DirectoryOverrides;
+pub use reload::{ConfigWatcher, ReloadTrigger};
+pub use report::{print_status_report, OutputFormat, ReportWriter};
+pub use scanner::{DirectoryResult, FileMatch, ScanReport, ScanRoot, Scanner};
+pub use status::DirectoryStatus;
+pub use transition::{StatusTransition, TransitionKind, TransitionTracker};
+pub use validation::{ConfigErrors, ConfigIssue};
+pub use watcher::{DirectoryActivity, EventWatcher};
+pub use webhook::WebhookNotifier;
+pub use writers::OpenWriter;
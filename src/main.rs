@@ -1,65 +1,178 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, Local};
-use clap::Parser;
-use futures::future::join_all;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use clap::{Args, Parser, Subcommand};
+use file_monitor::cache::ScanCache;
+use file_monitor::config::{
+    create_default_config_safely, render_config, resolve_path, save_config_safely,
+};
+use file_monitor::doctor::{self, Severity};
+use file_monitor::history::{self, HistoryStore};
+use file_monitor::report::{OutputFormat, ReportWriter};
+use file_monitor::server::{self, StatusStore};
+use file_monitor::{
+    CheckResult, CheckRules, Config, ConfigWatcher, DirectoryActivity, DirectoryOverrides,
+    EventWatcher, HookRunner, MetricsRegistry, PathFilter, ReloadTrigger, ScanReport, Scanner,
+    TransitionTracker, WebhookNotifier,
+};
 use log::{debug, error, info, warn};
-use rayon::prelude::*;
-use serde::Deserialize;
-use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
-use tokio::task;
-use walkdir::WalkDir;
 
 #[derive(Parser, Debug)]
 #[command(name = "file_monitor")]
 #[command(about = "监控目录中的新文件创建")]
-struct Args {
+struct Cli {
     /// 配置文件路径
-    #[arg(short, long, default_value = "config.toml")]
+    #[arg(short, long, default_value = "config.toml", global = true)]
     config: String,
 
-    /// 只运行一次，不持续监控
-    #[arg(short, long)]
+    /// 覆盖 [monitor] 中的配置项，格式 KEY=VALUE（可重复），例如 --set check_hours=2
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    settings: Vec<String>,
+
+    // 以下为旧版参数，仅在未指定子命令时使用，请改用 scan / watch / serve 子命令
+    #[arg(short, long, hide = true)]
     once: bool,
 
-    /// 非交互式模式，自动使用默认配置
+    #[arg(long, hide = true)]
+    serve: bool,
+
+    #[arg(long, hide = true)]
+    listen: Option<String>,
+
+    #[arg(long, hide = true)]
+    non_interactive: bool,
+
+    #[arg(long, hide = true)]
+    monitor_path: Option<String>,
+
+    #[arg(long, hide = true, value_parser = ["text", "json", "ndjson", "csv"])]
+    output_format: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// scan / watch / serve 共用的参数
+#[derive(Args, Debug, Clone)]
+struct RunArgs {
+    /// 非交互式模式，配置文件不存在或监控目录无效时不提示输入
     #[arg(long)]
     non_interactive: bool,
 
     /// 指定监控目录路径（用于非交互式模式）
     #[arg(long)]
     monitor_path: Option<String>,
+
+    /// 报告输出格式，覆盖配置文件中的 [output] format
+    #[arg(long, value_parser = ["text", "json", "ndjson", "csv"])]
+    output_format: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
-struct Config {
-    monitor: MonitorConfig,
-    output: OutputConfig,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 扫描一次并输出报告
+    Scan(RunArgs),
+    /// 持续监控，每隔 scan_interval 秒扫描一次（未指定子命令时的默认行为）
+    Watch(RunArgs),
+    /// 持续监控并启动 HTTP 状态服务
+    Serve {
+        #[command(flatten)]
+        run: RunArgs,
+
+        /// HTTP 状态服务监听地址，覆盖配置文件中的 [server] listen
+        #[arg(long)]
+        listen: Option<String>,
+    },
+    /// 创建、查看、校验和修改配置文件
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// 检查配置和运行环境中的常见问题，有错误时退出码为 1
+    Doctor,
+    /// 查看录制历史：录制会话、总录制时长和在线率
+    History {
+        /// 只显示指定目录
+        #[arg(long)]
+        dir: Option<String>,
+
+        /// 统计最近多少天（未指定 --since 时使用）
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+
+        /// 统计起始时间，格式 "YYYY-MM-DD" 或 "YYYY-MM-DD HH:MM:SS"
+        #[arg(long, value_parser = parse_local_time)]
+        since: Option<DateTime<Local>>,
+
+        /// 统计结束时间（默认当前时间），格式同 --since
+        #[arg(long, value_parser = parse_local_time)]
+        until: Option<DateTime<Local>>,
+    },
+    /// 扫描一次并输出 Nagios/Icinga 兼容的单行状态，退出码 0/1/2/3 对应 OK/WARNING/CRITICAL/UNKNOWN
+    Check {
+        /// 录制中的目录少于 N 个时为 WARNING
+        #[arg(long, value_name = "N")]
+        warning_below: Option<usize>,
+
+        /// 录制中的目录少于 N 个时为 CRITICAL
+        #[arg(long, value_name = "N")]
+        critical_below: Option<usize>,
+
+        /// 指定目录必须处于录制状态，否则为 CRITICAL（可重复）
+        #[arg(long = "require", value_name = "DIR")]
+        required: Vec<String>,
+    },
 }
 
-#[derive(Deserialize, Debug)]
-struct MonitorConfig {
-    root_path: String,
-    check_hours: u64,
-    scan_interval: u64,
-    max_depth: Option<usize>,
-    follow_links: Option<bool>,
-    time_type: Option<String>,
-    parallel_mode: Option<String>,
-    max_parallel_tasks: Option<usize>,
-    search_latest_subdir_only: Option<bool>,
-    // 性能优化选项（不影响精确度）
-    use_async_io: Option<bool>,
-    batch_size: Option<usize>,
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// 创建默认配置文件，已存在时拒绝覆盖
+    Init {
+        /// 监控目录路径，未指定时交互式输入
+        #[arg(long)]
+        monitor_path: Option<String>,
+    },
+    /// 输出应用 --set 覆盖后的完整配置
+    Show,
+    /// 校验配置文件，无效时退出码为 1
+    Validate,
+    /// 修改 [monitor] 中的配置项并保存（保存前备份原文件）
+    Set {
+        /// 要修改的配置项，例如 check_hours=2
+        #[arg(value_name = "KEY=VALUE", required = true)]
+        assignments: Vec<String>,
+    },
 }
 
-#[derive(Deserialize, Debug)]
-struct OutputConfig {
-    recording_message: String,
-    not_recording_message: String,
+/// 监控的运行方式
+enum RunMode {
+    Scan,
+    Watch,
+    Serve { listen: Option<String> },
+}
+
+impl Cli {
+    /// 未指定子命令时按旧版参数选择运行方式
+    fn legacy_command(&self) -> Command {
+        let run = RunArgs {
+            non_interactive: self.non_interactive,
+            monitor_path: self.monitor_path.clone(),
+            output_format: self.output_format.clone(),
+        };
+        if self.serve {
+            warn!("--serve 参数已弃用，请使用 serve 子命令");
+            Command::Serve {
+                run,
+                listen: self.listen.clone(),
+            }
+        } else if self.once {
+            warn!("--once 参数已弃用，请使用 scan 子命令");
+            Command::Scan(run)
+        } else {
+            Command::Watch(run)
+        }
+    }
 }
 
 #[tokio::main]
@@ -69,50 +182,639 @@ async fn main() -> Result<()> {
         .filter_level(log::LevelFilter::Info)
         .init();
 
-    let args = Args::parse();
-
-    // 加载或创建配置文件
-    let mut config = load_or_create_config(&args.config, &args)?;
+    let mut cli = Cli::parse();
+    let command = match cli.command.take() {
+        Some(command) => command,
+        None => cli.legacy_command(),
+    };
+
+    match command {
+        Command::Scan(run) => run_monitor(&cli, &run, RunMode::Scan).await,
+        Command::Watch(run) => run_monitor(&cli, &run, RunMode::Watch).await,
+        Command::Serve { run, listen } => run_monitor(&cli, &run, RunMode::Serve { listen }).await,
+        Command::Config(command) => run_config_command(&cli, command),
+        Command::Doctor => {
+            let results = doctor::diagnose(&cli.config, &cli.settings).await;
+            doctor::write_diagnostics(&mut io::stdout(), &results)?;
+            if results.iter().any(|d| d.severity == Severity::Error) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::History {
+            dir,
+            days,
+            since,
+            until,
+        } => {
+            let config = Config::load(&cli.config, &cli.settings)?;
+            let until = until.unwrap_or_else(Local::now);
+            let since = since.unwrap_or(until - chrono::Duration::days(days));
+            print_history(&cli.config, &config, dir.as_deref(), since, until)
+        }
+        Command::Check {
+            warning_below,
+            critical_below,
+            required,
+        } => {
+            let rules = CheckRules {
+                warning_below,
+                critical_below,
+                required,
+            };
+            let result = run_check(&cli.config, &cli.settings, &rules).await;
+            println!("{}", result);
+            std::process::exit(result.state.exit_code());
+        }
+    }
+}
 
-    // 检查并更新监控路径
-    let original_path = config.monitor.root_path.clone();
-    config.monitor.root_path = ensure_valid_monitor_path(&config.monitor.root_path, &args)?;
+/// 执行 config 子命令
+fn run_config_command(cli: &Cli, command: ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Init { monitor_path } => {
+            if Path::new(&cli.config).exists() {
+                return Err(anyhow::anyhow!("配置文件已存在，拒绝覆盖: {}", cli.config));
+            }
+            let monitor_path = match monitor_path {
+                Some(path) => path,
+                None => get_monitor_path_from_user()?,
+            };
+            create_default_config_safely(&cli.config, &monitor_path)?;
+            if !cli.settings.is_empty() {
+                let config = Config::load(&cli.config, &cli.settings)?;
+                save_config_safely(&cli.config, &config)?;
+            }
+            println!("[配置] 配置文件创建完成: {}", cli.config);
+            Ok(())
+        }
+        ConfigCommand::Show => {
+            let config = Config::load(&cli.config, &cli.settings)?;
+            print!("{}", render_config(&config)?);
+            Ok(())
+        }
+        ConfigCommand::Validate => match Config::load(&cli.config, &cli.settings) {
+            Ok(_) => {
+                println!("[成功] 配置有效: {}", cli.config);
+                Ok(())
+            }
+            Err(e) => {
+                println!("[错误] 配置无效: {:#}", e);
+                std::process::exit(1);
+            }
+        },
+        ConfigCommand::Set { assignments } => {
+            let all: Vec<String> = assignments.iter().chain(&cli.settings).cloned().collect();
+            let config = Config::load(&cli.config, &all)?;
+            save_config_safely(&cli.config, &config)?;
+            for setting in &all {
+                println!("[配置] 已设置 {}", setting);
+            }
+            Ok(())
+        }
+    }
+}
 
-    // 只在路径实际改变时才保存配置文件
-    if config.monitor.root_path != original_path {
-        info!("监控路径已更新，保存配置文件...");
-        save_config_safely(&args.config, &config)?;
+/// 执行 scan / watch / serve 子命令
+async fn run_monitor(cli: &Cli, run: &RunArgs, mode: RunMode) -> Result<()> {
+    // 加载或创建配置文件，--set 覆盖只在本次运行中生效，不写入配置文件；
+    // 校验的是应用覆盖之后的配置
+    let mut config = load_or_create_config(&cli.config, &cli.settings, run)?;
+
+    // 检查并更新监控路径（配置了多个根目录时由扫描器逐个检查）
+    if config.monitor.roots().is_empty() {
+        let monitor_path = ensure_valid_monitor_path(&config.monitor.root_path, run)?;
+
+        // 只在路径实际改变时才保存配置文件
+        if monitor_path != config.monitor.root_path {
+            info!("监控路径已更新，保存配置文件...");
+            let mut file_config = Config::from_file_unchecked(&cli.config)?;
+            file_config.monitor.root_path = monitor_path.clone();
+            save_config_safely(&cli.config, &file_config)?;
+            config.monitor.root_path = monitor_path;
+        }
     }
 
+    let writer = Some(ReportWriter::stdout(output_format(
+        run.output_format.as_deref(),
+        &config,
+    )?));
+
     info!("文件监控程序启动");
-    info!("监控目录: {}", config.monitor.root_path);
+    log_monitor_paths(&config);
     info!("检查时间范围: {} 小时", config.monitor.check_hours);
 
-    if args.once {
-        // 只运行一次
-        check_and_report(&config).await?;
+    // HTTP 状态服务与扫描循环并行运行
+    let status_store = if let RunMode::Serve { listen } = &mode {
+        let listen = listen
+            .as_deref()
+            .or(config.server.as_ref().and_then(|s| s.listen.as_deref()))
+            .unwrap_or(server::DEFAULT_LISTEN);
+        let listener = tokio::net::TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("无法监听地址: {}", listen))?;
+        let store = StatusStore::new();
+        let server_store = store.clone();
+        tokio::spawn(async move {
+            if let Err(e) = server::serve(listener, server_store).await {
+                error!("{:#}", e);
+            }
+        });
+        Some(store)
     } else {
-        // 持续监控
-        info!("扫描间隔: {} 秒", config.monitor.scan_interval);
-        info!("按 Ctrl+C 停止监控");
+        None
+    };
+
+    // 状态变化时的 Webhook 通知与钩子命令
+    let webhooks = create_webhooks(&config);
+    let hooks = HookRunner::new(config.hooks.clone().unwrap_or_default());
+
+    // 扫描历史
+    let history = open_history(&cli.config, &config);
+
+    // Prometheus 指标：/metrics 与 textfile collector 共用同一份累计数据
+    let metrics = status_store
+        .as_ref()
+        .map(|store| store.metrics().clone())
+        .unwrap_or_default();
+    let metrics_textfile = metrics_textfile_path(&cli.config, &config);
+
+    let mut session = Session {
+        config_path: cli.config.clone(),
+        settings: cli.settings.clone(),
+        output_format_override: run.output_format.clone(),
+        scanner: create_scanner(&cli.config, &config),
+        config,
+        writer,
+        tracker: TransitionTracker::new(),
+        status_store,
+        webhooks,
+        hooks,
+        history,
+        metrics,
+        metrics_textfile,
+    };
+
+    if let RunMode::Scan = mode {
+        // 只运行一次
+        session.check_and_report().await?;
+        return Ok(());
+    }
+
+    // 持续监控
+    let config = &session.config;
+    info!("扫描间隔: {} 秒", config.monitor.scan_interval);
+    info!("按 Ctrl+C 停止监控");
+
+    let mut watcher = create_event_watcher(config);
+    // 收到 SIGHUP 或配置文件变化时重新加载配置
+    let mut reloader = Some(ConfigWatcher::new(Path::new(&cli.config)));
+
+    loop {
+        session.clear_screen();
+        let config = &session.config;
+        info!("文件监控中... (按 Ctrl+C 停止)");
+        log_monitor_paths(config);
+        info!("检查时间范围: {} 小时", config.monitor.check_hours);
+        let scan_interval = config.monitor.scan_interval;
+        let mut report = session.check_and_report().await?;
+        if let Some(watcher) = watcher.as_mut() {
+            watcher.clear_activity();
+        }
+        let next_scan =
+            tokio::time::Instant::now() + tokio::time::Duration::from_secs(scan_interval);
+        session
+            .wait_for_next_scan(next_scan, &mut watcher, &mut reloader, &mut report)
+            .await?;
+    }
+}
+
+/// 在多次扫描之间保持的输出与状态
+struct Session {
+    // 重新加载配置时使用
+    config_path: String,
+    settings: Vec<String>,
+    output_format_override: Option<String>,
+    config: Config,
+    // 跨扫描复用，以便增长检测比较前后两次扫描
+    scanner: Scanner,
+    // check 子命令只输出单行状态，不输出完整报告
+    writer: Option<ReportWriter<io::Stdout>>,
+    tracker: TransitionTracker,
+    status_store: Option<StatusStore>,
+    webhooks: WebhookNotifier,
+    hooks: HookRunner,
+    history: Option<HistoryStore>,
+    metrics: MetricsRegistry,
+    // 每次发布后写入的 .prom 文件
+    metrics_textfile: Option<PathBuf>,
+}
+
+impl Session {
+    async fn check_and_report(&mut self) -> Result<ScanReport> {
+        let config = &self.config;
+        let scanner = self.scanner.clone();
+        let root_path = scanner.root_path();
+
+        // 多个根目录时，不可访问的根目录会在报告中单独标出
+        if !config.monitor.roots().is_empty() {
+            let mut report = scanner.scan().await?;
+            self.publish(&mut report).await?;
+            return Ok(report);
+        }
+
+        if !root_path.exists() {
+            error!("监控目录不存在: {}", config.monitor.root_path);
+            error!("请检查配置文件中的 root_path 设置");
+            // 仍然输出报告，标出不可访问的根目录
+            let mut report =
+                ScanReport::unavailable(root_path, scanner.threshold_time(), "监控目录不存在");
+            self.publish(&mut report).await?;
+            return Ok(report);
+        }
+
+        // 网络文件系统性能验证
+        let start_time = Instant::now();
+        if let Err(e) = fs::read_dir(root_path) {
+            error!("无法读取监控目录: {}", e);
+            // 仍然输出报告，标出不可访问的根目录
+            let mut report = ScanReport::unavailable(
+                root_path,
+                scanner.threshold_time(),
+                format!("无法读取监控目录: {}", e),
+            );
+            self.publish(&mut report).await?;
+            return Ok(report);
+        }
+        let read_duration = start_time.elapsed();
+
+        // 如果目录读取超过1秒，可能是网络文件系统延迟问题
+        if read_duration.as_secs() > 1 {
+            warn!(
+                "目录读取耗时 {:.2}秒，可能存在网络延迟或挂载问题",
+                read_duration.as_secs_f64()
+            );
+        } else {
+            debug!("目录读取耗时 {:.2}毫秒", read_duration.as_millis());
+        }
+
+        // 获取所有二级目录及其新文件状态
+        let mut report = scanner.scan().await?;
+
+        // 总是输出结果
+        self.publish(&mut report).await?;
+
+        Ok(report)
+    }
+
+    /// 检测状态变化，并把报告发布到标准输出、状态服务和通知
+    async fn publish(&mut self, report: &mut ScanReport) -> Result<()> {
+        let transitions = self.tracker.observe(report, &self.config.output);
+        for transition in &transitions {
+            info!(
+                "目录 '{}' 状态变化: {} ({})",
+                transition.directory,
+                transition.kind,
+                transition.at.format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+
+        if let Some(writer) = &mut self.writer {
+            writer.write(report, &self.config.output)?;
+        }
+
+        self.metrics.observe(report);
+        if let Some(path) = &self.metrics_textfile {
+            if let Err(e) = self.metrics.write_textfile(path) {
+                error!("无法写入指标文件: {:#}", e);
+            }
+        }
+
+        if let Some(store) = &self.status_store {
+            store.update(report.clone()).await;
+        }
+
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record(report) {
+                error!("无法记录扫描历史: {:#}", e);
+            }
+        }
+
+        self.hooks.spawn_run(transitions.clone());
+        self.webhooks.spawn_notify(transitions);
 
+        Ok(())
+    }
+
+    /// 等待到下一次全量扫描；事件模式下期间收到的写入事件会实时更新报告
+    ///
+    /// 配置重新加载成功后立即返回，以便按新配置扫描。
+    async fn wait_for_next_scan(
+        &mut self,
+        next_scan: tokio::time::Instant,
+        watcher: &mut Option<EventWatcher>,
+        reloader: &mut Option<ConfigWatcher>,
+        report: &mut ScanReport,
+    ) -> Result<()> {
         loop {
-            clear_screen();
-            info!("文件监控中... (按 Ctrl+C 停止)");
-            info!("监控目录: {}", config.monitor.root_path);
-            info!("检查时间范围: {} 小时", config.monitor.check_hours);
-            check_and_report(&config).await?;
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                config.monitor.scan_interval,
-            ))
-            .await;
+            let wake = tokio::select! {
+                _ = tokio::time::sleep_until(next_scan) => return Ok(()),
+                trigger = next_reload(reloader) => Wake::Reload(trigger),
+                activity = next_activity(watcher) => Wake::Activity(activity),
+            };
+
+            let activity = match wake {
+                Wake::Reload(trigger) => {
+                    if self.reload(trigger, watcher) {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Wake::Activity(Some(activity)) => activity,
+                Wake::Activity(None) => {
+                    warn!("文件系统事件通道已关闭，回退到周期扫描");
+                    *watcher = None;
+                    continue;
+                }
+            };
+
+            let Some(active_watcher) = watcher.as_mut() else {
+                continue;
+            };
+            let changed = active_watcher.apply(report);
+            if !changed.is_empty() {
+                info!(
+                    "目录 '{}' 检测到新文件: {}",
+                    activity.name,
+                    activity.path.display()
+                );
+                self.clear_screen();
+                self.publish(report).await?;
+            }
+        }
+    }
+
+    /// 重新读取并校验配置文件，成功时替换当前配置；配置无效时保留原配置并记录原因
+    fn reload(&mut self, trigger: ReloadTrigger, watcher: &mut Option<EventWatcher>) -> bool {
+        let loaded = Config::load(&self.config_path, &self.settings).and_then(|config| {
+            let format = output_format(self.output_format_override.as_deref(), &config)?;
+            Ok((config, format))
+        });
+        let (config, format) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                error!("{}，但新配置无效，继续使用原配置: {:#}", trigger, e);
+                return false;
+            }
+        };
+        info!("{}，已重新加载配置: {}", trigger, self.config_path);
+
+        if config.monitor != self.config.monitor || config.directories != self.config.directories {
+            // 增长基准、超时退避和扫描缓存等状态尽量沿用，避免重新加载后漏掉一个扫描周期
+            self.scanner =
+                create_scanner(&self.config_path, &config).with_state_from(&self.scanner);
+            if config.monitor.root_path != self.config.monitor.root_path
+                || config.monitor.roots != self.config.monitor.roots
+                || config.monitor.watch_mode != self.config.monitor.watch_mode
+                || config.monitor.include != self.config.monitor.include
+                || config.monitor.exclude != self.config.monitor.exclude
+                || config.monitor.check_hours != self.config.monitor.check_hours
+                || config.monitor.stale_minutes != self.config.monitor.stale_minutes
+                || config.monitor.detection_mode != self.config.monitor.detection_mode
+                || config.directories != self.config.directories
+            {
+                *watcher = create_event_watcher(&config);
+            }
+        }
+
+        if let Some(writer) = &mut self.writer {
+            if writer.format() != format {
+                *writer = ReportWriter::stdout(format);
+            }
+        }
+
+        let listen = |config: &Config| config.server.as_ref().and_then(|s| s.listen.clone());
+        if self.status_store.is_some() && listen(&config) != listen(&self.config) {
+            warn!("HTTP 状态服务监听地址的修改需要重启后生效");
+        }
+
+        if config.history != self.config.history
+            || config.monitor.scan_interval != self.config.monitor.scan_interval
+        {
+            self.history = open_history(&self.config_path, &config);
+        }
+
+        self.webhooks = create_webhooks(&config);
+        self.hooks = HookRunner::new(config.hooks.clone().unwrap_or_default());
+        self.metrics_textfile = metrics_textfile_path(&self.config_path, &config);
+        self.config = config;
+        true
+    }
+
+    fn clear_screen(&self) {
+        // 机器可读格式下不输出控制字符
+        if self
+            .writer
+            .as_ref()
+            .is_none_or(|w| w.format().is_machine_readable())
+        {
+            return;
+        }
+
+        // 跨平台清屏
+        if cfg!(target_os = "windows") {
+            // Windows 清屏
+            let _ = std::process::Command::new("cmd")
+                .args(["/c", "cls"])
+                .status();
+        } else {
+            // Unix/Linux/Mac 清屏
+            print!("\x1B[2J\x1B[1;1H");
+        }
+    }
+}
+
+/// `wait_for_next_scan` 中唤醒扫描循环的事件
+enum Wake {
+    Reload(ReloadTrigger),
+    Activity(Option<DirectoryActivity>),
+}
+
+async fn next_reload(reloader: &mut Option<ConfigWatcher>) -> ReloadTrigger {
+    match reloader {
+        Some(reloader) => reloader.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn next_activity(watcher: &mut Option<EventWatcher>) -> Option<DirectoryActivity> {
+    match watcher {
+        Some(watcher) => watcher.next_activity().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 确定报告格式：命令行参数优先于配置文件
+fn output_format(cli_format: Option<&str>, config: &Config) -> Result<OutputFormat> {
+    Ok(cli_format
+        .or(config.output.format.as_deref())
+        .map(str::parse)
+        .transpose()?
+        .unwrap_or_default())
+}
+
+fn create_webhooks(config: &Config) -> WebhookNotifier {
+    WebhookNotifier::new(
+        config
+            .notify
+            .as_ref()
+            .map(|n| n.webhook.clone())
+            .unwrap_or_default(),
+    )
+}
+
+fn metrics_textfile_path(config_path: &str, config: &Config) -> Option<PathBuf> {
+    let textfile = config.metrics.as_ref()?.textfile.as_deref()?;
+    Some(resolve_path(config_path, textfile))
+}
+
+fn log_monitor_paths(config: &Config) {
+    if config.monitor.roots().is_empty() {
+        info!("监控目录: {}", config.monitor.root_path);
+    }
+    for root in config.monitor.roots() {
+        info!("监控目录 '{}': {}", root.label(), root.root_path);
+    }
+}
+
+fn create_event_watcher(config: &Config) -> Option<EventWatcher> {
+    if config.monitor.watch_mode.as_deref() != Some("events") {
+        return None;
+    }
+    if !config.monitor.roots().is_empty() {
+        warn!("事件监控模式暂不支持多个根目录，回退到周期扫描");
+        return None;
+    }
+
+    match EventWatcher::new(Path::new(&config.monitor.root_path)) {
+        Ok(watcher) => {
+            info!("事件监控模式: 实时订阅目录事件，周期扫描作为校正");
+            Some(
+                watcher
+                    .with_filter(PathFilter::from_config(&config.monitor))
+                    .with_overrides(
+                        DirectoryOverrides::from_config(config.directories.as_ref()),
+                        config.monitor.check_hours,
+                    )
+                    .with_detection(&config.monitor),
+            )
+        }
+        Err(e) => {
+            warn!("无法启用事件监控，回退到周期扫描: {:#}", e);
+            None
         }
     }
+}
 
+/// 执行 check 子命令：基于 `check_and_report` 扫描一次，按规则得出检查结果
+async fn run_check(config_path: &str, settings: &[String], rules: &CheckRules) -> CheckResult {
+    let config = match Config::load(config_path, settings) {
+        Ok(config) => config,
+        Err(e) => return CheckResult::unknown(format!("{:#}", e)),
+    };
+
+    // 检查模式只读取状态，不发送通知、不执行钩子、不记录历史，也不读写扫描缓存文件
+    let mut session = Session {
+        config_path: config_path.to_string(),
+        settings: settings.to_vec(),
+        output_format_override: None,
+        scanner: Scanner::from_config(&config),
+        webhooks: WebhookNotifier::new(Vec::new()),
+        config,
+        writer: None,
+        tracker: TransitionTracker::new(),
+        status_store: None,
+        hooks: HookRunner::default(),
+        history: None,
+        metrics: MetricsRegistry::new(),
+        metrics_textfile: None,
+    };
+
+    match session.check_and_report().await {
+        Ok(report) => rules.evaluate(&report),
+        Err(e) => CheckResult::unknown(format!("扫描失败: {:#}", e)),
+    }
+}
+
+/// 创建扫描器；启用增量扫描并设置了 `scan_cache` 时从文件读取缓存
+fn create_scanner(config_path: &str, config: &Config) -> Scanner {
+    let scanner = Scanner::from_config(config);
+    match config.monitor.scan_cache.as_deref() {
+        Some(scan_cache) if scanner.scan_cache().is_some() => {
+            let path = resolve_path(config_path, scan_cache);
+            scanner.with_scan_cache(ScanCache::load(path, &config.monitor))
+        }
+        _ => scanner,
+    }
+}
+
+/// 按 `[history]` 配置打开历史数据库；打开失败时只记录日志，不影响监控
+fn open_history(config_path: &str, config: &Config) -> Option<HistoryStore> {
+    let history_config = config.history.clone().unwrap_or_default();
+    if !history_config.enabled.unwrap_or(false) {
+        return None;
+    }
+
+    let path = history::history_path(config_path, history_config.path.as_deref());
+    match HistoryStore::open(&path) {
+        Ok(store) => {
+            debug!("扫描历史数据库: {}", path.display());
+            Some(
+                store
+                    .with_retention_days(history_config.retention_days)
+                    .with_scan_interval(config.monitor.scan_interval),
+            )
+        }
+        Err(e) => {
+            warn!("无法启用扫描历史: {:#}", e);
+            None
+        }
+    }
+}
+
+fn print_history(
+    config_path: &str,
+    config: &Config,
+    dir: Option<&str>,
+    since: DateTime<Local>,
+    until: DateTime<Local>,
+) -> Result<()> {
+    let configured = config.history.as_ref().and_then(|h| h.path.as_deref());
+    let path = history::history_path(config_path, configured);
+    if !path.exists() {
+        return Err(anyhow::anyhow!("历史数据库不存在: {}", path.display()));
+    }
+
+    let store = HistoryStore::open(&path)?;
+    let histories = store.summarize(since, until, dir)?;
+    history::write_history_report(&mut io::stdout(), &histories, since, until)?;
     Ok(())
 }
 
-fn load_or_create_config(config_path: &str, args: &Args) -> Result<Config> {
+fn parse_local_time(value: &str) -> Result<DateTime<Local>, String> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| format!("无效的时间: {}（格式: YYYY-MM-DD [HH:MM:SS]）", value))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("无效的本地时间: {}", value))
+}
+
+fn load_or_create_config(config_path: &str, settings: &[String], args: &RunArgs) -> Result<Config> {
     // 检查配置文件是否存在
     if !Path::new(config_path).exists() {
         if args.non_interactive {
@@ -124,7 +826,7 @@ fn load_or_create_config(config_path: &str, args: &Args) -> Result<Config> {
             } else {
                 return Err(anyhow::anyhow!(
                     "非交互式模式下必须使用 --monitor-path 参数指定监控目录路径\n\
-                     示例: {} --non-interactive --monitor-path /path/to/monitor",
+                     示例: {} watch --non-interactive --monitor-path /path/to/monitor",
                     std::env::args()
                         .next()
                         .unwrap_or_else(|| "file_monitor".to_string())
@@ -148,15 +850,10 @@ fn load_or_create_config(config_path: &str, args: &Args) -> Result<Config> {
         println!();
     }
 
-    let content = fs::read_to_string(config_path)
-        .with_context(|| format!("无法读取配置文件: {}", config_path))?;
-
-    let config: Config = toml::from_str(&content).with_context(|| "配置文件格式错误")?;
-
-    Ok(config)
+    Config::load(config_path, settings)
 }
 
-fn ensure_valid_monitor_path(current_path: &str, args: &Args) -> Result<String> {
+fn ensure_valid_monitor_path(current_path: &str, args: &RunArgs) -> Result<String> {
     let path = Path::new(current_path);
 
     if path.exists() && path.is_dir() {
@@ -241,727 +938,3 @@ fn get_monitor_path_from_user() -> Result<String> {
         return Ok(input.to_string());
     }
 }
-
-fn save_config_safely(config_path: &str, config: &Config) -> Result<()> {
-    let escaped_path = config.monitor.root_path.replace("\\", "\\\\");
-
-    let config_content = format!(
-        r#"# 文件监控配置
-[monitor]
-# 监控的根目录路径
-root_path = "{}"
-# 检查新文件的时间范围（小时）
-check_hours = {}
-# 扫描间隔（秒）
-scan_interval = {}
-# 最大扫描深度（可选）
-# 注释掉或删除此行表示无限制深度
-# 设置具体数值可限制扫描深度，例如: max_depth = 10
-{}
-# 是否跟随符号链接（可选，默认false）
-# 启用此选项会跟随符号链接到其目标位置
-{}
-# 时间戳类型（可选，默认modified）
-# modified: 使用文件修改时间（跨平台兼容性更好）
-# created: 使用文件创建时间（Windows上更准确，但Linux可能不支持）
-{}
-# 并行模式（可选，默认sync）
-# sync: 同步模式，等待所有任务完成
-# async: 异步模式，不等待任务完成
-# parallel: 并行模式，同时执行多个任务
-{}
-# 最大并行任务数（可选，默认CPU核心数）
-# 设置具体数值可限制并行任务数，例如: max_parallel_tasks = 4
-{}
-# 是否只搜索最新子目录（可选，默认false）
-# 启用此选项可大大节省扫描时间，但只会检查最新修改的子目录
-{}
-# 性能优化选项（不影响精确度）
-use_async_io = {}
-batch_size = {}
-
-[output]
-# 有新文件时的提示信息
-recording_message = "{}"
-# 没有新文件时的提示信息
-not_recording_message = "{}"
-"#,
-        escaped_path,
-        config.monitor.check_hours,
-        config.monitor.scan_interval,
-        if let Some(depth) = config.monitor.max_depth {
-            format!("max_depth = {}", depth)
-        } else {
-            "# max_depth = 10".to_string()
-        },
-        if let Some(follow) = config.monitor.follow_links {
-            format!("follow_links = {}", follow)
-        } else {
-            "# follow_links = true".to_string()
-        },
-        if let Some(time_type) = &config.monitor.time_type {
-            format!("time_type = \"{}\"", time_type)
-        } else {
-            "# time_type = \"created\"".to_string()
-        },
-        if let Some(parallel_mode) = &config.monitor.parallel_mode {
-            format!("parallel_mode = \"{}\"", parallel_mode)
-        } else {
-            "# parallel_mode = \"sync\"".to_string()
-        },
-        if let Some(max_parallel_tasks) = config.monitor.max_parallel_tasks {
-            format!("max_parallel_tasks = {}", max_parallel_tasks)
-        } else {
-            "# max_parallel_tasks = 4".to_string()
-        },
-        if let Some(search_latest) = config.monitor.search_latest_subdir_only {
-            format!("search_latest_subdir_only = {}", search_latest)
-        } else {
-            "# search_latest_subdir_only = true".to_string()
-        },
-        if let Some(use_async_io) = config.monitor.use_async_io {
-            format!("use_async_io = {}", use_async_io)
-        } else {
-            "# use_async_io = false".to_string()
-        },
-        if let Some(batch_size) = config.monitor.batch_size {
-            format!("batch_size = {}", batch_size)
-        } else {
-            "# batch_size = 1000".to_string()
-        },
-        config.output.recording_message,
-        config.output.not_recording_message
-    );
-
-    // 原子性写入：先写入临时文件，然后重命名
-    let temp_path = format!("{}.tmp", config_path);
-    let backup_path = format!("{}.backup", config_path);
-
-    // 如果配置文件已存在，先创建备份
-    if Path::new(config_path).exists() {
-        fs::copy(config_path, &backup_path)
-            .with_context(|| format!("无法创建配置文件备份: {}", backup_path))?;
-    }
-
-    // 写入临时文件
-    fs::write(&temp_path, &config_content)
-        .with_context(|| format!("无法写入临时配置文件: {}", temp_path))?;
-
-    // 验证临时文件内容
-    let verification_content = fs::read_to_string(&temp_path)
-        .with_context(|| format!("无法验证临时配置文件: {}", temp_path))?;
-
-    if verification_content != config_content {
-        // 清理临时文件
-        let _ = fs::remove_file(&temp_path);
-        return Err(anyhow::anyhow!("配置文件写入验证失败"));
-    }
-
-    // 原子性重命名
-    fs::rename(&temp_path, config_path)
-        .with_context(|| format!("无法完成配置文件更新: {}", config_path))?;
-
-    println!("[配置] 配置文件已安全保存，备份文件: {}", backup_path);
-    Ok(())
-}
-
-fn create_default_config_safely(config_path: &str, monitor_path: &str) -> Result<()> {
-    // 转义Windows路径中的反斜杠
-    let escaped_path = monitor_path.replace("\\", "\\\\");
-
-    let default_config = format!(
-        r#"# 文件监控配置
-[monitor]
-# 监控的根目录路径
-root_path = "{}"
-# 检查新文件的时间范围（小时）
-check_hours = 2
-# 扫描间隔（秒）
-scan_interval = 3600
-# 最大扫描深度（可选）
-# 注释掉或删除此行表示无限制深度
-# 设置具体数值可限制扫描深度，例如: max_depth = 10
-# max_depth = 10
-# 是否跟随符号链接（可选，默认false）
-# 启用此选项会跟随符号链接到其目标位置
-# follow_links = true
-# 时间戳类型（可选，默认modified）
-# modified: 使用文件修改时间（跨平台兼容性更好）
-# created: 使用文件创建时间（Windows上更准确，但Linux可能不支持）
-# time_type = "created"
-# 并行模式（可选，默认sync）
-# sync: 同步模式，等待所有任务完成
-# async: 异步模式，不等待任务完成
-# parallel: 并行模式，同时执行多个任务
-parallel_mode = "async"
-# 最大并行任务数（可选，默认CPU核心数）
-# 设置具体数值可限制并行任务数，例如: max_parallel_tasks = 4
-# max_parallel_tasks = 4
-# 是否只搜索最新子目录（可选，默认false）
-# 启用此选项可大大节省扫描时间，但只会检查最新修改的子目录
-search_latest_subdir_only = true
-# 性能优化选项（不影响精确度）
-use_async_io = true
-batch_size = 100
-
-[output]
-# 有新文件时的提示信息
-recording_message = "正在录制"
-# 没有新文件时的提示信息
-not_recording_message = "未录制"
-"#,
-        escaped_path
-    );
-
-    // 检查文件是否已存在，避免意外覆盖
-    if Path::new(config_path).exists() {
-        return Err(anyhow::anyhow!("配置文件已存在，拒绝覆盖: {}", config_path));
-    }
-
-    // 原子性写入
-    let temp_path = format!("{}.tmp", config_path);
-
-    fs::write(&temp_path, &default_config)
-        .with_context(|| format!("无法创建临时配置文件: {}", temp_path))?;
-
-    // 验证文件内容
-    let verification_content = fs::read_to_string(&temp_path)
-        .with_context(|| format!("无法验证临时配置文件: {}", temp_path))?;
-
-    if verification_content != default_config {
-        // 清理临时文件
-        let _ = fs::remove_file(&temp_path);
-        return Err(anyhow::anyhow!("配置文件创建验证失败"));
-    }
-
-    // 原子性重命名
-    fs::rename(&temp_path, config_path)
-        .with_context(|| format!("无法完成配置文件创建: {}", config_path))?;
-
-    Ok(())
-}
-
-fn clear_screen() {
-    // 跨平台清屏
-    if cfg!(target_os = "windows") {
-        // Windows 清屏
-        let _ = std::process::Command::new("cmd")
-            .args(["/c", "cls"])
-            .status();
-    } else {
-        // Unix/Linux/Mac 清屏
-        print!("\x1B[2J\x1B[1;1H");
-    }
-}
-
-async fn check_and_report(config: &Config) -> Result<()> {
-    let root_path = Path::new(&config.monitor.root_path);
-
-    if !root_path.exists() {
-        error!("监控目录不存在: {}", config.monitor.root_path);
-        error!("请检查配置文件中的 root_path 设置");
-        // 仍然输出报告，显示空结果
-        let empty_status = HashMap::new();
-        print_status_report(&empty_status, config);
-        return Ok(());
-    }
-
-    // 网络文件系统性能验证
-    let start_time = Instant::now();
-    if let Err(e) = fs::read_dir(root_path) {
-        error!("无法读取监控目录: {}", e);
-        // 仍然输出报告，显示空结果
-        let empty_status = HashMap::new();
-        print_status_report(&empty_status, config);
-        return Ok(());
-    }
-    let read_duration = start_time.elapsed();
-
-    // 如果目录读取超过1秒，可能是网络文件系统延迟问题
-    if read_duration.as_secs() > 1 {
-        warn!(
-            "目录读取耗时 {:.2}秒，可能存在网络延迟或挂载问题",
-            read_duration.as_secs_f64()
-        );
-    } else {
-        debug!("目录读取耗时 {:.2}毫秒", read_duration.as_millis());
-    }
-
-    // 计算时间阈值
-    let threshold_time = Local::now() - Duration::hours(config.monitor.check_hours as i64);
-
-    // 获取所有二级目录及其新文件状态
-    let subdirs_status = check_subdirectories_async(root_path, threshold_time, config).await?;
-
-    // 总是输出结果
-    print_status_report(&subdirs_status, config);
-
-    Ok(())
-}
-
-async fn check_subdirectories_async(
-    root_path: &Path,
-    threshold_time: DateTime<Local>,
-    config: &Config,
-) -> Result<HashMap<String, bool>> {
-    let mut status_map = HashMap::new();
-
-    // 确定并行模式
-    let parallel_mode = config.monitor.parallel_mode.as_deref().unwrap_or("sync");
-
-    let max_tasks = config
-        .monitor
-        .max_parallel_tasks
-        .unwrap_or_else(num_cpus::get);
-
-    debug!("使用并行模式: {}, 最大任务数: {}", parallel_mode, max_tasks);
-
-    // 收集所有子目录
-    let mut directories = Vec::new();
-    if let Ok(entries) = fs::read_dir(root_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
-                    directories.push((dir_name.to_string(), path));
-                }
-            }
-        }
-    }
-
-    let scan_start = Instant::now();
-
-    match parallel_mode {
-        "async" => {
-            // 异步并发模式
-            info!("使用异步并发模式扫描 {} 个目录", directories.len());
-            let tasks: Vec<_> = directories
-                .into_iter()
-                .map(|(dir_name, path)| {
-                    let max_depth = config.monitor.max_depth;
-                    let follow_links = config.monitor.follow_links;
-                    let time_type = config.monitor.time_type.clone();
-                    let search_latest_subdir_only = config.monitor.search_latest_subdir_only;
-                    let use_async_io = config.monitor.use_async_io;
-                    let batch_size = config.monitor.batch_size;
-
-                    task::spawn(async move {
-                        let has_recent = task::spawn_blocking(move || {
-                            has_recent_files_optimized(
-                                &path,
-                                threshold_time,
-                                max_depth,
-                                follow_links,
-                                &time_type,
-                                search_latest_subdir_only,
-                                use_async_io.unwrap_or(false),
-                                batch_size,
-                            )
-                        })
-                        .await
-                        .unwrap_or(Ok(false))
-                        .unwrap_or(false);
-                        (dir_name, has_recent)
-                    })
-                })
-                .collect();
-
-            let results = join_all(tasks).await;
-            for (dir_name, has_recent) in results.into_iter().flatten() {
-                status_map.insert(dir_name, has_recent);
-            }
-        }
-        "parallel" => {
-            // CPU 并行模式
-            info!("使用 CPU 并行模式扫描 {} 个目录", directories.len());
-            let results: Vec<_> = directories
-                .par_iter()
-                .map(|(dir_name, path)| {
-                    let has_recent = has_recent_files_optimized(
-                        path,
-                        threshold_time,
-                        config.monitor.max_depth,
-                        config.monitor.follow_links,
-                        &config.monitor.time_type,
-                        config.monitor.search_latest_subdir_only,
-                        config.monitor.use_async_io.unwrap_or(false),
-                        config.monitor.batch_size,
-                    )
-                    .unwrap_or(false);
-                    (dir_name.clone(), has_recent)
-                })
-                .collect();
-
-            for (dir_name, has_recent) in results {
-                status_map.insert(dir_name, has_recent);
-            }
-        }
-        _ => {
-            // 同步模式（默认）
-            debug!("使用同步模式扫描 {} 个目录", directories.len());
-            for (dir_name, path) in directories {
-                let has_recent_files = has_recent_files_optimized(
-                    &path,
-                    threshold_time,
-                    config.monitor.max_depth,
-                    config.monitor.follow_links,
-                    &config.monitor.time_type,
-                    config.monitor.search_latest_subdir_only,
-                    config.monitor.use_async_io.unwrap_or(false),
-                    config.monitor.batch_size,
-                )?;
-                status_map.insert(dir_name, has_recent_files);
-            }
-        }
-    }
-
-    let scan_duration = scan_start.elapsed();
-    info!(
-        "目录扫描完成，耗时 {:.2}ms（模式: {}）",
-        scan_duration.as_millis(),
-        parallel_mode
-    );
-
-    Ok(status_map)
-}
-
-fn has_recent_files_optimized(
-    dir_path: &Path,
-    threshold_time: DateTime<Local>,
-    max_depth: Option<usize>,
-    follow_links: Option<bool>,
-    time_type: &Option<String>,
-    search_latest_subdir_only: Option<bool>,
-    use_async_io: bool,
-    batch_size: Option<usize>,
-) -> Result<bool> {
-    // 确定使用的时间戳类型，默认为modified
-    let use_modified = time_type
-        .as_ref()
-        .map(|t| t.to_lowercase())
-        .as_deref()
-        .unwrap_or("modified")
-        == "modified";
-
-    // 如果启用了只搜索最新子目录的选项
-    if search_latest_subdir_only.unwrap_or(false) {
-        return search_in_latest_subdir_only_optimized(
-            dir_path,
-            threshold_time,
-            max_depth,
-            follow_links,
-            use_modified,
-            batch_size,
-        );
-    }
-
-    // 激进优化3: 使用异步I/O
-    if use_async_io {
-        return has_recent_files_async_io(
-            dir_path,
-            threshold_time,
-            max_depth,
-            follow_links,
-            use_modified,
-            batch_size,
-        );
-    }
-
-    // 回退到原有逻辑（使用批处理优化）
-    let mut walker = WalkDir::new(dir_path);
-
-    if let Some(depth) = max_depth {
-        walker = walker.max_depth(depth);
-    }
-
-    if let Some(follow) = follow_links {
-        walker = walker.follow_links(follow);
-    }
-
-    // 如果设置了批处理，则使用批处理方式
-    if let Some(batch_size) = batch_size {
-        return check_files_in_batches(walker, threshold_time, use_modified, batch_size);
-    }
-
-    // 原有的逐一检查方式
-    for entry in walker.into_iter().flatten() {
-        let path = entry.path();
-        if path.is_file() {
-            if let Ok(metadata) = fs::metadata(path) {
-                let time_result = if use_modified {
-                    metadata.modified()
-                } else {
-                    metadata.created()
-                };
-
-                match time_result {
-                    Ok(time) => {
-                        let file_time: DateTime<Local> = time.into();
-                        if file_time > threshold_time {
-                            return Ok(true);
-                        }
-                    }
-                    Err(e) => {
-                        let time_name = if use_modified {
-                            "修改时间"
-                        } else {
-                            "创建时间"
-                        };
-                        warn!("无法获取文件{} '{}': {}", time_name, path.display(), e);
-                    }
-                }
-            } else {
-                warn!("无法获取文件元数据 '{}'", path.display());
-            }
-        }
-    }
-    Ok(false)
-}
-
-// 激进优化2: 优化版的最新子目录搜索
-fn search_in_latest_subdir_only_optimized(
-    dir_path: &Path,
-    threshold_time: DateTime<Local>,
-    max_depth: Option<usize>,
-    follow_links: Option<bool>,
-    use_modified: bool,
-    batch_size: Option<usize>,
-) -> Result<bool> {
-    // 首先快速检查当前目录时间
-    if let Ok(metadata) = fs::metadata(dir_path) {
-        let time_result = if use_modified {
-            metadata.modified()
-        } else {
-            metadata.created()
-        };
-
-        if let Ok(time) = time_result {
-            let dir_time: DateTime<Local> = time.into();
-            if dir_time > threshold_time {
-                debug!("目录本身就是新的: {}", dir_path.display());
-                return Ok(true);
-            }
-        }
-    }
-
-    // 找到最新的子目录
-    let latest_subdir = find_latest_subdir(dir_path, use_modified)?;
-
-    if let Some(latest_dir) = latest_subdir {
-        debug!("搜索最新子目录: {}", latest_dir.display());
-
-        // 构建目录遍历器
-        let mut walker = WalkDir::new(&latest_dir);
-
-        // 如果设置了最大深度，需要减1（因为我们已经进入了一层子目录）
-        if let Some(depth) = max_depth {
-            if depth > 1 {
-                walker = walker.max_depth(depth - 1);
-            } else {
-                walker = walker.max_depth(1);
-            }
-        }
-
-        // 如果设置了跟随符号链接，则应用设置
-        if let Some(follow) = follow_links {
-            walker = walker.follow_links(follow);
-        }
-
-        // 如果设置了批处理大小，使用批处理方式
-        if let Some(batch_size) = batch_size {
-            return check_files_in_batches(walker, threshold_time, use_modified, batch_size);
-        }
-
-        // 否则使用原有的逐一检查方式
-        for entry in walker.into_iter().flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                // 使用DirEntry的metadata而不是fs::metadata，更快
-                if let Ok(metadata) = entry.metadata() {
-                    let time_result = if use_modified {
-                        metadata.modified()
-                    } else {
-                        metadata.created()
-                    };
-
-                    if let Ok(time) = time_result {
-                        let file_time: DateTime<Local> = time.into();
-                        if file_time > threshold_time {
-                            debug!("在最新子目录中找到新文件: {}", path.display());
-                            return Ok(true);
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    Ok(false)
-}
-
-// 优化的异步I/O版本（不影响精确度）
-fn has_recent_files_async_io(
-    dir_path: &Path,
-    threshold_time: DateTime<Local>,
-    max_depth: Option<usize>,
-    follow_links: Option<bool>,
-    use_modified: bool,
-    batch_size: Option<usize>,
-) -> Result<bool> {
-    let mut walker = WalkDir::new(dir_path);
-
-    if let Some(depth) = max_depth {
-        walker = walker.max_depth(depth);
-    }
-
-    if let Some(follow) = follow_links {
-        walker = walker.follow_links(follow);
-    }
-
-    // 如果设置了批处理大小，使用批处理方式
-    if let Some(batch_size) = batch_size {
-        return check_files_in_batches(walker, threshold_time, use_modified, batch_size);
-    }
-
-    // 否则批量收集所有文件然后一次性检查
-    let mut files_to_check = Vec::new();
-    for entry in walker.into_iter().flatten() {
-        if entry.path().is_file() {
-            files_to_check.push(entry);
-        }
-    }
-
-    // 检查所有文件（不影响精确度）
-    check_files_batch(&files_to_check, threshold_time, use_modified)
-}
-
-fn check_files_batch(
-    files: &[walkdir::DirEntry],
-    threshold_time: DateTime<Local>,
-    use_modified: bool,
-) -> Result<bool> {
-    for entry in files {
-        // 使用DirEntry的metadata方法，避免额外的系统调用
-        if let Ok(metadata) = entry.metadata() {
-            let time_result = if use_modified {
-                metadata.modified()
-            } else {
-                metadata.created()
-            };
-
-            if let Ok(time) = time_result {
-                let file_time: DateTime<Local> = time.into();
-                if file_time > threshold_time {
-                    debug!("批量检查找到新文件: {}", entry.path().display());
-                    return Ok(true);
-                }
-            }
-        }
-    }
-    Ok(false)
-}
-
-// 新增：分批次检查文件
-fn check_files_in_batches(
-    walker: walkdir::WalkDir,
-    threshold_time: DateTime<Local>,
-    use_modified: bool,
-    batch_size: usize,
-) -> Result<bool> {
-    let mut file_batch = Vec::new();
-
-    for entry in walker.into_iter().flatten() {
-        if entry.path().is_file() {
-            file_batch.push(entry);
-
-            // 当达到批处理大小时，处理这一批文件
-            if file_batch.len() >= batch_size {
-                debug!("处理文件批次，大小: {}", file_batch.len());
-                if check_files_batch(&file_batch, threshold_time, use_modified)? {
-                    return Ok(true);
-                }
-                file_batch.clear();
-            }
-        }
-    }
-
-    // 处理最后不满一批的文件
-    if !file_batch.is_empty() {
-        debug!("处理最后的文件批次，大小: {}", file_batch.len());
-        if check_files_batch(&file_batch, threshold_time, use_modified)? {
-            return Ok(true);
-        }
-    }
-
-    Ok(false)
-}
-
-fn find_latest_subdir(dir_path: &Path, use_modified: bool) -> Result<Option<std::path::PathBuf>> {
-    let mut latest_dir: Option<std::path::PathBuf> = None;
-    let mut latest_time: Option<DateTime<Local>> = None;
-
-    if let Ok(entries) = fs::read_dir(dir_path) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Ok(metadata) = fs::metadata(&path) {
-                    let time_result = if use_modified {
-                        metadata.modified()
-                    } else {
-                        metadata.created()
-                    };
-
-                    if let Ok(time) = time_result {
-                        let dir_time: DateTime<Local> = time.into();
-
-                        if latest_time.is_none() || dir_time > latest_time.unwrap() {
-                            latest_time = Some(dir_time);
-                            latest_dir = Some(path);
-                        }
-                    } else {
-                        let time_name = if use_modified {
-                            "修改时间"
-                        } else {
-                            "创建时间"
-                        };
-                        warn!(
-                            "无法获取目录{} '{}': {}",
-                            time_name,
-                            path.display(),
-                            time_result.unwrap_err()
-                        );
-                    }
-                } else {
-                    warn!("无法获取目录元数据 '{}'", path.display());
-                }
-            }
-        }
-    }
-
-    Ok(latest_dir)
-}
-
-fn print_status_report(status_map: &HashMap<String, bool>, config: &Config) {
-    let current_time = Local::now().format("%Y-%m-%d %H:%M:%S");
-    println!("\n=== [报告] 文件监控报告 [{}] ===", current_time);
-
-    if status_map.is_empty() {
-        println!("[警告] 未找到任何二级目录");
-        return;
-    }
-
-    // 按目录名排序输出
-    let mut sorted_dirs: Vec<_> = status_map.iter().collect();
-    sorted_dirs.sort_by_key(|&(name, _)| name);
-
-    for (dir_name, &has_recent_files) in sorted_dirs {
-        let (status, icon) = if has_recent_files {
-            (&config.output.recording_message, "[REC]")
-        } else {
-            (&config.output.not_recording_message, "[---]")
-        };
-
-        println!("{} 目录 '{}': {}", icon, dir_name, status);
-    }
-    println!("=======================================\n");
-}
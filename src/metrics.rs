@@ -0,0 +1,169 @@
+//! Prometheus 指标：HTTP `/metrics` 与 node_exporter textfile collector 输出
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::scanner::ScanReport;
+
+/// 跨扫描累计的指标
+///
+/// 计数器在进程生命周期内单调递增；同一次扫描被重复发布（例如事件模式下
+/// 实时更新报告）时只累计一次。
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    inner: Arc<Mutex<Metrics>>,
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    last_scan: Option<DateTime<Local>>,
+    scan_duration_seconds: f64,
+    directories: BTreeMap<String, bool>,
+    scans_total: u64,
+    files_examined_total: u64,
+    scan_errors_total: u64,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 用一次扫描的结果更新指标
+    pub fn observe(&self, report: &ScanReport) {
+        let mut metrics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        // 目录状态总是以最新发布的报告为准
+        metrics.directories = report
+            .directories
+            .iter()
+            .map(|dir| (dir.name.clone(), dir.is_recording()))
+            .collect();
+
+        if metrics.last_scan == Some(report.scanned_at) {
+            return;
+        }
+        metrics.last_scan = Some(report.scanned_at);
+        metrics.scan_duration_seconds = report.scan_duration.as_secs_f64();
+        metrics.scans_total += 1;
+        metrics.files_examined_total += report.files_examined();
+        // 无法访问的根目录同样算作一次扫描失败
+        let failed_directories = report
+            .directories
+            .iter()
+            .filter(|dir| dir.status.is_failure())
+            .count();
+        let failed_roots = report.roots.iter().filter(|r| r.error.is_some()).count();
+        metrics.scan_errors_total += (failed_directories + failed_roots) as u64;
+    }
+
+    /// 以 Prometheus 文本格式输出所有指标
+    pub fn render(&self) -> String {
+        let metrics = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        let header = |out: &mut String, name: &str, kind: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        };
+
+        header(
+            &mut out,
+            "file_monitor_directory_recording",
+            "gauge",
+            "目录是否正在录制（1 为录制中）",
+        );
+        for (dir, recording) in &metrics.directories {
+            let _ = writeln!(
+                out,
+                "file_monitor_directory_recording{{dir=\"{}\"}} {}",
+                escape_label(dir),
+                u8::from(*recording)
+            );
+        }
+
+        header(
+            &mut out,
+            "file_monitor_directories_recording",
+            "gauge",
+            "正在录制的目录数量",
+        );
+        let _ = writeln!(
+            out,
+            "file_monitor_directories_recording {}",
+            metrics.directories.values().filter(|r| **r).count()
+        );
+
+        header(
+            &mut out,
+            "file_monitor_scan_duration_seconds",
+            "gauge",
+            "最近一次扫描的耗时（秒）",
+        );
+        let _ = writeln!(
+            out,
+            "file_monitor_scan_duration_seconds {}",
+            metrics.scan_duration_seconds
+        );
+
+        header(
+            &mut out,
+            "file_monitor_last_scan_timestamp_seconds",
+            "gauge",
+            "最近一次扫描的 Unix 时间戳",
+        );
+        let _ = writeln!(
+            out,
+            "file_monitor_last_scan_timestamp_seconds {}",
+            metrics.last_scan.map(|t| t.timestamp()).unwrap_or_default()
+        );
+
+        for (name, help, value) in [
+            (
+                "file_monitor_scans_total",
+                "完成的扫描次数",
+                metrics.scans_total,
+            ),
+            (
+                "file_monitor_files_examined_total",
+                "读取了时间戳的文件总数",
+                metrics.files_examined_total,
+            ),
+            (
+                "file_monitor_scan_errors_total",
+                "扫描失败的目录和根目录总数",
+                metrics.scan_errors_total,
+            ),
+        ] {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        out
+    }
+
+    /// 原子性地写入 textfile collector 使用的 `.prom` 文件
+    ///
+    /// 先写入同目录下的临时文件再重命名，node_exporter 不会读到写了一半的文件。
+    pub fn write_textfile(&self, path: &Path) -> Result<()> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+
+        fs::write(&temp_path, self.render())
+            .with_context(|| format!("无法写入指标文件: {}", Path::new(&temp_path).display()))?;
+        fs::rename(&temp_path, path)
+            .with_context(|| format!("无法更新指标文件: {}", path.display()))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
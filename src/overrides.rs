@@ -0,0 +1,104 @@
+//! `[directories."<名称或通配符>"]` 按二级目录覆盖监控参数
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use globset::{Glob, GlobMatcher};
+use log::warn;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::config::{DirectoryOverride, MonitorConfig};
+
+/// 按目录名查找覆盖设置
+///
+/// 与目录名完全相同的键优先；否则按键的字典序使用第一个匹配的通配符。
+/// 配置了多个根目录时，键也可以写成 "根目录标签/目录名" 的形式。
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryOverrides {
+    label: Option<String>,
+    exact: BTreeMap<String, Arc<DirectoryOverride>>,
+    globs: Vec<(GlobMatcher, Arc<DirectoryOverride>)>,
+}
+
+impl DirectoryOverrides {
+    pub fn new(directories: Option<&BTreeMap<String, DirectoryOverride>>) -> Result<Self> {
+        let mut overrides = Self::default();
+        for (pattern, settings) in directories.into_iter().flatten() {
+            let settings = Arc::new(settings.clone());
+            overrides
+                .exact
+                .insert(pattern.clone(), Arc::clone(&settings));
+            if pattern.contains(['*', '?', '[', '{']) {
+                let matcher = Glob::new(pattern)
+                    .with_context(|| format!("无效的目录覆盖模式 '{}'", pattern))?
+                    .compile_matcher();
+                overrides.globs.push((matcher, settings));
+            }
+        }
+        Ok(overrides)
+    }
+
+    /// 根据配置创建；模式无效时记录警告并忽略所有覆盖设置
+    pub fn from_config(directories: Option<&BTreeMap<String, DirectoryOverride>>) -> Self {
+        Self::new(directories).unwrap_or_else(|e| {
+            warn!("{:#}，已忽略目录覆盖设置", e);
+            Self::default()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty()
+    }
+
+    /// 用于某个根目录的副本，该根目录下的目录也可以按 "标签/目录名" 匹配
+    pub fn for_root(&self, label: &str) -> Self {
+        Self {
+            label: Some(label.to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// 查找目录的覆盖设置
+    pub fn find(&self, name: &str) -> Option<&Arc<DirectoryOverride>> {
+        let qualified = self
+            .label
+            .as_ref()
+            .map(|label| format!("{}/{}", label, name));
+        let names: Vec<&str> = qualified.as_deref().into_iter().chain([name]).collect();
+
+        names
+            .iter()
+            .find_map(|name| self.exact.get(*name))
+            .or_else(|| {
+                self.globs
+                    .iter()
+                    .find(|(matcher, _)| names.iter().any(|name| matcher.is_match(name)))
+                    .map(|(_, settings)| settings)
+            })
+    }
+}
+
+/// 目录实际使用的监控配置和时间阈值
+///
+/// `check_hours` 被覆盖时，阈值按与全局 `check_hours` 的差值平移，
+/// 因此调用方指定的阈值仍然有效。
+pub fn apply_override(
+    settings: &DirectoryOverride,
+    config: &Arc<MonitorConfig>,
+    threshold_time: DateTime<Local>,
+) -> (Arc<MonitorConfig>, DateTime<Local>) {
+    let threshold_time = override_threshold(settings, config.check_hours, threshold_time);
+    (Arc::new(settings.apply(config)), threshold_time)
+}
+
+/// 按覆盖的 `check_hours` 平移全局 `check_hours` 对应的时间阈值
+pub fn override_threshold(
+    settings: &DirectoryOverride,
+    check_hours: u64,
+    threshold_time: DateTime<Local>,
+) -> DateTime<Local> {
+    match settings.check_hours {
+        Some(hours) => threshold_time + Duration::hours(check_hours as i64 - hours as i64),
+        None => threshold_time,
+    }
+}
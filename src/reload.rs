@@ -0,0 +1,139 @@
+//! 配置文件热重载：收到 SIGHUP 或配置文件在磁盘上变化时触发
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// 编辑器保存时通常会连续产生多个事件，等待片刻后合并为一次重载
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 触发重载的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadTrigger {
+    /// 收到 SIGHUP
+    Signal,
+    /// 配置文件被修改、替换或重新创建
+    FileChanged,
+}
+
+impl std::fmt::Display for ReloadTrigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Signal => "收到 SIGHUP",
+            Self::FileChanged => "配置文件已修改",
+        })
+    }
+}
+
+/// 监听配置文件的变化和 SIGHUP 信号
+pub struct ConfigWatcher {
+    // 保持订阅有效，释放后事件流即停止
+    _watcher: Option<RecommendedWatcher>,
+    events: mpsc::UnboundedReceiver<()>,
+    hangup: Option<Hangup>,
+}
+
+impl ConfigWatcher {
+    /// 订阅配置文件所在目录的事件（编辑器常以重命名方式保存文件），并注册 SIGHUP
+    ///
+    /// 任一方式不可用时只记录警告，另一种方式仍然有效。
+    pub fn new(config_path: &Path) -> Self {
+        let (tx, events) = mpsc::unbounded_channel();
+        let watcher = match watch_file(config_path, tx) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("无法监听配置文件变化: {:#}", e);
+                None
+            }
+        };
+
+        Self {
+            _watcher: watcher,
+            events,
+            hangup: register_hangup(),
+        }
+    }
+
+    /// 等待下一次重载请求；两种方式都不可用时永远不会返回
+    pub async fn changed(&mut self) -> ReloadTrigger {
+        let trigger = tokio::select! {
+            Some(()) = self.events.recv() => ReloadTrigger::FileChanged,
+            Some(()) = recv_hangup(&mut self.hangup) => ReloadTrigger::Signal,
+            else => std::future::pending().await,
+        };
+
+        // 合并短时间内的连续事件
+        tokio::time::sleep(DEBOUNCE).await;
+        while self.events.try_recv().is_ok() {}
+        trigger
+    }
+}
+
+#[cfg(unix)]
+type Hangup = tokio::signal::unix::Signal;
+
+#[cfg(not(unix))]
+type Hangup = std::convert::Infallible;
+
+#[cfg(unix)]
+fn register_hangup() -> Option<Hangup> {
+    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .map_err(|e| warn!("无法注册 SIGHUP 处理: {}", e))
+        .ok()
+}
+
+#[cfg(not(unix))]
+fn register_hangup() -> Option<Hangup> {
+    None
+}
+
+#[cfg(unix)]
+async fn recv_hangup(hangup: &mut Option<Hangup>) -> Option<()> {
+    match hangup {
+        Some(hangup) => hangup.recv().await,
+        None => None,
+    }
+}
+
+#[cfg(not(unix))]
+async fn recv_hangup(_hangup: &mut Option<Hangup>) -> Option<()> {
+    None
+}
+
+fn watch_file(config_path: &Path, tx: mpsc::UnboundedSender<()>) -> Result<RecommendedWatcher> {
+    let file_name: OsString = config_path
+        .file_name()
+        .with_context(|| format!("无效的配置文件路径: {}", config_path.display()))?
+        .to_owned();
+    let parent = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == Some(file_name.as_os_str()))
+        {
+            debug!("配置文件事件: {:?}", event.kind);
+            let _ = tx.send(());
+        }
+    })
+    .context("无法创建文件系统事件监听器")?;
+
+    watcher
+        .watch(parent, RecursiveMode::NonRecursive)
+        .with_context(|| format!("无法订阅目录事件: {}", parent.display()))?;
+    Ok(watcher)
+}
@@ -0,0 +1,378 @@
+//! 扫描结果的输出
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::config::OutputConfig;
+use crate::scanner::{DirectoryResult, FileMatch, ScanReport, ScanRoot};
+use crate::transition::{StatusTransition, TransitionKind};
+use crate::writers::OpenWriter;
+
+/// 报告输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// 人类可读的文本报告
+    #[default]
+    Text,
+    /// 每次扫描输出一个格式化的 JSON 对象
+    Json,
+    /// 每次扫描输出一行 JSON 对象
+    Ndjson,
+    /// 每个目录一行 CSV，表头只输出一次
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            _ => Err(anyhow::anyhow!(
+                "不支持的输出格式: {}（可选值: text/json/ndjson/csv）",
+                s
+            )),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// 是否为机器可读格式（此时不应清屏或输出装饰文本）
+    pub fn is_machine_readable(self) -> bool {
+        self != Self::Text
+    }
+}
+
+/// 单个目录的机器可读记录
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryRecord<'a> {
+    pub directory: &'a str,
+    /// 所属根目录的标签（仅在配置了多个根目录时输出）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<&'a str>,
+    pub status: &'static str,
+    /// `unavailable` / `error` 状态的原因
+    pub reason: Option<&'a str>,
+    pub scanned_at: DateTime<Local>,
+    pub scan_duration_ms: f64,
+    /// 本次扫描中发生的状态变化
+    pub transition: Option<TransitionKind>,
+    /// 正在写入该目录的进程
+    pub writers: &'a [OpenWriter],
+    /// 扫描中找到的最新文件
+    pub newest_file: Option<NewestFileRecord>,
+}
+
+/// 最新文件的机器可读记录
+#[derive(Debug, Clone, Serialize)]
+pub struct NewestFileRecord {
+    /// 相对于目录的路径
+    pub path: String,
+    pub time: DateTime<Local>,
+    /// 距扫描时间的秒数
+    pub age_seconds: i64,
+    pub size: u64,
+}
+
+impl NewestFileRecord {
+    fn new(file: &FileMatch, scanned_at: DateTime<Local>) -> Self {
+        Self {
+            path: file.path.display().to_string(),
+            time: file.time,
+            age_seconds: file_age(file, scanned_at).num_seconds(),
+            size: file.size,
+        }
+    }
+}
+
+/// CSV 不支持嵌套字段，写入进程合并为一列，最新文件展开为多列
+///
+/// 表头只输出一次，`root` 列始终存在，只有一个根目录时为空。
+#[derive(Debug, Clone, Serialize)]
+struct CsvRecord<'a> {
+    directory: &'a str,
+    root: Option<&'a str>,
+    status: &'static str,
+    reason: Option<&'a str>,
+    scanned_at: DateTime<Local>,
+    scan_duration_ms: f64,
+    transition: Option<TransitionKind>,
+    writers: String,
+    newest_file: Option<String>,
+    newest_file_time: Option<DateTime<Local>>,
+    newest_file_age_seconds: Option<i64>,
+    newest_file_size: Option<u64>,
+}
+
+impl<'a> From<DirectoryRecord<'a>> for CsvRecord<'a> {
+    fn from(record: DirectoryRecord<'a>) -> Self {
+        Self {
+            directory: record.directory,
+            root: record.root,
+            status: record.status,
+            reason: record.reason,
+            scanned_at: record.scanned_at,
+            scan_duration_ms: record.scan_duration_ms,
+            transition: record.transition,
+            writers: format_writers(record.writers),
+            newest_file_time: record.newest_file.as_ref().map(|f| f.time),
+            newest_file_age_seconds: record.newest_file.as_ref().map(|f| f.age_seconds),
+            newest_file_size: record.newest_file.as_ref().map(|f| f.size),
+            newest_file: record.newest_file.map(|f| f.path),
+        }
+    }
+}
+
+/// 一次扫描的机器可读记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanRecord<'a> {
+    pub root_path: String,
+    pub scanned_at: DateTime<Local>,
+    pub scan_duration_ms: f64,
+    pub directories: Vec<DirectoryRecord<'a>>,
+    pub transitions: &'a [StatusTransition],
+    /// 配置了多个根目录时的各根目录
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub roots: &'a [ScanRoot],
+}
+
+impl<'a> ScanRecord<'a> {
+    pub fn new(report: &'a ScanReport) -> Self {
+        let scan_duration_ms = report.scan_duration.as_secs_f64() * 1000.0;
+        Self {
+            root_path: report.root_path.display().to_string(),
+            scanned_at: report.scanned_at,
+            scan_duration_ms,
+            directories: report
+                .directories
+                .iter()
+                .map(|dir| DirectoryRecord {
+                    directory: &dir.name,
+                    root: dir.root.as_deref(),
+                    status: dir.status.as_str(),
+                    reason: dir.status.reason(),
+                    scanned_at: report.scanned_at,
+                    scan_duration_ms,
+                    transition: report.transition_for(&dir.name).map(|t| t.kind),
+                    writers: &dir.writers,
+                    newest_file: dir
+                        .newest_file
+                        .as_ref()
+                        .map(|file| NewestFileRecord::new(file, report.scanned_at)),
+                })
+                .collect(),
+            transitions: &report.transitions,
+            roots: &report.roots,
+        }
+    }
+}
+
+/// 按配置的格式输出扫描报告，在持续监控中保持跨扫描的状态（如 CSV 表头）
+#[derive(Debug)]
+pub struct ReportWriter<W: Write> {
+    format: OutputFormat,
+    out: W,
+    csv_header_written: bool,
+}
+
+impl ReportWriter<io::Stdout> {
+    pub fn stdout(format: OutputFormat) -> Self {
+        Self::new(format, io::stdout())
+    }
+}
+
+impl<W: Write> ReportWriter<W> {
+    pub fn new(format: OutputFormat, out: W) -> Self {
+        Self {
+            format,
+            out,
+            csv_header_written: false,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    pub fn write(&mut self, report: &ScanReport, output: &OutputConfig) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => write_status_report(&mut self.out, report, output)?,
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut self.out, &ScanRecord::new(report))?;
+                writeln!(self.out)?;
+            }
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, &ScanRecord::new(report))?;
+                writeln!(self.out)?;
+            }
+            OutputFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(!self.csv_header_written)
+                    .from_writer(&mut self.out);
+                for record in ScanRecord::new(report).directories {
+                    writer.serialize(CsvRecord::from(record))?;
+                }
+                writer.flush()?;
+                // 没有目录时 csv 不会写出表头，留到下一次扫描
+                self.csv_header_written |= !report.directories.is_empty();
+            }
+        }
+
+        self.out.flush().context("无法输出扫描报告")
+    }
+}
+
+pub fn print_status_report(report: &ScanReport, output: &OutputConfig) {
+    let _ = write_status_report(&mut io::stdout(), report, output);
+}
+
+fn write_status_report(
+    out: &mut impl Write,
+    report: &ScanReport,
+    output: &OutputConfig,
+) -> io::Result<()> {
+    let current_time = Local::now().format("%Y-%m-%d %H:%M:%S");
+    writeln!(out, "\n=== [报告] 文件监控报告 [{}] ===", current_time)?;
+
+    if report.roots.is_empty() {
+        if report.directories.is_empty() {
+            writeln!(out, "[警告] 未找到任何二级目录")?;
+            return write_transitions(out, report);
+        }
+
+        // 报告中的目录已按目录名排序
+        for dir in &report.directories {
+            write_directory(out, dir, report, output)?;
+        }
+    } else {
+        // 多个根目录时按根目录分组
+        for root in &report.roots {
+            writeln!(
+                out,
+                "--- [根目录] {} ({}) ---",
+                root.label,
+                root.path.display()
+            )?;
+            if let Some(error) = &root.error {
+                writeln!(out, "[警告] 根目录不可访问: {}", error)?;
+                continue;
+            }
+
+            let mut directories = report.directories_in(&root.label).peekable();
+            if directories.peek().is_none() {
+                writeln!(out, "[警告] 未找到任何二级目录")?;
+            }
+            for dir in directories {
+                write_directory(out, dir, report, output)?;
+            }
+        }
+    }
+
+    write_transitions(out, report)?;
+    writeln!(out, "=======================================\n")
+}
+
+fn write_directory(
+    out: &mut impl Write,
+    dir: &DirectoryResult,
+    report: &ScanReport,
+    output: &OutputConfig,
+) -> io::Result<()> {
+    let mut details = Vec::new();
+    if let Some(reason) = dir.status.reason() {
+        details.push(reason.to_string());
+    }
+    if !dir.writers.is_empty() {
+        details.push(format!("写入进程: {}", format_writers(&dir.writers)));
+    }
+    if let Some(file) = &dir.newest_file {
+        details.push(format!(
+            "最新文件: {}, {}, {}前, {}",
+            file.path.display(),
+            file.time.format("%Y-%m-%d %H:%M:%S"),
+            format_duration(file_age(file, report.scanned_at)),
+            format_size(file.size)
+        ));
+    }
+
+    write!(
+        out,
+        "{} 目录 '{}': {}",
+        output.icon(&dir.status),
+        dir.short_name(),
+        output.message_for(dir)
+    )?;
+    if details.is_empty() {
+        writeln!(out)?;
+    } else {
+        writeln!(out, " ({})", details.join("; "))?;
+    }
+    Ok(())
+}
+
+/// 文件距扫描时间的时长，文件时间晚于扫描时间时为零
+fn file_age(file: &FileMatch, scanned_at: DateTime<Local>) -> Duration {
+    (scanned_at - file.time).max(Duration::zero())
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes >= 60 {
+        format!("{}小时{}分钟", minutes / 60, minutes % 60)
+    } else if minutes > 0 {
+        format!("{}分钟", minutes)
+    } else {
+        format!("{}秒", duration.num_seconds())
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 以 "command[pid]" 的形式列出写入进程，同一进程只列一次
+fn format_writers(writers: &[OpenWriter]) -> String {
+    let mut names: Vec<String> = writers
+        .iter()
+        .map(|w| format!("{}[{}]", w.command, w.pid))
+        .collect();
+    names.dedup();
+    names.join(", ")
+}
+
+fn write_transitions(out: &mut impl Write, report: &ScanReport) -> io::Result<()> {
+    for transition in &report.transitions {
+        let action = match transition.kind {
+            TransitionKind::RecordingStarted => "开始录制",
+            TransitionKind::RecordingStopped => "停止录制",
+        };
+        writeln!(
+            out,
+            "[变化] 目录 '{}': {} ({})",
+            transition.directory,
+            action,
+            transition.at.format("%Y-%m-%d %H:%M:%S")
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1859 @@
+//! 二级目录扫描逻辑
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use futures::future::join_all;
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use tokio::task;
+use walkdir::WalkDir;
+
+use crate::backoff::ScanBackoff;
+use crate::cache::{CachedDirectory, CachedFile, ScanCache};
+use crate::config::{Config, DirectoryOverride, MonitorConfig};
+use crate::filter::PathFilter;
+use crate::growth::{FileSample, GrowthTracker, DEFAULT_MIN_GROWTH_BYTES, GROWTH_SAMPLE_FILES};
+use crate::overrides::{self, DirectoryOverrides};
+use crate::status::DirectoryStatus;
+use crate::transition::StatusTransition;
+use crate::traversal;
+use crate::writers::{self, OpenWriter};
+
+/// 单个二级目录的扫描结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryResult {
+    /// 目录名（相对于根目录）；配置了多个根目录时为 "根目录标签/目录名"
+    pub name: String,
+    /// 所属根目录的标签（仅在配置了多个根目录时设置）
+    pub root: Option<String>,
+    /// 目录的完整路径
+    pub path: PathBuf,
+    /// 录制状态
+    pub status: DirectoryStatus,
+    /// 正在写入该目录的进程（启用 `detect_open_writers` 时填充）
+    pub writers: Vec<OpenWriter>,
+    /// 扫描中找到的最新文件
+    pub newest_file: Option<FileMatch>,
+    /// 扫描时读取了时间戳的文件数
+    pub files_examined: u64,
+    /// 匹配该目录的 `[directories]` 覆盖设置
+    pub overrides: Option<Arc<DirectoryOverride>>,
+}
+
+impl DirectoryResult {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>, status: DirectoryStatus) -> Self {
+        Self {
+            name: name.into(),
+            root: None,
+            path: path.into(),
+            status,
+            writers: Vec::new(),
+            newest_file: None,
+            files_examined: 0,
+            overrides: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.status.is_recording()
+    }
+
+    /// 不带根目录标签前缀的目录名
+    pub fn short_name(&self) -> &str {
+        self.root
+            .as_deref()
+            .and_then(|root| self.name.strip_prefix(root)?.strip_prefix('/'))
+            .unwrap_or(&self.name)
+    }
+}
+
+/// 配置了多个根目录时，报告中的一个根目录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanRoot {
+    pub label: String,
+    pub path: PathBuf,
+    /// 根目录无法访问时的原因
+    pub error: Option<String>,
+}
+
+/// 一次完整扫描的结构化结果
+#[derive(Debug, Clone)]
+pub struct ScanReport {
+    /// 监控的根目录
+    pub root_path: PathBuf,
+    /// 扫描开始时间
+    pub scanned_at: DateTime<Local>,
+    /// 判断新文件使用的时间阈值
+    pub threshold_time: DateTime<Local>,
+    /// 扫描耗时
+    pub scan_duration: std::time::Duration,
+    /// 实际使用的并行模式
+    pub parallel_mode: String,
+    /// 按目录名排序的各目录结果
+    pub directories: Vec<DirectoryResult>,
+    /// 与上一次扫描相比的状态变化（由 `TransitionTracker` 填充）
+    pub transitions: Vec<StatusTransition>,
+    /// 配置了多个根目录时的各根目录；单个根目录时为空，只有根目录不可访问时包含它
+    pub roots: Vec<ScanRoot>,
+}
+
+impl ScanReport {
+    /// 创建不包含任何目录的报告（根目录不可用时使用）
+    pub fn empty(root_path: impl Into<PathBuf>, threshold_time: DateTime<Local>) -> Self {
+        Self {
+            root_path: root_path.into(),
+            scanned_at: Local::now(),
+            threshold_time,
+            scan_duration: std::time::Duration::ZERO,
+            parallel_mode: String::new(),
+            directories: Vec::new(),
+            transitions: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// 创建根目录不可访问时的报告，原因记录在 `roots` 中
+    pub fn unavailable(
+        root_path: impl Into<PathBuf>,
+        threshold_time: DateTime<Local>,
+        error: impl Into<String>,
+    ) -> Self {
+        let mut report = Self::empty(root_path, threshold_time);
+        report.roots.push(ScanRoot {
+            label: report.root_path.display().to_string(),
+            path: report.root_path.clone(),
+            error: Some(error.into()),
+        });
+        report
+    }
+
+    /// 按目录名查找结果
+    pub fn get(&self, name: &str) -> Option<&DirectoryResult> {
+        self.directories.iter().find(|d| d.name == name)
+    }
+
+    /// 目录在本次扫描中的状态变化
+    pub fn transition_for(&self, name: &str) -> Option<&StatusTransition> {
+        self.transitions.iter().find(|t| t.directory == name)
+    }
+
+    /// 正在录制的目录数量
+    pub fn recording_count(&self) -> usize {
+        self.directories.iter().filter(|d| d.is_recording()).count()
+    }
+
+    /// 属于指定根目录的结果
+    pub fn directories_in<'a>(
+        &'a self,
+        root: &'a str,
+    ) -> impl Iterator<Item = &'a DirectoryResult> + 'a {
+        self.directories
+            .iter()
+            .filter(move |d| d.root.as_deref() == Some(root))
+    }
+
+    /// 本次扫描读取了时间戳的文件总数
+    pub fn files_examined(&self) -> u64 {
+        self.directories.iter().map(|d| d.files_examined).sum()
+    }
+}
+
+/// 基于 `MonitorConfig` 的目录扫描器
+///
+/// 增长检测模式（`detection_mode = "growth"`）需要比较前后两次扫描，
+/// 因此应在多次扫描之间复用同一个 `Scanner`。
+/// 配置了 `[[monitor.roots]]` 时，每个根目录由单独的扫描器并发扫描后合并。
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    config: Arc<MonitorConfig>,
+    filter: Arc<PathFilter>,
+    growth: Option<Arc<GrowthTracker>>,
+    overrides: Arc<DirectoryOverrides>,
+    roots: Arc<[(String, Scanner)]>,
+    /// `parallel` 模式专用的线程池，大小为 `max_parallel_tasks`
+    pool: Option<Arc<rayon::ThreadPool>>,
+    /// 增量扫描缓存，多个根目录共用同一个
+    cache: Option<Arc<ScanCache>>,
+    /// 扫描超时目录的退避状态
+    backoff: Arc<ScanBackoff>,
+    /// 上次扫描中因超过 `scan_deadline` 而未能完成扫描的目录
+    unfinished: Arc<Mutex<HashSet<PathBuf>>>,
+    /// 每个目录开始扫描时调用的回调
+    hook: Option<DirectoryHook>,
+}
+
+/// 目录开始扫描时调用的回调，参数为目录路径
+#[derive(Clone)]
+struct DirectoryHook(Arc<dyn Fn(&Path) + Send + Sync>);
+
+impl fmt::Debug for DirectoryHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DirectoryHook")
+    }
+}
+
+impl Scanner {
+    pub fn new(config: MonitorConfig) -> Self {
+        let growth = (config.detection_mode.as_deref() == Some("growth")).then(|| {
+            Arc::new(GrowthTracker::new(
+                config.min_growth_bytes.unwrap_or(DEFAULT_MIN_GROWTH_BYTES),
+            ))
+        });
+
+        let roots = config
+            .roots()
+            .iter()
+            .map(|root| {
+                (
+                    root.label().to_string(),
+                    Scanner::new(config.for_root(root)),
+                )
+            })
+            .collect::<Arc<[_]>>();
+
+        // 多个根目录时由各根目录的扫描器各自创建线程池
+        let pool = (roots.is_empty() && config.parallel_mode.as_deref() == Some("parallel"))
+            .then(|| build_pool(config.max_parallel_tasks.unwrap_or_else(num_cpus::get)))
+            .flatten();
+
+        let cache = config
+            .incremental_enabled()
+            .then(|| Arc::new(ScanCache::new(&config)));
+
+        let mut scanner = Self {
+            filter: Arc::new(PathFilter::from_config(&config)),
+            config: Arc::new(config),
+            growth,
+            overrides: Arc::default(),
+            roots,
+            pool,
+            cache: None,
+            backoff: Arc::default(),
+            unfinished: Arc::default(),
+            hook: None,
+        };
+        if let Some(cache) = cache {
+            scanner.share_cache(cache);
+        }
+        scanner
+    }
+
+    /// 根据完整配置创建扫描器，包括 `[directories]` 覆盖设置
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.monitor.clone())
+            .with_directory_overrides(DirectoryOverrides::from_config(config.directories.as_ref()))
+    }
+
+    /// 对匹配的二级目录应用覆盖设置
+    pub fn with_directory_overrides(mut self, overrides: DirectoryOverrides) -> Self {
+        self.roots = self
+            .roots
+            .iter()
+            .map(|(label, scanner)| {
+                let scanner = scanner
+                    .clone()
+                    .with_directory_overrides(overrides.for_root(label));
+                (label.clone(), scanner)
+            })
+            .collect();
+        self.overrides = Arc::new(overrides);
+        self
+    }
+
+    /// 在扫描线程中、每个目录开始扫描之前调用 `hook`（用于测试和诊断，例如统计并发数）
+    ///
+    /// 列出根目录之前也会以根目录路径调用一次。仅供测试使用，不属于稳定的公开接口。
+    #[doc(hidden)]
+    pub fn with_directory_hook(mut self, hook: impl Fn(&Path) + Send + Sync + 'static) -> Self {
+        self.set_hook(DirectoryHook(Arc::new(hook)));
+        self
+    }
+
+    fn set_hook(&mut self, hook: DirectoryHook) {
+        self.roots = self
+            .roots
+            .iter()
+            .map(|(label, scanner)| {
+                let mut scanner = scanner.clone();
+                scanner.set_hook(hook.clone());
+                (label.clone(), scanner)
+            })
+            .collect();
+        self.hook = Some(hook);
+    }
+
+    /// 使用指定的增量扫描缓存（例如从文件读取的缓存）代替内存中的缓存
+    pub fn with_scan_cache(mut self, cache: ScanCache) -> Self {
+        self.share_cache(Arc::new(cache));
+        self
+    }
+
+    fn share_cache(&mut self, cache: Arc<ScanCache>) {
+        self.roots = self
+            .roots
+            .iter()
+            .map(|(label, scanner)| {
+                let mut scanner = scanner.clone();
+                scanner.share_cache(Arc::clone(&cache));
+                (label.clone(), scanner)
+            })
+            .collect();
+        self.cache = Some(cache);
+    }
+
+    /// 沿用重新加载配置前的扫描器中仍然有效的状态
+    ///
+    /// 超时退避和未完成目录按路径记录，总是沿用；增长基准在 `min_growth_bytes` 不变时沿用；
+    /// 增量扫描缓存在缓存文件和影响读取结果的配置都不变时沿用。多个根目录按标签对应。
+    pub fn with_state_from(mut self, previous: &Scanner) -> Self {
+        self.backoff = Arc::clone(&previous.backoff);
+        self.unfinished = Arc::clone(&previous.unfinished);
+        if let (Some(growth), Some(previous_growth)) = (&self.growth, &previous.growth) {
+            if growth.min_growth_bytes() == previous_growth.min_growth_bytes() {
+                self.growth = Some(Arc::clone(previous_growth));
+            }
+        }
+
+        self.roots = self
+            .roots
+            .iter()
+            .map(|(label, scanner)| {
+                let scanner = match previous.roots.iter().find(|(l, _)| l == label) {
+                    Some((_, previous)) => scanner.clone().with_state_from(previous),
+                    None => scanner.clone(),
+                };
+                (label.clone(), scanner)
+            })
+            .collect();
+
+        if let (Some(cache), Some(previous_cache)) = (&self.cache, &previous.cache) {
+            if cache.path() == previous_cache.path() && previous_cache.is_compatible(&self.config) {
+                let previous_cache = Arc::clone(previous_cache);
+                self.share_cache(previous_cache);
+            }
+        }
+        self
+    }
+
+    pub fn config(&self) -> &MonitorConfig {
+        &self.config
+    }
+
+    /// 增量扫描缓存，未启用 `incremental` 时为 `None`
+    pub fn scan_cache(&self) -> Option<&ScanCache> {
+        self.cache.as_deref()
+    }
+
+    pub fn root_path(&self) -> &Path {
+        Path::new(&self.config.root_path)
+    }
+
+    /// 实际扫描的所有根目录
+    pub fn root_paths(&self) -> Vec<&Path> {
+        if self.roots.is_empty() {
+            return vec![self.root_path()];
+        }
+        self.roots
+            .iter()
+            .map(|(_, scanner)| scanner.root_path())
+            .collect()
+    }
+
+    /// 根据 `check_hours` 计算当前的时间阈值
+    pub fn threshold_time(&self) -> DateTime<Local> {
+        Local::now() - Duration::hours(self.config.check_hours as i64)
+    }
+
+    /// 使用当前时间阈值扫描所有二级目录
+    ///
+    /// 多个根目录时各自使用覆盖后的 `check_hours`。
+    pub async fn scan(&self) -> Result<ScanReport> {
+        self.run_scan(None).await
+    }
+
+    /// 使用指定的时间阈值扫描所有二级目录
+    pub async fn scan_with_threshold(&self, threshold_time: DateTime<Local>) -> Result<ScanReport> {
+        self.run_scan(Some(threshold_time)).await
+    }
+
+    async fn run_scan(&self, threshold_time: Option<DateTime<Local>>) -> Result<ScanReport> {
+        let full_scan = self.cache.as_ref().map(|cache| cache.begin_scan());
+
+        let report = if self.roots.is_empty() {
+            let threshold_time = threshold_time.unwrap_or_else(|| self.threshold_time());
+            match self.scan_directories(threshold_time).await {
+                Ok(report) => Ok(report),
+                Err(e) => {
+                    error!("{:#}", e);
+                    Ok(ScanReport::unavailable(
+                        self.root_path(),
+                        threshold_time,
+                        format!("{:#}", e),
+                    ))
+                }
+            }
+        } else {
+            self.scan_roots(threshold_time).await
+        };
+
+        if let (Some(cache), Some(full_scan)) = (&self.cache, full_scan) {
+            let (hits, misses) = cache.stats();
+            info!(
+                "增量扫描{}: {} 个目录沿用缓存，{} 个目录重新读取",
+                if full_scan { "（全量）" } else { "" },
+                hits,
+                misses
+            );
+            if let Err(e) = cache.save() {
+                warn!("{:#}", e);
+            }
+        }
+        report
+    }
+
+    /// 扫描单个根目录下的所有二级目录
+    async fn scan_directories(&self, threshold_time: DateTime<Local>) -> Result<ScanReport> {
+        let scanned_at = Local::now();
+        let (mut directories, scan_duration) =
+            self.check_subdirectories_async(threshold_time).await?;
+        directories.sort_by(|a, b| a.name.cmp(&b.name));
+        if let Some(growth) = &self.growth {
+            growth.retain(directories.iter().map(|dir| dir.path.as_path()));
+        }
+
+        if self.config.detect_open_writers.unwrap_or(false) {
+            self.attach_open_writers(&mut directories).await;
+        }
+
+        Ok(ScanReport {
+            root_path: self.root_path().to_path_buf(),
+            scanned_at,
+            threshold_time,
+            scan_duration,
+            parallel_mode: self.parallel_mode().to_string(),
+            directories,
+            transitions: Vec::new(),
+            roots: Vec::new(),
+        })
+    }
+
+    /// 并发扫描所有根目录并合并结果，目录名加上根目录标签前缀以保证唯一
+    async fn scan_roots(&self, threshold_time: Option<DateTime<Local>>) -> Result<ScanReport> {
+        let scanned_at = Local::now();
+        let scans = self.roots.iter().map(|(label, scanner)| async move {
+            let threshold_time = threshold_time.unwrap_or_else(|| scanner.threshold_time());
+            let report = scanner.scan_directories(threshold_time).await;
+            (label, scanner, report)
+        });
+
+        let mut report = ScanReport::empty(
+            self.root_path(),
+            threshold_time.unwrap_or_else(|| self.threshold_time()),
+        );
+        report.scanned_at = scanned_at;
+        report.parallel_mode = self.parallel_mode().to_string();
+
+        for (label, scanner, result) in join_all(scans).await {
+            let mut root = ScanRoot {
+                label: label.clone(),
+                path: scanner.root_path().to_path_buf(),
+                error: None,
+            };
+            match result {
+                Ok(root_report) => {
+                    // 各根目录并发扫描，总耗时取最慢的根目录
+                    report.scan_duration = report.scan_duration.max(root_report.scan_duration);
+                    report
+                        .directories
+                        .extend(root_report.directories.into_iter().map(|mut dir| {
+                            dir.name = format!("{}/{}", label, dir.name);
+                            dir.root = Some(label.clone());
+                            dir
+                        }));
+                }
+                Err(e) => {
+                    error!("扫描根目录 '{}' 失败: {:#}", label, e);
+                    root.error = Some(format!("{:#}", e));
+                }
+            }
+            report.roots.push(root);
+        }
+
+        Ok(report)
+    }
+
+    /// 使用指定的时间阈值检查单个目录的状态
+    pub fn directory_status(
+        &self,
+        dir_path: &Path,
+        threshold_time: DateTime<Local>,
+    ) -> DirectoryStatus {
+        self.check_directory(dir_path, threshold_time).status
+    }
+
+    /// 使用指定的时间阈值检查单个目录，返回包含最新文件的完整结果
+    pub fn check_directory(
+        &self,
+        dir_path: &Path,
+        threshold_time: DateTime<Local>,
+    ) -> DirectoryResult {
+        let name = dir_path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        DirectoryTask::new(
+            name,
+            dir_path.to_path_buf(),
+            threshold_time,
+            self.stale_time(threshold_time),
+            &self.config,
+            &self.overrides,
+        )
+        .run(&self.context())
+    }
+
+    fn context(&self) -> ScanContext {
+        ScanContext {
+            filter: Arc::clone(&self.filter),
+            growth: self.growth.clone(),
+            cache: self.cache.clone(),
+            hook: self.hook.clone(),
+        }
+    }
+
+    /// 根据 `stale_minutes` 计算软阈值；未设置或不小于 `check_hours` 时返回 `None`
+    fn stale_time(&self, threshold_time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let stale_time = Local::now() - Duration::minutes(self.config.stale_minutes? as i64);
+        (stale_time > threshold_time).then_some(stale_time)
+    }
+
+    /// 把正在写入的进程归入对应目录；有写入进程的空闲或停滞目录同样视为录制中
+    async fn attach_open_writers(&self, directories: &mut [DirectoryResult]) {
+        let root_path = self.root_path().to_path_buf();
+        let mut found = match task::spawn_blocking(move || writers::find_open_writers(&root_path))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result)
+        {
+            Ok(found) => found,
+            Err(e) => {
+                warn!("无法检测写入进程: {:#}", e);
+                return;
+            }
+        };
+
+        // /proc 中的路径都是规范化路径
+        let canonical_root = self
+            .root_path()
+            .canonicalize()
+            .unwrap_or_else(|_| self.root_path().to_path_buf());
+        for dir in directories {
+            let Some(dir_writers) = found.remove(&dir.name) else {
+                continue;
+            };
+            let dir_path = canonical_root.join(&dir.name);
+            dir.writers = dir_writers
+                .into_iter()
+                .filter(|writer| self.filter.matches_file(&dir_path, &writer.path))
+                .collect();
+            // 扫描失败的目录保持失败状态，写入进程只显示在报告中
+            let upgradable = matches!(dir.status, DirectoryStatus::Idle | DirectoryStatus::Stale);
+            if !dir.writers.is_empty() && upgradable {
+                debug!("目录 '{}' 有进程正在写入，视为录制中", dir.name);
+                dir.status = DirectoryStatus::Recording;
+            }
+        }
+    }
+
+    fn parallel_mode(&self) -> &str {
+        self.config.parallel_mode.as_deref().unwrap_or("sync")
+    }
+
+    /// 列出根目录下未被排除的二级目录
+    ///
+    /// 设置了时限时在阻塞线程中列出：根目录本身挂起时按时限报告为不可访问，
+    /// 并与超时的目录一样退避，不会卡住整次扫描。
+    async fn list_subdirectories(&self, limits: ScanLimits) -> Result<Vec<(String, PathBuf)>> {
+        let root_path = self.root_path().to_path_buf();
+        let filter = Arc::clone(&self.filter);
+        let hook = self.hook.clone();
+        let list = move || list_subdirectories(&root_path, &filter, hook.as_ref());
+        let root_path = self.root_path();
+        let Some(limit) = limits.first_limit() else {
+            return list();
+        };
+
+        if let Some(status) = self.backoff.skip(root_path) {
+            anyhow::bail!("跳过监控目录 {}: {}", root_path.display(), status);
+        }
+        let running = self.backoff.start(root_path);
+        let handle = task::spawn_blocking(move || {
+            let _running = running;
+            list()
+        });
+        match tokio::time::timeout_at(limit, handle).await {
+            Ok(result) => {
+                let directories = result.context("列出监控目录的任务异常")??;
+                self.backoff.record_success(root_path);
+                Ok(directories)
+            }
+            Err(_) if limits.deadline == Some(limit) => {
+                self.backoff.record_deadline(root_path);
+                anyhow::bail!(
+                    "超过扫描截止时间，未能列出监控目录: {}",
+                    root_path.display()
+                )
+            }
+            Err(_) => {
+                let timeout = limits.timeout.unwrap_or_default().as_secs();
+                self.backoff.record_timeout(root_path);
+                anyhow::bail!(
+                    "列出监控目录超时（超过 {} 秒）: {}",
+                    timeout,
+                    root_path.display()
+                )
+            }
+        }
+    }
+
+    /// 扫描根目录下的所有二级目录，按 `parallel_mode` 选择执行方式
+    async fn check_subdirectories_async(
+        &self,
+        threshold_time: DateTime<Local>,
+    ) -> Result<(Vec<DirectoryResult>, std::time::Duration)> {
+        let stale_time = self.stale_time(threshold_time);
+        let (config, overrides) = (&self.config, &*self.overrides);
+        let context = self.context();
+        let mut results = Vec::new();
+
+        // 确定并行模式
+        let parallel_mode = config.parallel_mode.as_deref().unwrap_or("sync");
+
+        let max_tasks = config.max_parallel_tasks.unwrap_or_else(num_cpus::get);
+
+        // 单个目录的扫描时限，超时的目录报告为不可用；
+        // 整次扫描的截止时间，届时未完成的目录报告为状态未知
+        let limits = ScanLimits {
+            timeout: config.scan_timeout.map(std::time::Duration::from_secs),
+            deadline: config.scan_deadline.map(|seconds| {
+                tokio::time::Instant::now() + std::time::Duration::from_secs(seconds)
+            }),
+        };
+
+        debug!("使用并行模式: {}, 最大任务数: {}", parallel_mode, max_tasks);
+
+        // 收集所有子目录
+        let directories = self.list_subdirectories(limits).await?;
+        let mut directories: Vec<_> = directories
+            .into_iter()
+            .map(|(dir_name, path)| {
+                DirectoryTask::new(
+                    dir_name,
+                    path,
+                    threshold_time,
+                    stale_time,
+                    config,
+                    overrides,
+                )
+            })
+            .collect();
+
+        // 上次扫描因截止时间未能完成（包括未开始和扫描到一半）的目录优先扫描
+        if limits.deadline.is_some() {
+            let unfinished = self.unfinished.lock().unwrap_or_else(|e| e.into_inner());
+            directories.sort_by_key(|dir_task| !unfinished.contains(&dir_task.path));
+        }
+
+        // 上次的扫描线程仍未结束或处于超时退避期间的目录不再扫描
+        if limits.is_set() {
+            directories.retain(|dir_task| match self.backoff.skip(&dir_task.path) {
+                Some(status) => {
+                    debug!("跳过未完成的目录 '{}': {}", dir_task.name, status);
+                    results.push(DirectoryResult {
+                        status,
+                        ..dir_task.pending()
+                    });
+                    false
+                }
+                None => true,
+            });
+        }
+
+        let scan_start = Instant::now();
+
+        match parallel_mode {
+            "async" => {
+                // 异步并发模式，同时运行的扫描任务不超过 max_parallel_tasks 个
+                info!(
+                    "使用异步并发模式扫描 {} 个目录，最多 {} 个并发任务",
+                    directories.len(),
+                    max_tasks
+                );
+                results.extend(
+                    scan_concurrently(directories, &context, limits, &self.backoff, max_tasks)
+                        .await,
+                );
+            }
+            "parallel" => {
+                // CPU 并行模式，优先使用按 max_parallel_tasks 创建的专用线程池，
+                // 而不是与其他代码共享的全局线程池
+                let pool = self.pool.as_deref();
+                info!(
+                    "使用 CPU 并行模式扫描 {} 个目录，线程数: {}",
+                    directories.len(),
+                    pool.map_or_else(rayon::current_num_threads, |p| p.current_num_threads())
+                );
+                if limits.is_set() {
+                    // 设置了时限时与 async 模式一样在阻塞线程中扫描，
+                    // 超时的目录不会一直占住线程池中的线程、挡住后面排队的目录
+                    let threads =
+                        pool.map_or_else(rayon::current_num_threads, |p| p.current_num_threads());
+                    results.extend(
+                        scan_concurrently(directories, &context, limits, &self.backoff, threads)
+                            .await,
+                    );
+                } else {
+                    let scan = || -> Vec<_> {
+                        directories
+                            .into_par_iter()
+                            .map(|dir_task| dir_task.run(&context))
+                            .collect()
+                    };
+                    results.extend(match pool {
+                        Some(pool) => pool.install(scan),
+                        None => scan(),
+                    });
+                }
+            }
+            _ => {
+                // 同步模式（默认）
+                debug!("使用同步模式扫描 {} 个目录", directories.len());
+                for dir_task in directories {
+                    let result = if limits.deadline_passed() {
+                        dir_task.unknown()
+                    } else if limits.is_set() {
+                        // 在阻塞线程中扫描，才能在超时后继续扫描下一个目录
+                        run_blocking(dir_task, context.clone(), limits, &self.backoff).await
+                    } else {
+                        dir_task.run(&context)
+                    };
+                    results.push(result);
+                }
+            }
+        }
+
+        let scan_duration = scan_start.elapsed();
+        info!(
+            "目录扫描完成，耗时 {:.2}ms（模式: {}）",
+            scan_duration.as_millis(),
+            parallel_mode
+        );
+
+        let unfinished: HashSet<PathBuf> = results
+            .iter()
+            .filter(|dir| matches!(dir.status, DirectoryStatus::Unknown(_)))
+            .map(|dir| dir.path.clone())
+            .collect();
+        if !unfinished.is_empty() {
+            warn!(
+                "超过扫描截止时间，{} 个目录未完成扫描，下次扫描时优先扫描",
+                unfinished.len()
+            );
+        }
+        *self.unfinished.lock().unwrap_or_else(|e| e.into_inner()) = unfinished;
+
+        Ok((results, scan_duration))
+    }
+}
+
+/// 单个目录的扫描时限（`scan_timeout`）和整次扫描的截止时间（`scan_deadline`）
+#[derive(Debug, Clone, Copy)]
+struct ScanLimits {
+    timeout: Option<std::time::Duration>,
+    deadline: Option<tokio::time::Instant>,
+}
+
+impl ScanLimits {
+    fn is_set(&self) -> bool {
+        self.timeout.is_some() || self.deadline.is_some()
+    }
+
+    /// 从现在开始计算、最先到达的时限
+    fn first_limit(&self) -> Option<tokio::time::Instant> {
+        let timeout_at = self
+            .timeout
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        timeout_at.into_iter().chain(self.deadline).min()
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| tokio::time::Instant::now() >= deadline)
+    }
+}
+
+/// 列出根目录下未被排除的二级目录
+///
+/// 使用目录项自带的类型判断是否为目录，不对每一项再调用 `stat`：
+/// 二级目录本身是挂起的挂载点时，`stat` 会一直阻塞。只有符号链接才需要跟随检查。
+fn list_subdirectories(
+    root_path: &Path,
+    filter: &PathFilter,
+    hook: Option<&DirectoryHook>,
+) -> Result<Vec<(String, PathBuf)>> {
+    if let Some(hook) = hook {
+        (hook.0)(root_path);
+    }
+    let entries = fs::read_dir(root_path)
+        .with_context(|| format!("无法读取监控目录: {}", root_path.display()))?;
+    let mut directories = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if !(file_type.is_dir() || file_type.is_symlink() && path.is_dir()) {
+            continue;
+        }
+        if filter.is_excluded(root_path, &path) {
+            debug!("跳过被排除的目录: {}", path.display());
+            continue;
+        }
+        if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
+            directories.push((dir_name.to_string(), path));
+        }
+    }
+    Ok(directories)
+}
+
+/// 超过 `scan_deadline` 时尚未开始扫描的目录的状态原因
+const DEADLINE_NOT_STARTED: &str = "超过扫描截止时间，未开始扫描";
+
+/// 超过 `scan_deadline` 时仍在扫描的目录的状态原因
+const DEADLINE_UNFINISHED: &str = "超过扫描截止时间，扫描未完成";
+
+/// 在阻塞线程池中扫描目录，按 `scan_timeout` 和 `scan_deadline` 等待结果
+async fn run_blocking(
+    dir_task: DirectoryTask,
+    context: ScanContext,
+    limits: ScanLimits,
+    backoff: &Arc<ScanBackoff>,
+) -> DirectoryResult {
+    let pending = dir_task.pending();
+    let running = backoff.start(&dir_task.path);
+    let handle = task::spawn_blocking(move || {
+        let _running = running;
+        dir_task.run(&context)
+    });
+    let result = async { handle.await.map_err(|e| format!("扫描任务异常: {}", e)) };
+    await_directory(pending, result, limits, backoff).await
+}
+
+/// 按顺序为每个目录取得并发名额，在阻塞线程中扫描，同时扫描的目录不超过 `max_tasks` 个
+///
+/// 名额在目录得到结果（包括超时）时归还；到达 `scan_deadline` 时尚未取得名额的目录不再扫描。
+async fn scan_concurrently(
+    directories: Vec<DirectoryTask>,
+    context: &ScanContext,
+    limits: ScanLimits,
+    backoff: &Arc<ScanBackoff>,
+    max_tasks: usize,
+) -> Vec<DirectoryResult> {
+    let semaphore = Arc::new(Semaphore::new(max_tasks.max(1)));
+    let mut results = Vec::new();
+    let mut tasks = Vec::new();
+
+    for dir_task in directories {
+        if limits.deadline_passed() {
+            results.push(dir_task.unknown());
+            continue;
+        }
+        let permit = Arc::clone(&semaphore).acquire_owned();
+        let permit = match limits.deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, permit).await {
+                Ok(permit) => permit,
+                Err(_) => {
+                    results.push(dir_task.unknown());
+                    continue;
+                }
+            },
+            None => permit.await,
+        }
+        .expect("信号量不会被关闭");
+
+        let pending = dir_task.pending();
+        let context = context.clone();
+        let backoff = Arc::clone(backoff);
+        let handle = task::spawn(async move {
+            let _permit = permit;
+            run_blocking(dir_task, context, limits, &backoff).await
+        });
+        // 任务异常或被取消时该目录仍出现在报告中
+        tasks.push(async move {
+            handle.await.unwrap_or_else(|e| {
+                error!("目录 '{}' 扫描任务异常: {}", pending.name, e);
+                DirectoryResult {
+                    status: DirectoryStatus::Error(format!("扫描任务异常: {}", e)),
+                    ..pending
+                }
+            })
+        });
+    }
+
+    results.extend(join_all(tasks).await);
+    results
+}
+
+/// 等待目录的扫描结果
+///
+/// 超过 `scan_timeout` 的目录报告为不可用，并在之后的扫描中退避；
+/// 先到达 `scan_deadline` 时报告为状态未知。
+async fn await_directory(
+    pending: DirectoryResult,
+    result: impl std::future::Future<Output = std::result::Result<DirectoryResult, String>>,
+    limits: ScanLimits,
+    backoff: &ScanBackoff,
+) -> DirectoryResult {
+    let result = match limits.first_limit() {
+        Some(limit) => match tokio::time::timeout_at(limit, result).await {
+            Ok(result) => result,
+            Err(_) if limits.deadline == Some(limit) => {
+                backoff.record_deadline(&pending.path);
+                return DirectoryResult {
+                    status: DirectoryStatus::Unknown(DEADLINE_UNFINISHED.to_string()),
+                    ..pending
+                };
+            }
+            Err(_) => {
+                let timeout = limits.timeout.unwrap_or_default().as_secs();
+                warn!(
+                    "目录 '{}' 扫描超时（超过 {} 秒）",
+                    pending.path.display(),
+                    timeout
+                );
+                backoff.record_timeout(&pending.path);
+                let reason = format!("扫描超时（超过 {} 秒）", timeout);
+                return DirectoryResult {
+                    status: DirectoryStatus::Unavailable(reason),
+                    ..pending
+                };
+            }
+        },
+        None => result.await,
+    };
+
+    match result {
+        Ok(result) => {
+            backoff.record_success(&result.path);
+            result
+        }
+        Err(reason) => {
+            error!("目录 '{}' {}", pending.name, reason);
+            DirectoryResult {
+                status: DirectoryStatus::Error(reason),
+                ..pending
+            }
+        }
+    }
+}
+
+/// 创建 `parallel` 模式使用的线程池，失败时回退到 rayon 全局线程池
+fn build_pool(threads: usize) -> Option<Arc<rayon::ThreadPool>> {
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(threads.max(1))
+        .thread_name(|i| format!("scan-{}", i))
+        .build()
+    {
+        Ok(pool) => Some(Arc::new(pool)),
+        Err(e) => {
+            warn!("无法创建扫描线程池，使用全局线程池: {}", e);
+            None
+        }
+    }
+}
+
+/// 单个二级目录的扫描任务，已应用匹配的 `[directories]` 覆盖设置
+struct DirectoryTask {
+    name: String,
+    path: PathBuf,
+    threshold_time: DateTime<Local>,
+    stale_time: Option<DateTime<Local>>,
+    config: Arc<MonitorConfig>,
+    overrides: Option<Arc<DirectoryOverride>>,
+}
+
+impl DirectoryTask {
+    fn new(
+        name: String,
+        path: PathBuf,
+        threshold_time: DateTime<Local>,
+        stale_time: Option<DateTime<Local>>,
+        config: &Arc<MonitorConfig>,
+        overrides: &DirectoryOverrides,
+    ) -> Self {
+        let settings = overrides.find(&name).cloned();
+        let (config, threshold_time) = match &settings {
+            Some(settings) => {
+                debug!("目录 '{}' 使用覆盖设置: {:?}", name, settings);
+                overrides::apply_override(settings, config, threshold_time)
+            }
+            None => (Arc::clone(config), threshold_time),
+        };
+
+        Self {
+            name,
+            path,
+            threshold_time,
+            // 覆盖后的时间范围可能比软阈值还短
+            stale_time: stale_time.filter(|stale_time| *stale_time > threshold_time),
+            config,
+            overrides: settings,
+        }
+    }
+
+    /// 截止时间前未能开始扫描的结果
+    fn unknown(&self) -> DirectoryResult {
+        DirectoryResult {
+            status: DirectoryStatus::Unknown(DEADLINE_NOT_STARTED.to_string()),
+            ..self.pending()
+        }
+    }
+
+    /// 尚未得到扫描结果时的占位结果
+    fn pending(&self) -> DirectoryResult {
+        self.unavailable(String::new())
+    }
+
+    /// 未实际扫描、报告为不可用的结果
+    fn unavailable(&self, reason: String) -> DirectoryResult {
+        DirectoryResult {
+            overrides: self.overrides.clone(),
+            ..DirectoryResult::new(
+                self.name.clone(),
+                self.path.clone(),
+                DirectoryStatus::Unavailable(reason),
+            )
+        }
+    }
+
+    fn run(self, context: &ScanContext) -> DirectoryResult {
+        if let Some(hook) = &context.hook {
+            (hook.0)(&self.path);
+        }
+        DirectoryResult {
+            overrides: self.overrides,
+            ..check_directory_status(
+                self.name,
+                self.path,
+                self.threshold_time,
+                self.stale_time,
+                &self.config,
+                context,
+            )
+        }
+    }
+}
+
+/// 一次扫描中各目录共用的过滤规则、增长检测和增量扫描缓存
+#[derive(Clone)]
+struct ScanContext {
+    filter: Arc<PathFilter>,
+    growth: Option<Arc<GrowthTracker>>,
+    cache: Option<Arc<ScanCache>>,
+    hook: Option<DirectoryHook>,
+}
+
+/// 判断单个二级目录的状态，扫描出错时返回 `Error` 而不是中断整个扫描
+fn check_directory_status(
+    name: String,
+    dir_path: PathBuf,
+    threshold_time: DateTime<Local>,
+    stale_time: Option<DateTime<Local>>,
+    config: &MonitorConfig,
+    context: &ScanContext,
+) -> DirectoryResult {
+    // 遍历时会跳过无法读取的条目，因此先确认目录本身可读
+    if let Err(e) = fs::read_dir(&dir_path) {
+        warn!("无法读取目录 '{}': {}", dir_path.display(), e);
+        let status = DirectoryStatus::Unavailable(e.to_string());
+        return DirectoryResult::new(name, dir_path, status);
+    }
+
+    let examined = Cell::new(0);
+    let (status, newest_file) = match recording_status(
+        &dir_path,
+        threshold_time,
+        stale_time,
+        config,
+        context,
+        &examined,
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("扫描目录 '{}' 出错: {:#}", dir_path.display(), e);
+            (DirectoryStatus::Error(format!("{:#}", e)), None)
+        }
+    };
+
+    DirectoryResult {
+        newest_file,
+        files_examined: examined.get(),
+        ..DirectoryResult::new(name, dir_path, status)
+    }
+}
+
+fn recording_status(
+    dir_path: &Path,
+    threshold_time: DateTime<Local>,
+    stale_time: Option<DateTime<Local>>,
+    config: &MonitorConfig,
+    context: &ScanContext,
+    examined: &Cell<u64>,
+) -> Result<(DirectoryStatus, Option<FileMatch>)> {
+    let filter = &*context.filter;
+    let growth = context.growth.as_deref();
+    let cache = context.cache.as_deref();
+    // 增长检测的样本在同一次遍历中收集，不再单独遍历目录
+    let samples = growth.map(|_| GrowthSamples::default());
+    let newest_file = has_recent_files_optimized(
+        dir_path,
+        threshold_time,
+        config,
+        filter,
+        cache,
+        examined,
+        samples.as_ref(),
+    )?;
+    let has_recent_files = newest_file
+        .as_ref()
+        .is_some_and(|file| file.time > threshold_time);
+
+    // 增长检测：时间阈值内有新文件只是必要条件，还需要最新文件在两次扫描之间变大
+    if let (Some(growth), Some(samples)) = (growth, samples) {
+        let samples = if has_recent_files {
+            samples.into_newest()
+        } else {
+            Vec::new()
+        };
+        let status = match growth.observe(dir_path, samples) {
+            Some(true) => DirectoryStatus::Recording,
+            // 有新文件但没有增长：录制可能已经结束
+            Some(false) if has_recent_files => DirectoryStatus::Stale,
+            Some(false) => DirectoryStatus::Idle,
+            None => {
+                // 第一次扫描没有基线，沿用时间阈值的结果
+                debug!("目录 '{}' 建立增长检测基线", dir_path.display());
+                if has_recent_files {
+                    DirectoryStatus::Recording
+                } else {
+                    DirectoryStatus::Idle
+                }
+            }
+        };
+        return Ok((status, newest_file));
+    }
+
+    if !has_recent_files {
+        return Ok((DirectoryStatus::Idle, newest_file));
+    }
+
+    // 软阈值内没有新文件时视为录制可能已停止
+    let Some(stale_time) = stale_time else {
+        return Ok((DirectoryStatus::Recording, newest_file));
+    };
+    if newest_file
+        .as_ref()
+        .is_some_and(|file| file.time > stale_time)
+    {
+        return Ok((DirectoryStatus::Recording, newest_file));
+    }
+    if config.find_newest.unwrap_or(false) {
+        // 已经找到真正最新的文件，不需要再次遍历
+        return Ok((DirectoryStatus::Stale, newest_file));
+    }
+    match has_recent_files_optimized(dir_path, stale_time, config, filter, cache, examined, None)? {
+        Some(file) if file.time > stale_time => Ok((DirectoryStatus::Recording, Some(file))),
+        _ => Ok((DirectoryStatus::Stale, newest_file)),
+    }
+}
+
+/// 在目录中查找新文件，返回找到的最新文件
+///
+/// 默认在第一个晚于时间阈值的文件处停止，此时返回的就是该文件；
+/// 设置 `find_newest` 或收集增长检测样本时会遍历全部文件以找到真正最新的文件。
+fn has_recent_files_optimized(
+    dir_path: &Path,
+    threshold_time: DateTime<Local>,
+    config: &MonitorConfig,
+    filter: &PathFilter,
+    cache: Option<&ScanCache>,
+    examined: &Cell<u64>,
+    samples: Option<&GrowthSamples>,
+) -> Result<Option<FileMatch>> {
+    let max_depth = config.max_depth;
+    let follow_links = config.follow_links;
+    let batch_size = config.batch_size;
+
+    // 确定使用的时间戳类型，默认为modified
+    let use_modified = config
+        .time_type
+        .as_ref()
+        .map(|t| t.to_lowercase())
+        .as_deref()
+        .unwrap_or("modified")
+        == "modified";
+
+    let check = FileCheck {
+        threshold_time,
+        use_modified,
+        find_newest: config.find_newest.unwrap_or(false) || samples.is_some(),
+        newest_first: config.traversal.as_deref() == Some("newest_first"),
+        examined,
+        samples,
+    };
+
+    // 如果启用了只搜索最新子目录的选项
+    if config.search_latest_subdir_only.unwrap_or(false) {
+        return search_in_latest_subdir_only_optimized(
+            dir_path,
+            check,
+            max_depth,
+            follow_links,
+            batch_size,
+            filter,
+        );
+    }
+
+    // 增量扫描：修改时间未变化的目录沿用缓存
+    if let Some(cache) = cache {
+        return has_recent_files_incremental(
+            dir_path,
+            check,
+            max_depth,
+            follow_links,
+            filter,
+            cache,
+        );
+    }
+
+    // 激进优化3: 使用异步I/O
+    if config.use_async_io.unwrap_or(false) {
+        return has_recent_files_async_io(
+            dir_path,
+            check,
+            max_depth,
+            follow_links,
+            batch_size,
+            filter,
+        );
+    }
+
+    // 回退到原有逻辑（使用批处理优化）
+    let mut walker = WalkDir::new(dir_path);
+
+    if let Some(depth) = max_depth {
+        walker = walker.max_depth(depth);
+    }
+
+    if let Some(follow) = follow_links {
+        walker = walker.follow_links(follow);
+    }
+    let walker = check.order(walker);
+
+    // 如果设置了批处理，则使用批处理方式
+    if let Some(batch_size) = batch_size {
+        return check_files_in_batches(
+            walk_filtered(walker, dir_path, filter),
+            dir_path,
+            check,
+            batch_size,
+            filter,
+        );
+    }
+
+    // 原有的逐一检查方式
+    let mut newest = None;
+    for entry in walk_filtered(walker, dir_path, filter) {
+        let path = entry.path();
+        if path.is_file() && filter.matches_file(dir_path, path) {
+            if let Ok(metadata) = fs::metadata(path) {
+                match check.file_time(&metadata) {
+                    Ok(time) => {
+                        if check.observe(dir_path, path, time, metadata.len(), &mut newest) {
+                            return Ok(newest);
+                        }
+                    }
+                    Err(e) => {
+                        let time_name = if use_modified {
+                            "修改时间"
+                        } else {
+                            "创建时间"
+                        };
+                        warn!("无法获取文件{} '{}': {}", time_name, path.display(), e);
+                    }
+                }
+            } else {
+                warn!("无法获取文件元数据 '{}'", path.display());
+            }
+        }
+    }
+    Ok(newest)
+}
+
+/// 扫描中找到的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileMatch {
+    /// 相对于二级目录的路径
+    pub path: PathBuf,
+    /// 文件时间（按 `time_type` 取修改时间或创建时间）
+    pub time: DateTime<Local>,
+    /// 文件大小（字节）
+    pub size: u64,
+}
+
+/// 逐个文件检查时使用的参数
+#[derive(Debug, Clone, Copy)]
+struct FileCheck<'a> {
+    threshold_time: DateTime<Local>,
+    use_modified: bool,
+    /// 为 true 时不在第一个新文件处停止，继续查找真正最新的文件
+    find_newest: bool,
+    /// 为 true 时在每一层先访问最新的条目，使遍历尽早找到新文件
+    newest_first: bool,
+    /// 已读取时间戳的文件数
+    examined: &'a Cell<u64>,
+    /// 增长检测模式下收集的样本
+    samples: Option<&'a GrowthSamples>,
+}
+
+impl FileCheck<'_> {
+    /// 按 `traversal` 设置遍历顺序
+    fn order(&self, walker: WalkDir) -> WalkDir {
+        if self.newest_first {
+            traversal::newest_first(walker, self.use_modified)
+        } else {
+            walker
+        }
+    }
+
+    fn file_time(&self, metadata: &fs::Metadata) -> std::io::Result<std::time::SystemTime> {
+        if self.use_modified {
+            metadata.modified()
+        } else {
+            metadata.created()
+        }
+    }
+
+    /// 用文件更新目前找到的最新文件，返回是否可以结束遍历
+    fn observe(
+        &self,
+        base_path: &Path,
+        path: &Path,
+        time: std::time::SystemTime,
+        size: u64,
+        newest: &mut Option<FileMatch>,
+    ) -> bool {
+        self.examined.set(self.examined.get() + 1);
+        self.sample(path, time, size);
+        let time: DateTime<Local> = time.into();
+        if newest.as_ref().is_none_or(|file| time > file.time) {
+            *newest = Some(FileMatch {
+                path: path.strip_prefix(base_path).unwrap_or(path).to_path_buf(),
+                time,
+                size,
+            });
+        }
+        self.is_done(newest)
+    }
+
+    /// 增长检测模式下记录时间阈值之后的文件
+    fn sample(&self, path: &Path, time: std::time::SystemTime, size: u64) {
+        if let Some(samples) = self.samples {
+            if DateTime::<Local>::from(time) > self.threshold_time {
+                samples.push(FileSample {
+                    path: path.to_path_buf(),
+                    size,
+                    modified: time,
+                });
+            }
+        }
+    }
+
+    fn is_done(&self, newest: &Option<FileMatch>) -> bool {
+        !self.find_newest
+            && newest
+                .as_ref()
+                .is_some_and(|file| file.time > self.threshold_time)
+    }
+
+    /// 合并两个结果，保留较新的文件
+    fn merge(newest: &mut Option<FileMatch>, candidate: Option<FileMatch>) {
+        if let Some(candidate) = candidate {
+            if newest
+                .as_ref()
+                .is_none_or(|file| candidate.time > file.time)
+            {
+                *newest = Some(candidate);
+            }
+        }
+    }
+}
+
+// 激进优化2: 优化版的最新子目录搜索
+fn search_in_latest_subdir_only_optimized(
+    dir_path: &Path,
+    check: FileCheck<'_>,
+    max_depth: Option<usize>,
+    follow_links: Option<bool>,
+    batch_size: Option<usize>,
+    filter: &PathFilter,
+) -> Result<Option<FileMatch>> {
+    // 首先快速检查当前目录时间
+    // 设置了过滤规则时跳过：被排除的文件同样会更新目录时间
+    let mut dir_is_new = false;
+    if let Some(metadata) = fs::metadata(dir_path).ok().filter(|_| filter.is_empty()) {
+        if let Ok(time) = check.file_time(&metadata) {
+            let dir_time: DateTime<Local> = time.into();
+            if dir_time > check.threshold_time {
+                debug!("目录本身就是新的: {}", dir_path.display());
+                dir_is_new = true;
+            }
+        }
+    }
+
+    // 找到最新的子目录
+    let latest_subdir = find_latest_subdir(dir_path, check.use_modified, filter)?;
+
+    let mut newest = None;
+    if let Some(latest_dir) = latest_subdir {
+        debug!("搜索最新子目录: {}", latest_dir.display());
+
+        // 构建目录遍历器
+        let mut walker = WalkDir::new(&latest_dir);
+
+        // 如果设置了最大深度，需要减1（因为我们已经进入了一层子目录）
+        if let Some(depth) = max_depth {
+            if depth > 1 {
+                walker = walker.max_depth(depth - 1);
+            } else {
+                walker = walker.max_depth(1);
+            }
+        }
+
+        // 如果设置了跟随符号链接，则应用设置
+        if let Some(follow) = follow_links {
+            walker = walker.follow_links(follow);
+        }
+        let walker = check.order(walker);
+
+        // 如果设置了批处理大小，使用批处理方式
+        if let Some(batch_size) = batch_size {
+            newest = check_files_in_batches(
+                walk_filtered(walker, dir_path, filter),
+                dir_path,
+                check,
+                batch_size,
+                filter,
+            )?;
+        } else {
+            // 否则使用原有的逐一检查方式
+            for entry in walk_filtered(walker, dir_path, filter) {
+                let path = entry.path();
+                if path.is_file() && filter.matches_file(dir_path, path) {
+                    // 使用DirEntry的metadata而不是fs::metadata，更快
+                    if let Ok(metadata) = entry.metadata() {
+                        if let Ok(time) = check.file_time(&metadata) {
+                            if check.observe(dir_path, path, time, metadata.len(), &mut newest) {
+                                debug!("在最新子目录中找到新文件: {}", path.display());
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // 目录本身是新的（例如刚创建了新的会话目录）但还没有新文件时，以目录时间为准
+    if dir_is_new && !check.is_done(&newest) {
+        let metadata = fs::metadata(dir_path)?;
+        let time = check.file_time(&metadata)?;
+        return Ok(Some(FileMatch {
+            path: PathBuf::from("."),
+            time: time.into(),
+            size: 0,
+        }));
+    }
+
+    Ok(newest)
+}
+
+// 优化的异步I/O版本（不影响精确度）
+fn has_recent_files_async_io(
+    dir_path: &Path,
+    check: FileCheck<'_>,
+    max_depth: Option<usize>,
+    follow_links: Option<bool>,
+    batch_size: Option<usize>,
+    filter: &PathFilter,
+) -> Result<Option<FileMatch>> {
+    let mut walker = WalkDir::new(dir_path);
+
+    if let Some(depth) = max_depth {
+        walker = walker.max_depth(depth);
+    }
+
+    if let Some(follow) = follow_links {
+        walker = walker.follow_links(follow);
+    }
+    let walker = check.order(walker);
+
+    // 如果设置了批处理大小，使用批处理方式
+    if let Some(batch_size) = batch_size {
+        return check_files_in_batches(
+            walk_filtered(walker, dir_path, filter),
+            dir_path,
+            check,
+            batch_size,
+            filter,
+        );
+    }
+
+    // 否则批量收集所有文件然后一次性检查
+    let mut files_to_check = Vec::new();
+    for entry in walk_filtered(walker, dir_path, filter) {
+        if entry.path().is_file() {
+            files_to_check.push(entry);
+        }
+    }
+
+    // 检查所有文件（不影响精确度）
+    check_files_batch(&files_to_check, dir_path, check, filter)
+}
+
+/// 增量遍历目录树：修改时间未变化的目录沿用缓存中的文件和子目录，只读取发生变化的目录
+///
+/// 缓存中的最新文件仍会重新获取一次时间，以发现对已有文件的追加写入。
+/// 跟随符号链接时按规范化路径记录已访问的目录，避免符号链接循环导致无限遍历。
+fn has_recent_files_incremental(
+    dir_path: &Path,
+    check: FileCheck<'_>,
+    max_depth: Option<usize>,
+    follow_links: Option<bool>,
+    filter: &PathFilter,
+    cache: &ScanCache,
+) -> Result<Option<FileMatch>> {
+    let mut newest = None;
+    let mut pending = vec![(dir_path.to_path_buf(), 0)];
+    let mut visited = HashSet::new();
+
+    while let Some((path, depth)) = pending.pop() {
+        // 与 WalkDir 的 max_depth 一致：深度达到 max_depth 的目录不再读取其中的条目
+        if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            continue;
+        }
+        if follow_links.unwrap_or(false) {
+            match fs::canonicalize(&path) {
+                Ok(canonical) if !visited.insert(canonical.clone()) => {
+                    warn!(
+                        "检测到符号链接循环，跳过目录 '{}' (指向 '{}')",
+                        path.display(),
+                        canonical.display()
+                    );
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("无法解析目录路径 '{}': {}", path.display(), e);
+                    continue;
+                }
+            }
+        }
+
+        let modified = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                warn!("无法获取目录修改时间 '{}': {}", path.display(), e);
+                continue;
+            }
+        };
+
+        let cached = cache
+            .get(&path, modified, check.use_modified)
+            .and_then(|cached| refresh_newest(cached, check));
+        let directory = match cached {
+            Some(cached) => cached,
+            None => {
+                let directory =
+                    read_directory(&path, modified, dir_path, check, follow_links, filter);
+                cache.insert(path.clone(), directory.clone());
+                directory
+            }
+        };
+
+        if let Some(file) = &directory.newest {
+            let path = file.path.strip_prefix(dir_path).unwrap_or(&file.path);
+            FileCheck::merge(
+                &mut newest,
+                Some(FileMatch {
+                    path: path.to_path_buf(),
+                    time: file.time.into(),
+                    size: file.size,
+                }),
+            );
+            if check.is_done(&newest) {
+                return Ok(newest);
+            }
+        }
+        let mut subdirs = directory.subdirs;
+        if check.newest_first {
+            // 后进先出：最新的子目录最后入栈、最先访问
+            traversal::sort_newest_first(&mut subdirs, check.use_modified);
+            subdirs.reverse();
+        }
+        pending.extend(subdirs.into_iter().map(|subdir| (subdir, depth + 1)));
+    }
+
+    Ok(newest)
+}
+
+/// 重新获取缓存中最新文件的时间和大小；文件已不存在时缓存作废
+fn refresh_newest(mut cached: CachedDirectory, check: FileCheck<'_>) -> Option<CachedDirectory> {
+    if let Some(file) = &mut cached.newest {
+        let metadata = fs::metadata(&file.path).ok()?;
+        check.examined.set(check.examined.get() + 1);
+        file.time = check.file_time(&metadata).ok()?;
+        file.size = metadata.len();
+    }
+    Some(cached)
+}
+
+/// 读取目录的直接子项，找出其中最新的文件和未被排除的子目录
+fn read_directory(
+    path: &Path,
+    modified: std::time::SystemTime,
+    base_path: &Path,
+    check: FileCheck<'_>,
+    follow_links: Option<bool>,
+    filter: &PathFilter,
+) -> CachedDirectory {
+    let mut directory = CachedDirectory {
+        modified,
+        use_modified: check.use_modified,
+        newest: None,
+        subdirs: Vec::new(),
+    };
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("无法读取目录 '{}': {}", path.display(), e);
+            return directory;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let entry_path = entry.path();
+        if filter.is_excluded(base_path, &entry_path) {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        // 与 WalkDir 一致：默认不进入符号链接指向的目录
+        let follow = file_type.is_symlink() && follow_links.unwrap_or(false);
+        if (file_type.is_dir() || follow) && entry_path.is_dir() {
+            directory.subdirs.push(entry_path);
+            continue;
+        }
+        if !entry_path.is_file() || !filter.matches_file(base_path, &entry_path) {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&entry_path) else {
+            warn!("无法获取文件元数据 '{}'", entry_path.display());
+            continue;
+        };
+        let Ok(time) = check.file_time(&metadata) else {
+            continue;
+        };
+        check.examined.set(check.examined.get() + 1);
+        if directory
+            .newest
+            .as_ref()
+            .is_none_or(|file| time > file.time)
+        {
+            directory.newest = Some(CachedFile {
+                path: entry_path,
+                time,
+                size: metadata.len(),
+            });
+        }
+    }
+    directory
+}
+
+/// 检查一批文件，返回其中最新的文件（找到新文件且不要求最新时提前返回）
+fn check_files_batch(
+    files: &[walkdir::DirEntry],
+    base_path: &Path,
+    check: FileCheck<'_>,
+    filter: &PathFilter,
+) -> Result<Option<FileMatch>> {
+    let mut newest = None;
+    for entry in files {
+        if !filter.matches_file(base_path, entry.path()) {
+            continue;
+        }
+
+        // 使用DirEntry的metadata方法，避免额外的系统调用
+        if let Ok(metadata) = entry.metadata() {
+            if let Ok(time) = check.file_time(&metadata) {
+                if check.observe(base_path, entry.path(), time, metadata.len(), &mut newest) {
+                    debug!("批量检查找到新文件: {}", entry.path().display());
+                    break;
+                }
+            }
+        }
+    }
+    Ok(newest)
+}
+
+// 新增：分批次检查文件
+fn check_files_in_batches(
+    entries: impl Iterator<Item = walkdir::DirEntry>,
+    base_path: &Path,
+    check: FileCheck<'_>,
+    batch_size: usize,
+    filter: &PathFilter,
+) -> Result<Option<FileMatch>> {
+    let mut file_batch = Vec::new();
+    let mut newest = None;
+
+    for entry in entries {
+        if entry.path().is_file() {
+            file_batch.push(entry);
+
+            // 当达到批处理大小时，处理这一批文件
+            if file_batch.len() >= batch_size {
+                debug!("处理文件批次，大小: {}", file_batch.len());
+                FileCheck::merge(
+                    &mut newest,
+                    check_files_batch(&file_batch, base_path, check, filter)?,
+                );
+                if check.is_done(&newest) {
+                    return Ok(newest);
+                }
+                file_batch.clear();
+            }
+        }
+    }
+
+    // 处理最后不满一批的文件
+    if !file_batch.is_empty() {
+        debug!("处理最后的文件批次，大小: {}", file_batch.len());
+        FileCheck::merge(
+            &mut newest,
+            check_files_batch(&file_batch, base_path, check, filter)?,
+        );
+    }
+
+    Ok(newest)
+}
+
+/// 扫描遍历中收集的时间阈值之后的文件，供增长检测比较大小
+///
+/// 时间按 `time_type` 取；增量扫描时每个目录只提供其中最新的文件。
+#[derive(Debug, Default)]
+struct GrowthSamples(RefCell<Vec<FileSample>>);
+
+impl GrowthSamples {
+    fn push(&self, sample: FileSample) {
+        let mut samples = self.0.borrow_mut();
+        samples.push(sample);
+        // 只需要最新的几个文件，遍历大目录时不必保留全部
+        if samples.len() > GROWTH_SAMPLE_FILES * 2 {
+            Self::keep_newest(&mut samples);
+        }
+    }
+
+    /// 最新的 [`GROWTH_SAMPLE_FILES`] 个文件
+    fn into_newest(self) -> Vec<FileSample> {
+        let mut samples = self.0.into_inner();
+        Self::keep_newest(&mut samples);
+        samples
+    }
+
+    fn keep_newest(samples: &mut Vec<FileSample>) {
+        samples.sort_by_key(|sample| std::cmp::Reverse(sample.modified));
+        samples.truncate(GROWTH_SAMPLE_FILES);
+    }
+}
+
+/// 遍历目录树，跳过被排除的文件和目录（被排除的目录不会进入）
+fn walk_filtered<'a>(
+    walker: WalkDir,
+    base_path: &'a Path,
+    filter: &'a PathFilter,
+) -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+    walker
+        .into_iter()
+        .filter_entry(move |entry| !filter.is_excluded(base_path, entry.path()))
+        .flatten()
+}
+
+fn find_latest_subdir(
+    dir_path: &Path,
+    use_modified: bool,
+    filter: &PathFilter,
+) -> Result<Option<PathBuf>> {
+    let mut latest_dir: Option<PathBuf> = None;
+    let mut latest_time: Option<DateTime<Local>> = None;
+
+    if let Ok(entries) = fs::read_dir(dir_path) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() && !filter.is_excluded(dir_path, &path) {
+                if let Ok(metadata) = fs::metadata(&path) {
+                    let time_result = if use_modified {
+                        metadata.modified()
+                    } else {
+                        metadata.created()
+                    };
+
+                    if let Ok(time) = time_result {
+                        let dir_time: DateTime<Local> = time.into();
+
+                        if latest_time.is_none() || dir_time > latest_time.unwrap() {
+                            latest_time = Some(dir_time);
+                            latest_dir = Some(path);
+                        }
+                    } else {
+                        let time_name = if use_modified {
+                            "修改时间"
+                        } else {
+                            "创建时间"
+                        };
+                        warn!(
+                            "无法获取目录{} '{}': {}",
+                            time_name,
+                            path.display(),
+                            time_result.unwrap_err()
+                        );
+                    }
+                } else {
+                    warn!("无法获取目录元数据 '{}'", path.display());
+                }
+            }
+        }
+    }
+
+    Ok(latest_dir)
+}
@@ -0,0 +1,129 @@
+//! 内置 HTTP 状态服务
+
+use anyhow::{Context, Result};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use log::info;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+use crate::metrics::MetricsRegistry;
+use crate::report::ScanRecord;
+use crate::scanner::ScanReport;
+
+/// 未配置监听地址时使用的默认地址
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// 在扫描循环与 HTTP 服务之间共享的最新扫描结果
+#[derive(Debug, Clone, Default)]
+pub struct StatusStore {
+    latest: Arc<RwLock<Option<ScanReport>>>,
+    metrics: MetricsRegistry,
+}
+
+impl StatusStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `/metrics` 输出的指标，由扫描循环负责更新
+    pub fn metrics(&self) -> &MetricsRegistry {
+        &self.metrics
+    }
+
+    /// 用新的扫描结果替换当前结果
+    pub async fn update(&self, report: ScanReport) {
+        *self.latest.write().await = Some(report);
+    }
+
+    /// 获取最新扫描结果的副本
+    pub async fn latest(&self) -> Option<ScanReport> {
+        self.latest.read().await.clone()
+    }
+}
+
+/// 构建状态服务的路由
+pub fn router(store: StatusStore) -> Router {
+    Router::new()
+        .route("/status", get(get_status))
+        // 多个根目录时目录名形如 "根目录标签/目录名"
+        .route("/status/{*dir}", get(get_directory_status))
+        .route("/healthz", get(get_health))
+        .route("/metrics", get(get_metrics))
+        .with_state(store)
+}
+
+/// 在已绑定的监听器上运行状态服务，直到出错为止
+pub async fn serve(listener: TcpListener, store: StatusStore) -> Result<()> {
+    if let Ok(addr) = listener.local_addr() {
+        info!("HTTP 状态服务已启动: http://{}", addr);
+    }
+
+    axum::serve(listener, router(store))
+        .await
+        .context("HTTP 状态服务异常退出")
+}
+
+async fn get_status(State(store): State<StatusStore>) -> Response {
+    let latest = store.latest.read().await;
+    match latest.as_ref() {
+        Some(report) => Json(ScanRecord::new(report)).into_response(),
+        None => not_ready(),
+    }
+}
+
+async fn get_directory_status(
+    State(store): State<StatusStore>,
+    Path(dir): Path<String>,
+) -> Response {
+    let latest = store.latest.read().await;
+    let Some(report) = latest.as_ref() else {
+        return not_ready();
+    };
+
+    match ScanRecord::new(report)
+        .directories
+        .into_iter()
+        .find(|record| record.directory == dir)
+    {
+        Some(record) => Json(record).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("目录不存在: {}", dir) })),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_health(State(store): State<StatusStore>) -> Response {
+    let latest = store.latest.read().await;
+    Json(json!({
+        "status": "ok",
+        "last_scan": latest.as_ref().map(|report| report.scanned_at),
+    }))
+    .into_response()
+}
+
+async fn get_metrics(State(store): State<StatusStore>) -> Response {
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        store.metrics.render(),
+    )
+        .into_response()
+}
+
+fn not_ready() -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({ "error": "尚未完成首次扫描" })),
+    )
+        .into_response()
+}
@@ -0,0 +1,63 @@
+//! 二级目录的录制状态
+
+use std::fmt;
+
+/// 单个二级目录在一次扫描中的状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryStatus {
+    /// 软阈值（`stale_minutes`）内有新文件，或文件仍在增长/被写入
+    Recording,
+    /// 在 `check_hours` 内有新文件，但最近没有活动
+    Stale,
+    /// 在 `check_hours` 内没有新文件
+    Idle,
+    /// 目录无法读取
+    Unavailable(String),
+    /// 扫描过程中出错
+    Error(String),
+    /// 未能在 `scan_deadline` 之前完成扫描
+    Unknown(String),
+}
+
+impl DirectoryStatus {
+    /// 机器可读输出中使用的状态名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Recording => "recording",
+            Self::Stale => "stale",
+            Self::Idle => "idle",
+            Self::Unavailable(_) => "unavailable",
+            Self::Error(_) => "error",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self, Self::Recording)
+    }
+
+    /// 扫描失败时无法判断目录是否在录制
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::Unavailable(_) | Self::Error(_) | Self::Unknown(_)
+        )
+    }
+
+    /// 失败原因
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Unavailable(reason) | Self::Error(reason) | Self::Unknown(reason) => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DirectoryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason() {
+            Some(reason) => write!(f, "{} ({})", self.as_str(), reason),
+            None => f.write_str(self.as_str()),
+        }
+    }
+}
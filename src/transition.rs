@@ -0,0 +1,182 @@
+//! 录制状态变化检测
+
+use chrono::{DateTime, Local};
+use serde::{Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::{DirectoryOverride, OutputConfig};
+use crate::scanner::ScanReport;
+use crate::status::DirectoryStatus;
+
+/// 状态变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+    /// 目录从无新文件变为有新文件
+    RecordingStarted,
+    /// 目录从有新文件变为无新文件（或目录消失）
+    RecordingStopped,
+}
+
+impl TransitionKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::RecordingStarted => "recording_started",
+            Self::RecordingStopped => "recording_stopped",
+        }
+    }
+}
+
+impl fmt::Display for TransitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 单个目录的一次状态变化
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusTransition {
+    pub directory: String,
+    pub path: PathBuf,
+    pub kind: TransitionKind,
+    pub at: DateTime<Local>,
+    /// 变化之后目录的状态（停止录制可能是 `Stale` 或 `Idle`，目录消失时为 `Idle`）
+    #[serde(serialize_with = "serialize_status")]
+    pub status: DirectoryStatus,
+    /// 变化之后目录的提示信息，已应用 `[directories]` 覆盖设置
+    pub message: String,
+}
+
+fn serialize_status<S: Serializer>(
+    status: &DirectoryStatus,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(status.as_str())
+}
+
+/// 上一次扫描中的目录
+#[derive(Debug, Clone)]
+struct Previous {
+    path: PathBuf,
+    root: Option<String>,
+    recording: bool,
+    overrides: Option<Arc<DirectoryOverride>>,
+}
+
+/// 记住上一次扫描的各目录状态，并与新的扫描结果比较
+///
+/// 第一次观察到的扫描结果只作为基线，不产生任何变化事件。
+#[derive(Debug, Clone, Default)]
+pub struct TransitionTracker {
+    previous: Option<HashMap<String, Previous>>,
+}
+
+impl TransitionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 比较报告与上一次的状态，把检测到的变化写入 `report.transitions` 并返回
+    ///
+    /// 扫描失败（`Unavailable` / `Error` / `Unknown`）的目录保持上一次的状态，不产生变化事件；
+    /// 所属根目录无法访问时，其下的目录同样保持上一次的状态。
+    /// 变化事件中的提示信息按 `output` 和目录的覆盖设置确定。
+    pub fn observe(
+        &mut self,
+        report: &mut ScanReport,
+        output: &OutputConfig,
+    ) -> Vec<StatusTransition> {
+        let was_recording = |name: &str| {
+            self.previous
+                .as_ref()
+                .and_then(|previous| previous.get(name))
+                .is_some_and(|previous| previous.recording)
+        };
+        // 根目录无法访问时报告中没有它的目录；单个根目录时目录不带标签
+        let root_failed = |root: Option<&str>| {
+            report
+                .roots
+                .iter()
+                .any(|r| r.error.is_some() && root.is_none_or(|label| label == r.label))
+        };
+        let mut current: HashMap<_, _> = report
+            .directories
+            .iter()
+            .map(|d| {
+                let recording = if d.status.is_failure() {
+                    was_recording(&d.name)
+                } else {
+                    d.is_recording()
+                };
+                let previous = Previous {
+                    path: d.path.clone(),
+                    root: d.root.clone(),
+                    recording,
+                    overrides: d.overrides.clone(),
+                };
+                (d.name.clone(), previous)
+            })
+            .collect();
+        if let Some(previous) = &self.previous {
+            for (name, p) in previous {
+                if !current.contains_key(name) && root_failed(p.root.as_deref()) {
+                    current.insert(name.clone(), p.clone());
+                }
+            }
+        }
+
+        let mut transitions = Vec::new();
+        if let Some(previous) = &self.previous {
+            let at = Local::now();
+
+            for dir in report.directories.iter().filter(|d| !d.status.is_failure()) {
+                let was_recording = previous.get(&dir.name).is_some_and(|p| p.recording);
+                let kind = match (was_recording, dir.is_recording()) {
+                    (false, true) => TransitionKind::RecordingStarted,
+                    (true, false) => TransitionKind::RecordingStopped,
+                    _ => continue,
+                };
+                transitions.push(StatusTransition {
+                    directory: dir.name.clone(),
+                    path: dir.path.clone(),
+                    kind,
+                    at,
+                    status: dir.status.clone(),
+                    message: output.message_for(dir).to_string(),
+                });
+            }
+
+            // 录制中的目录被删除或不可见时视为停止录制
+            let mut removed: Vec<_> = previous
+                .iter()
+                .filter(|(name, p)| p.recording && !current.contains_key(*name))
+                .map(|(name, p)| {
+                    let status = DirectoryStatus::Idle;
+                    let message = p
+                        .overrides
+                        .as_deref()
+                        .and_then(|o| o.message(&status))
+                        .unwrap_or_else(|| output.message(&status))
+                        .to_string();
+                    StatusTransition {
+                        directory: name.clone(),
+                        path: p.path.clone(),
+                        kind: TransitionKind::RecordingStopped,
+                        at,
+                        status,
+                        message,
+                    }
+                })
+                .collect();
+            removed.sort_by(|a, b| a.directory.cmp(&b.directory));
+            transitions.extend(removed);
+        }
+
+        self.previous = Some(current);
+        report.transitions = transitions.clone();
+        transitions
+    }
+}
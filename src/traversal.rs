@@ -0,0 +1,106 @@
+//! 目录遍历顺序：`traversal = "newest_first"` 时在每一层先访问最新的条目
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use walkdir::{DirEntry, WalkDir};
+
+/// 同一层条目的排序依据
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SortKey {
+    /// 以日期命名的目录（如 `2024`、`05`、`2024-05-17`）中的数字
+    dated: Option<Vec<u64>>,
+    /// 未以日期命名的条目的时间
+    time: Option<SystemTime>,
+}
+
+impl SortKey {
+    fn new(path: &Path, is_dir: bool, use_modified: bool) -> Self {
+        let dated = is_dir
+            .then(|| {
+                path.file_name()
+                    .and_then(|name| date_key(&name.to_string_lossy()))
+            })
+            .flatten();
+        // 日期目录按名称排序，不需要读取元数据
+        let time = match dated {
+            Some(_) => None,
+            None => fs::metadata(path).ok().and_then(|metadata| {
+                if use_modified {
+                    metadata.modified().ok()
+                } else {
+                    metadata.created().ok()
+                }
+            }),
+        };
+        Self { dated, time }
+    }
+
+    /// 降序：日期目录在前且日期越新越靠前，其余条目时间越新越靠前
+    fn newest_first(&self, other: &Self) -> Ordering {
+        (other.dated.is_some(), &other.dated, other.time).cmp(&(
+            self.dated.is_some(),
+            &self.dated,
+            self.time,
+        ))
+    }
+}
+
+/// 以日期或数字命名时返回其中各段数字，例如 `2024-05-17` 为 `[2024, 5, 17]`
+///
+/// 名称必须以数字开头，且只包含数字和 `-`、`_`、`.` 分隔符；按数字比较，因此 `5` 排在 `12` 之前。
+pub fn date_key(name: &str) -> Option<Vec<u64>> {
+    if !name.starts_with(|c: char| c.is_ascii_digit())
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+    {
+        return None;
+    }
+    name.split(['-', '_', '.'])
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+/// 让遍历器在每一层先访问最新的条目
+///
+/// WalkDir 每次对一个目录的全部条目排序，排序依据按目录缓存，
+/// 同一次排序中正在写入的文件时间变化也不会破坏顺序。
+pub fn newest_first(walker: WalkDir, use_modified: bool) -> WalkDir {
+    let keys: Mutex<(PathBuf, HashMap<OsString, SortKey>)> = Mutex::default();
+    walker.sort_by(move |a, b| {
+        let mut keys = keys.lock().unwrap_or_else(|e| e.into_inner());
+        let parent = a.path().parent().unwrap_or(a.path());
+        if keys.0 != parent {
+            *keys = (parent.to_path_buf(), HashMap::new());
+        }
+        let key_a = cached_key(&mut keys.1, a, use_modified);
+        let key_b = cached_key(&mut keys.1, b, use_modified);
+        key_a.newest_first(&key_b)
+    })
+}
+
+fn cached_key(
+    keys: &mut HashMap<OsString, SortKey>,
+    entry: &DirEntry,
+    use_modified: bool,
+) -> SortKey {
+    keys.entry(entry.file_name().to_owned())
+        .or_insert_with(|| SortKey::new(entry.path(), entry.file_type().is_dir(), use_modified))
+        .clone()
+}
+
+/// 把目录按从新到旧排序
+pub fn sort_newest_first(paths: &mut Vec<PathBuf>, use_modified: bool) {
+    let mut keyed: Vec<_> = paths
+        .drain(..)
+        .map(|path| (SortKey::new(&path, true, use_modified), path))
+        .collect();
+    keyed.sort_by(|a, b| a.0.newest_first(&b.0));
+    paths.extend(keyed.into_iter().map(|(_, path)| path));
+}
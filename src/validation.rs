@@ -0,0 +1,392 @@
+//! 配置文件的严格校验：未知配置项、取值范围与枚举值，并报告所在的行和列
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt;
+use std::ops::Range;
+
+use crate::config::{
+    Config, DirectoryOverride, HistoryConfig, HooksConfig, MetricsConfig, MonitorConfig,
+    NotifyConfig, OutputConfig, RootConfig, ServerConfig, WebhookConfig,
+};
+
+/// 配置中的一个问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// 配置项路径，例如 `["monitor", "parallel_mode"]`，数组元素为下标
+    pub path: Vec<String>,
+    pub message: String,
+    /// 在配置文件中的行号和列号（从 1 开始），由命令行 `--set` 设置的值没有位置
+    pub location: Option<(usize, usize)>,
+}
+
+impl ConfigIssue {
+    fn new(path: &[&str], message: impl Into<String>) -> Self {
+        Self {
+            path: path.iter().map(|s| s.to_string()).collect(),
+            message: message.into(),
+            location: None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.location {
+            write!(f, "第 {} 行第 {} 列: ", line, column)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// 配置文件中的全部问题
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigErrors(pub Vec<ConfigIssue>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "发现 {} 个问题", self.0.len())?;
+        for issue in &self.0 {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// 解析配置文件，同时收集无法识别的配置项
+///
+/// 语法错误和类型错误同样附带行号和列号。
+pub fn parse(content: &str) -> Result<(Config, Vec<ConfigIssue>), ConfigErrors> {
+    let mut unknown = Vec::new();
+    let config: Config = serde_ignored::deserialize(toml::Deserializer::new(content), |path| {
+        unknown.push(path_segments(&path))
+    })
+    .map_err(|e| {
+        ConfigErrors(vec![ConfigIssue {
+            path: Vec::new(),
+            message: e.message().trim_end().replace('\n', "; "),
+            location: e.span().map(|span| line_column(content, span.start)),
+        }])
+    })?;
+
+    let issues = unknown
+        .into_iter()
+        .map(|path| {
+            let (key, parent) = path.split_last().expect("未知配置项的路径不会为空");
+            let mut message = format!("未知的配置项 '{}'", path.join("."));
+            if let Some(suggestion) = known_fields(parent).and_then(|fields| suggest(key, fields)) {
+                message.push_str(&format!("，是否应为 '{}'？", suggestion));
+            }
+            ConfigIssue {
+                path,
+                message,
+                location: None,
+            }
+        })
+        .collect();
+    Ok((config, issues))
+}
+
+/// 检查取值范围和枚举值
+pub fn check_values(config: &Config) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    let monitor = &config.monitor;
+
+    positive(
+        &mut issues,
+        &["monitor", "check_hours"],
+        Some(monitor.check_hours),
+    );
+    positive(
+        &mut issues,
+        &["monitor", "scan_interval"],
+        Some(monitor.scan_interval),
+    );
+    positive(
+        &mut issues,
+        &["monitor", "batch_size"],
+        monitor.batch_size.map(|n| n as u64),
+    );
+    positive(
+        &mut issues,
+        &["monitor", "max_parallel_tasks"],
+        monitor.max_parallel_tasks.map(|n| n as u64),
+    );
+    positive(
+        &mut issues,
+        &["monitor", "full_rescan_minutes"],
+        monitor.full_rescan_minutes,
+    );
+    positive(
+        &mut issues,
+        &["monitor", "scan_timeout"],
+        monitor.scan_timeout,
+    );
+    positive(
+        &mut issues,
+        &["monitor", "scan_deadline"],
+        monitor.scan_deadline,
+    );
+    one_of(
+        &mut issues,
+        &["monitor", "time_type"],
+        &monitor.time_type,
+        TIME_TYPES,
+    );
+    one_of(
+        &mut issues,
+        &["monitor", "parallel_mode"],
+        &monitor.parallel_mode,
+        &["sync", "async", "parallel"],
+    );
+    one_of(
+        &mut issues,
+        &["monitor", "watch_mode"],
+        &monitor.watch_mode,
+        &["poll", "events"],
+    );
+    one_of(
+        &mut issues,
+        &["monitor", "detection_mode"],
+        &monitor.detection_mode,
+        &["mtime", "growth"],
+    );
+    one_of(
+        &mut issues,
+        &["monitor", "traversal"],
+        &monitor.traversal,
+        &["readdir", "newest_first"],
+    );
+    one_of(
+        &mut issues,
+        &["output", "format"],
+        &config.output.format,
+        &["text", "json", "ndjson", "csv"],
+    );
+
+    if let Some(history) = &config.history {
+        positive(
+            &mut issues,
+            &["history", "retention_days"],
+            history.retention_days,
+        );
+    }
+
+    for (i, root) in monitor.roots().iter().enumerate() {
+        let index = i.to_string();
+        positive(
+            &mut issues,
+            &["monitor", "roots", &index, "check_hours"],
+            root.check_hours,
+        );
+    }
+
+    for (name, settings) in config.directories.iter().flatten() {
+        positive(
+            &mut issues,
+            &["directories", name, "check_hours"],
+            settings.check_hours,
+        );
+        one_of(
+            &mut issues,
+            &["directories", name, "time_type"],
+            &settings.time_type,
+            TIME_TYPES,
+        );
+    }
+
+    issues
+}
+
+/// 为问题填上在配置文件中的位置：未知配置项指向键，取值问题指向值
+///
+/// `overridden` 为命令行 `--set` 修改过的 `[monitor]` 配置项，这些值不来自文件。
+pub fn locate(content: &str, issues: &mut [ConfigIssue], overridden: &[String]) {
+    let Ok(document) = toml_edit::ImDocument::parse(content) else {
+        return;
+    };
+
+    for issue in issues.iter_mut().filter(|issue| issue.location.is_none()) {
+        if let [section, key] = issue.path.as_slice() {
+            if section == "monitor" && overridden.contains(key) {
+                issue.message.push_str("（来自 --set）");
+                continue;
+            }
+        }
+        let is_unknown = issue.message.starts_with("未知的配置项");
+        if let Some(span) = find_span(document.as_table(), &issue.path, is_unknown) {
+            issue.location = Some(line_column(content, span.start));
+        }
+    }
+}
+
+const TIME_TYPES: &[&str] = &["modified", "created"];
+
+fn positive(issues: &mut Vec<ConfigIssue>, path: &[&str], value: Option<u64>) {
+    if value == Some(0) {
+        issues.push(ConfigIssue::new(
+            path,
+            format!("'{}' 必须大于 0", path.join(".")),
+        ));
+    }
+}
+
+fn one_of(issues: &mut Vec<ConfigIssue>, path: &[&str], value: &Option<String>, allowed: &[&str]) {
+    let Some(value) = value else {
+        return;
+    };
+    if allowed.contains(&value.as_str()) {
+        return;
+    }
+
+    let mut message = format!(
+        "'{}' 的值 \"{}\" 无效，可选值: {}",
+        path.join("."),
+        value,
+        allowed.join("、")
+    );
+    if let Some(suggestion) = suggest(value, allowed) {
+        message.push_str(&format!("，是否应为 \"{}\"？", suggestion));
+    }
+    issues.push(ConfigIssue::new(path, message));
+}
+
+/// `[monitor]` 中与输入最相近的配置项
+pub(crate) fn suggest_monitor_field(key: &str) -> Option<&'static str> {
+    suggest(key, fields_of::<MonitorConfig>())
+}
+
+/// 与输入最相近的候选项
+fn suggest<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(input, candidate), *candidate))
+        .filter(|(score, _)| *score > 0.8)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, candidate)| candidate)
+}
+
+/// 各配置表允许的配置项，由对应结构体的反序列化实现得出
+fn known_fields(parent: &[String]) -> Option<&'static [&'static str]> {
+    let parent: Vec<&str> = parent.iter().map(String::as_str).collect();
+    Some(match parent.as_slice() {
+        [] => fields_of::<Config>(),
+        ["monitor"] => fields_of::<MonitorConfig>(),
+        ["monitor", "roots", _] => fields_of::<RootConfig>(),
+        ["output"] => fields_of::<OutputConfig>(),
+        ["server"] => fields_of::<ServerConfig>(),
+        ["notify"] => fields_of::<NotifyConfig>(),
+        ["notify", "webhook", _] => fields_of::<WebhookConfig>(),
+        ["hooks"] => fields_of::<HooksConfig>(),
+        ["history"] => fields_of::<HistoryConfig>(),
+        ["metrics"] => fields_of::<MetricsConfig>(),
+        ["directories", _] => fields_of::<DirectoryOverride>(),
+        _ => return None,
+    })
+}
+
+/// 取得结构体反序列化时声明的字段名
+fn fields_of<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for FieldNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("只用于读取字段名"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("只用于读取字段名"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+fn path_segments(path: &serde_ignored::Path<'_>) -> Vec<String> {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => Vec::new(),
+        Path::Seq { parent, index } => {
+            let mut segments = path_segments(parent);
+            segments.push(index.to_string());
+            segments
+        }
+        Path::Map { parent, key } => {
+            let mut segments = path_segments(parent);
+            segments.push(key.clone());
+            segments
+        }
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => path_segments(parent),
+    }
+}
+
+/// 按路径在文档中查找键（`key_span` 为真时）或值的位置
+fn find_span(
+    root: &dyn toml_edit::TableLike,
+    path: &[String],
+    key_span: bool,
+) -> Option<Range<usize>> {
+    let mut table = root;
+    let mut segments = path.iter().peekable();
+    while let Some(segment) = segments.next() {
+        let (key, item) = table.get_key_value(segment)?;
+        let Some(next) = segments.peek() else {
+            // 表头中的键没有单独的位置，退回到整个表
+            return if key_span {
+                key.span().or_else(|| item.span())
+            } else {
+                item.span()
+            };
+        };
+        table = match next.parse::<usize>() {
+            Ok(index) => {
+                segments.next();
+                element_table(item, index)?
+            }
+            Err(_) => item.as_table_like()?,
+        };
+    }
+    None
+}
+
+/// 表数组（`[[...]]`）或内联表数组中的第 `index` 个表
+fn element_table(item: &toml_edit::Item, index: usize) -> Option<&dyn toml_edit::TableLike> {
+    match item {
+        toml_edit::Item::ArrayOfTables(tables) => {
+            tables.get(index).map(|t| t as &dyn toml_edit::TableLike)
+        }
+        toml_edit::Item::Value(toml_edit::Value::Array(array)) => array
+            .get(index)?
+            .as_inline_table()
+            .map(|t| t as &dyn toml_edit::TableLike),
+        _ => None,
+    }
+}
+
+/// 字节偏移转换为从 1 开始的行号和列号（按字符计列）
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
@@ -0,0 +1,227 @@
+//! 基于文件系统事件（Linux 上为 inotify）的监控模式
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Local};
+use log::{debug, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tokio::sync::mpsc;
+
+use crate::config::MonitorConfig;
+use crate::filter::PathFilter;
+use crate::overrides::{self, DirectoryOverrides};
+use crate::scanner::{DirectoryResult, ScanReport};
+use crate::status::DirectoryStatus;
+
+/// 二级目录中检测到的一次写入活动
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryActivity {
+    /// 二级目录名
+    pub name: String,
+    /// 触发事件的文件路径
+    pub path: PathBuf,
+    /// 收到事件的时间
+    pub at: DateTime<Local>,
+}
+
+/// 递归订阅根目录的创建/写入事件，并将其归属到对应的二级目录
+pub struct EventWatcher {
+    root_path: PathBuf,
+    // 保持订阅有效，释放后事件流即停止
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    last_activity: HashMap<String, DateTime<Local>>,
+    filter: PathFilter,
+    overrides: DirectoryOverrides,
+    /// 全局 `check_hours`，用于按覆盖设置平移时间阈值
+    check_hours: u64,
+    /// 软阈值，活动早于它时目录只算 `Stale`
+    stale_minutes: Option<u64>,
+    /// growth 模式下目录状态由扫描之间的文件增长决定，事件不改写已有结果
+    growth: bool,
+}
+
+impl EventWatcher {
+    pub fn new(root_path: &Path) -> Result<Self> {
+        // 使用规范化路径，保证事件路径能正确映射到二级目录
+        let root_path = root_path
+            .canonicalize()
+            .with_context(|| format!("无法解析监控目录: {}", root_path.display()))?;
+
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .context("无法创建文件系统事件监听器")?;
+
+        watcher
+            .watch(&root_path, RecursiveMode::Recursive)
+            .with_context(|| format!("无法订阅目录事件: {}", root_path.display()))?;
+
+        Ok(Self {
+            root_path,
+            _watcher: watcher,
+            events,
+            last_activity: HashMap::new(),
+            filter: PathFilter::default(),
+            overrides: DirectoryOverrides::default(),
+            check_hours: 0,
+            stale_minutes: None,
+            growth: false,
+        })
+    }
+
+    /// 忽略被 `include` / `exclude` 规则过滤掉的文件和目录上的事件
+    pub fn with_filter(mut self, filter: PathFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 按 `[directories]` 覆盖设置中的 `check_hours` 判断活动是否足够新，与扫描器一致
+    pub fn with_overrides(mut self, overrides: DirectoryOverrides, check_hours: u64) -> Self {
+        self.overrides = overrides;
+        self.check_hours = check_hours;
+        self
+    }
+
+    /// 与扫描器一致地按 `stale_minutes` 和 `detection_mode` 判断活动对应的状态
+    pub fn with_detection(mut self, config: &MonitorConfig) -> Self {
+        self.stale_minutes = config.stale_minutes;
+        self.growth = config.detection_mode.as_deref() == Some("growth");
+        self
+    }
+
+    /// 等待下一次落在二级目录内的创建/写入活动
+    ///
+    /// 事件通道关闭时返回 `None`。
+    pub async fn next_activity(&mut self) -> Option<DirectoryActivity> {
+        loop {
+            let event = match self.events.recv().await? {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!("文件系统事件错误: {}", e);
+                    continue;
+                }
+            };
+
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+
+            let at = Local::now();
+            for path in event.paths {
+                if let Some(name) = second_level_dir(&self.root_path, &path) {
+                    if !self.accepts(&name, &path) {
+                        debug!("忽略被过滤的事件: {}", path.display());
+                        continue;
+                    }
+                    debug!("目录 '{}' 收到事件: {}", name, path.display());
+                    self.last_activity.insert(name.clone(), at);
+                    return Some(DirectoryActivity { name, path, at });
+                }
+            }
+        }
+    }
+
+    /// 与扫描器一致：只有通过过滤规则的文件才算新文件，目录本身的事件不算
+    fn accepts(&self, name: &str, path: &Path) -> bool {
+        let dir_path = self.root_path.join(name);
+        if self.filter.is_excluded(&self.root_path, &dir_path) || path.is_dir() {
+            return false;
+        }
+        self.filter.matches_file(&dir_path, path)
+    }
+
+    /// 忘记已记录的活动；每次全量扫描之后调用，之前的活动已经反映在扫描结果中
+    pub fn clear_activity(&mut self) {
+        self.last_activity.clear();
+    }
+
+    /// 将事件记录的活动合并到扫描报告中，返回状态发生变化的目录名
+    ///
+    /// 只有晚于报告扫描时间和时间阈值的活动会改变目录状态：晚于软阈值时为 `Recording`，
+    /// 否则为 `Stale`。状态只会从 `Idle` / `Stale` 提升，扫描失败的目录和 growth 模式下
+    /// 已有的判断保持不变，growth 模式下报告中没有的目录以 `Idle` 加入；
+    /// 目录从"有新文件"变回"无新文件"仍由周期性全量扫描负责校正。
+    pub fn apply(&self, report: &mut ScanReport) -> Vec<String> {
+        let mut changed = Vec::new();
+        let now = Local::now();
+
+        for (name, &at) in &self.last_activity {
+            // 扫描开始之前的活动已由扫描结果反映，不能用旧时间戳覆盖扫描的判断
+            if at <= report.scanned_at {
+                continue;
+            }
+            let settings = self.overrides.find(name);
+            let threshold_time = match settings {
+                Some(settings) => {
+                    overrides::override_threshold(settings, self.check_hours, report.threshold_time)
+                }
+                None => report.threshold_time,
+            };
+            if at <= threshold_time {
+                continue;
+            }
+            let stale_time = self
+                .stale_minutes
+                .map(|minutes| now - Duration::minutes(minutes as i64))
+                .filter(|stale_time| *stale_time > threshold_time);
+            let status = match stale_time {
+                Some(stale_time) if at <= stale_time => DirectoryStatus::Stale,
+                _ => DirectoryStatus::Recording,
+            };
+
+            match report.directories.iter_mut().find(|d| &d.name == name) {
+                Some(dir) => {
+                    let promote = match dir.status {
+                        DirectoryStatus::Idle => true,
+                        DirectoryStatus::Stale => status.is_recording(),
+                        _ => false,
+                    };
+                    if promote && !self.growth {
+                        dir.status = status;
+                        changed.push(name.clone());
+                    }
+                }
+                None => {
+                    // growth 模式下新目录是否在录制由下一次扫描判断
+                    let status = if self.growth {
+                        DirectoryStatus::Idle
+                    } else {
+                        status
+                    };
+                    let mut dir =
+                        DirectoryResult::new(name.clone(), self.root_path.join(name), status);
+                    dir.overrides = settings.cloned();
+                    report.directories.push(dir);
+                    changed.push(name.clone());
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            report.directories.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        changed
+    }
+}
+
+/// 计算路径所属的二级目录名；根目录本身及其直接文件返回 `None`
+fn second_level_dir(root_path: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root_path).ok()?;
+    let mut components = relative.components();
+
+    let name = match components.next()? {
+        Component::Normal(name) => name.to_str()?.to_string(),
+        _ => return None,
+    };
+
+    // 根目录下直接创建的文件不属于任何二级目录
+    if components.next().is_none() && path.is_file() {
+        return None;
+    }
+
+    Some(name)
+}
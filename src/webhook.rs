@@ -0,0 +1,174 @@
+//! 状态变化的 Webhook 通知
+
+use anyhow::{Context, Result};
+use log::{debug, error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::WebhookConfig;
+use crate::transition::StatusTransition;
+
+/// 未配置 `payload_template` 时使用的请求体模板
+pub const DEFAULT_PAYLOAD_TEMPLATE: &str = r#"{"directory": "{{directory}}", "path": "{{path}}", "event": "{{event}}", "status": "{{status}}", "message": "{{message}}", "timestamp": "{{timestamp}}"}"#;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_RETRY_DELAY_SECS: u64 = 1;
+
+/// 把状态变化以 HTTP POST 发送到所有配置的 Webhook
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    webhooks: Arc<[WebhookConfig]>,
+}
+
+impl WebhookNotifier {
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhooks: webhooks.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty()
+    }
+
+    /// 在后台发送通知，不阻塞扫描循环
+    pub fn spawn_notify(&self, transitions: Vec<StatusTransition>) {
+        if self.is_empty() || transitions.is_empty() {
+            return;
+        }
+
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            for transition in &transitions {
+                notifier.notify(transition).await;
+            }
+        });
+    }
+
+    /// 把一次状态变化发送到所有 Webhook，返回每个 Webhook 的发送结果
+    pub async fn notify(&self, transition: &StatusTransition) -> Vec<Result<()>> {
+        let deliveries = self.webhooks.iter().map(|webhook| async move {
+            let result = self.deliver(webhook, transition).await;
+            match &result {
+                Ok(()) => info!(
+                    "Webhook 已发送: {} ({} {})",
+                    webhook.url, transition.directory, transition.kind
+                ),
+                Err(e) => error!("Webhook 发送失败: {:#}", e),
+            }
+            result
+        });
+
+        futures::future::join_all(deliveries).await
+    }
+
+    async fn deliver(&self, webhook: &WebhookConfig, transition: &StatusTransition) -> Result<()> {
+        let template = webhook
+            .payload_template
+            .as_deref()
+            .unwrap_or(DEFAULT_PAYLOAD_TEMPLATE);
+        let body = render_payload(template, transition);
+        let timeout = Duration::from_secs(webhook.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let retries = webhook.retries.unwrap_or(DEFAULT_RETRIES);
+        let retry_delay =
+            Duration::from_secs(webhook.retry_delay.unwrap_or(DEFAULT_RETRY_DELAY_SECS));
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.post(webhook, &body, timeout).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt <= retries => {
+                    warn!(
+                        "Webhook 第 {} 次发送失败，{} 秒后重试: {:#}",
+                        attempt,
+                        retry_delay.as_secs(),
+                        e
+                    );
+                    tokio::time::sleep(retry_delay).await;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!("Webhook {} 在 {} 次尝试后仍然失败", webhook.url, attempt)
+                    })
+                }
+            }
+        }
+    }
+
+    async fn post(&self, webhook: &WebhookConfig, body: &str, timeout: Duration) -> Result<()> {
+        let mut request = self
+            .client
+            .post(&webhook.url)
+            .timeout(timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+        for (name, value) in webhook.headers.iter().flatten() {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .body(body.to_string())
+            .send()
+            .await
+            .with_context(|| format!("无法连接 Webhook: {}", webhook.url))?;
+
+        let status = response.status();
+        debug!("Webhook {} 响应状态: {}", webhook.url, status);
+        if !status.is_success() {
+            return Err(anyhow::anyhow!("Webhook 返回错误状态: {}", status));
+        }
+        Ok(())
+    }
+}
+
+/// 用状态变化的字段替换模板中的 `{{name}}` 占位符
+///
+/// 替换的值按 JSON 字符串转义（不含两侧引号），因此模板中的占位符应写在引号内。
+/// 支持的占位符: directory、path、event、status、message、timestamp。
+/// status 和 message 为变化之后目录的状态和提示信息。
+pub fn render_payload(template: &str, transition: &StatusTransition) -> String {
+    let path = transition.path.display().to_string();
+    let timestamp = transition.at.to_rfc3339();
+    let value = |name: &str| match name {
+        "directory" => Some(transition.directory.as_str()),
+        "path" => Some(path.as_str()),
+        "event" => Some(transition.kind.as_str()),
+        "status" => Some(transition.status.as_str()),
+        "message" => Some(transition.message.as_str()),
+        "timestamp" => Some(timestamp.as_str()),
+        _ => None,
+    };
+
+    // 只扫描一遍模板，替换进来的值中即使含有 `{{...}}` 也不会再被展开
+    let mut body = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        body.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after
+            .find("}}")
+            .and_then(|end| Some((end, value(&after[..end])?)))
+        {
+            Some((end, value)) => {
+                body.push_str(&json_escape(value));
+                rest = &after[end + 2..];
+            }
+            // 未知的占位符原样保留
+            None => {
+                body.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    body.push_str(rest);
+    body
+}
+
+fn json_escape(value: &str) -> String {
+    let quoted = serde_json::to_string(value).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
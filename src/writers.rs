@@ -0,0 +1,117 @@
+//! 通过 /proc 检测正在写入文件的进程（仅 Linux）
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 以写入方式打开了二级目录中某个文件的进程
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpenWriter {
+    pub pid: u32,
+    /// 进程名（/proc/<pid>/comm）
+    pub command: String,
+    /// 被写入的文件
+    pub path: PathBuf,
+}
+
+/// 查找以写入方式打开了根目录下文件的进程，按二级目录名分组
+///
+/// 无权访问的进程会被跳过。非 Linux 平台始终返回空结果。
+pub fn find_open_writers(root_path: &Path) -> Result<BTreeMap<String, Vec<OpenWriter>>> {
+    let root_path = root_path.canonicalize()?;
+    let mut writers: BTreeMap<String, Vec<OpenWriter>> = BTreeMap::new();
+
+    for writer in scan_proc(&root_path)? {
+        if let Some(name) = second_level_dir(&root_path, &writer.path) {
+            writers.entry(name).or_default().push(writer);
+        }
+    }
+
+    for list in writers.values_mut() {
+        list.sort_by(|a, b| (a.pid, &a.path).cmp(&(b.pid, &b.path)));
+        list.dedup();
+    }
+    Ok(writers)
+}
+
+#[cfg(target_os = "linux")]
+fn scan_proc(root_path: &Path) -> Result<Vec<OpenWriter>> {
+    use anyhow::Context;
+    use std::fs;
+
+    let mut writers = Vec::new();
+    let entries = fs::read_dir("/proc").context("无法读取 /proc")?;
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let proc_dir = entry.path();
+        // 进程可能已退出或属于其他用户
+        let Ok(fds) = fs::read_dir(proc_dir.join("fd")) else {
+            continue;
+        };
+
+        let mut command = None;
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            if !target.starts_with(root_path) || !is_write_mode(&proc_dir, &fd.file_name()) {
+                continue;
+            }
+
+            let command = command
+                .get_or_insert_with(|| {
+                    fs::read_to_string(proc_dir.join("comm"))
+                        .map(|comm| comm.trim().to_string())
+                        .unwrap_or_default()
+                })
+                .clone();
+            writers.push(OpenWriter {
+                pid,
+                command,
+                path: target,
+            });
+        }
+    }
+
+    Ok(writers)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn scan_proc(_root_path: &Path) -> Result<Vec<OpenWriter>> {
+    log::debug!("当前平台不支持检测写入进程");
+    Ok(Vec::new())
+}
+
+/// 根据 /proc/<pid>/fdinfo/<fd> 中的 flags 判断描述符是否可写
+#[cfg(target_os = "linux")]
+fn is_write_mode(proc_dir: &Path, fd: &std::ffi::OsStr) -> bool {
+    const O_ACCMODE: u32 = 0o3;
+    const O_WRONLY: u32 = 0o1;
+    const O_RDWR: u32 = 0o2;
+
+    let Ok(fdinfo) = std::fs::read_to_string(proc_dir.join("fdinfo").join(fd)) else {
+        return false;
+    };
+    fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .is_some_and(|flags| matches!(flags & O_ACCMODE, O_WRONLY | O_RDWR))
+}
+
+/// 文件所属的二级目录名；根目录下的直接文件返回 `None`
+fn second_level_dir(root_path: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root_path).ok()?;
+    let mut components = relative.components();
+    let name = components.next()?.as_os_str().to_str()?.to_string();
+    components.next()?;
+    Some(name)
+}
@@ -1,6 +1,20 @@
+use file_monitor::backoff::{ScanBackoff, MAX_BACKOFF_SCANS};
+use file_monitor::report::{OutputFormat, ReportWriter};
+use file_monitor::server::{self, StatusStore};
+use file_monitor::traversal::date_key;
+use file_monitor::webhook::render_payload;
+use file_monitor::{
+    CheckRules, CheckState, Config, ConfigErrors, ConfigWatcher, DirectoryOverride,
+    DirectoryOverrides, DirectoryResult, DirectoryStatus, EventWatcher, HistoryStore, HookRunner,
+    HooksConfig, MonitorConfig, OutputConfig, ReloadTrigger, RootConfig, ScanCache, ScanReport,
+    Scanner, StatusTransition, TransitionKind, TransitionTracker, WebhookConfig, WebhookNotifier,
+};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
 
 /// 创建测试目录结构
@@ -274,6 +288,283 @@ fn test_non_interactive_mode() {
     assert!(stdout.contains("配置文件创建完成") || stdout.contains("文件监控报告"));
 }
 
+#[test]
+fn test_saved_config_escapes_strings() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let config_path = temp_dir.path().join("config.toml");
+    let config_arg = config_path.to_str().unwrap();
+    fs::write(
+        &config_path,
+        "[monitor]\nroot_path = \"/data\"\ncheck_hours = 1\nscan_interval = 60\n\n[output]\nrecording_message = \"a\"\nnot_recording_message = \"b\"\n",
+    )
+    .unwrap();
+
+    // 含引号和反斜杠的字符串保存后仍是有效的 TOML
+    let mut config = Config::from_file(config_arg).unwrap();
+    config.monitor.root_path = r#"D:\录像\"频道""#.to_string();
+    config.output.recording_message = r#"正在"录制""#.to_string();
+    config.output.not_recording_message = r"未录制\".to_string();
+    config.output.stale_icon = Some(r#"[\"OLD"]"#.to_string());
+    file_monitor::config::save_config_safely(config_arg, &config).expect("保存失败");
+
+    let saved = Config::from_file(config_arg).expect("保存后的配置应当有效");
+    assert_eq!(saved.monitor.root_path, config.monitor.root_path);
+    assert_eq!(
+        saved.output.recording_message,
+        config.output.recording_message
+    );
+    assert_eq!(
+        saved.output.not_recording_message,
+        config.output.not_recording_message
+    );
+    assert_eq!(saved.output.stale_icon, config.output.stale_icon);
+
+    // 枚举类取值同样转义，即使取值无效，输出也总能解析回原值
+    config.monitor.parallel_mode = Some(r#"as"ync\"#.to_string());
+    config.monitor.traversal = Some("newest\nfirst".to_string());
+    config.output.format = Some(r#"js"on"#.to_string());
+    let rendered = file_monitor::config::render_config(&config).expect("输出失败");
+    fs::write(&config_path, rendered).unwrap();
+    let reparsed = Config::from_file_unchecked(config_arg).expect("输出的配置应当能够解析");
+    assert_eq!(reparsed.monitor.parallel_mode, config.monitor.parallel_mode);
+    assert_eq!(reparsed.monitor.traversal, config.monitor.traversal);
+    assert_eq!(reparsed.output.format, config.output.format);
+}
+
+#[test]
+fn test_cli_subcommands() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+    let config_path = temp_dir.path().join("file_monitor.toml");
+    let config_arg = config_path.to_str().unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_file_monitor"))
+            .args(["--config", config_arg])
+            .args(args)
+            .output()
+            .expect("Failed to run program");
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+        )
+    };
+
+    let (code, stdout) = run(&[
+        "config",
+        "init",
+        "--monitor-path",
+        temp_dir.path().to_str().unwrap(),
+        "--set",
+        "check_hours=5",
+    ]);
+    assert_eq!(code, Some(0), "{}", stdout);
+    assert_eq!(
+        Config::from_file(config_arg).unwrap().monitor.check_hours,
+        5
+    );
+
+    // 已存在时拒绝覆盖
+    let (code, _) = run(&["config", "init", "--monitor-path", "/tmp"]);
+    assert_eq!(code, Some(1));
+
+    let (code, stdout) = run(&["config", "set", "scan_interval=30", "include=[\"*.txt\"]"]);
+    assert_eq!(code, Some(0), "{}", stdout);
+    let config = Config::from_file(config_arg).expect("保存后的配置应当有效");
+    assert_eq!(config.monitor.scan_interval, 30);
+    assert_eq!(config.monitor.include, Some(vec!["*.txt".to_string()]));
+    assert_eq!(config.monitor.check_hours, 5);
+
+    let (code, stdout) = run(&["config", "validate"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("[成功] 配置有效"), "{}", stdout);
+    let (code, stdout) = run(&["config", "validate", "--set", "check_hourz=1"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("未知的配置项 'check_hourz'"), "{}", stdout);
+
+    // --set 只在本次运行中生效
+    let (code, stdout) = run(&["config", "show", "--set", "stale_minutes=10"]);
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("\nstale_minutes = 10\n"), "{}", stdout);
+    assert!(Config::from_file(config_arg)
+        .unwrap()
+        .monitor
+        .stale_minutes
+        .is_none());
+
+    let (code, stdout) = run(&["scan", "--output-format", "json"]);
+    assert_eq!(code, Some(0));
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["directories"][2]["status"], "idle");
+    let (code, stdout) = run(&[
+        "scan",
+        "--output-format",
+        "json",
+        "--set",
+        "check_hours=168",
+    ]);
+    assert_eq!(code, Some(0));
+    let json: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(json["directories"][2]["status"], "recording");
+
+    let (code, stdout) = run(&["doctor"]);
+    assert_eq!(code, Some(0), "{}", stdout);
+    assert!(stdout.contains("[成功] 配置文件"), "{}", stdout);
+    assert!(stdout.contains("3 个二级目录"), "{}", stdout);
+    let (code, stdout) = run(&["doctor", "--set", "root_path=/nonexistent/file_monitor"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.contains("[错误] 监控目录"), "{}", stdout);
+
+    // 监控命令校验的是应用 --set 之后的配置：--set 可以修正文件中的错误，也会被校验
+    let (code, _) = run(&["scan", "--set", "scan_interval=0"]);
+    assert_ne!(code, Some(0));
+    let content = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        &config_path,
+        content.replace("scan_interval = 30", "scan_interval = 0"),
+    )
+    .unwrap();
+    assert!(Config::from_file(config_arg).is_err());
+    let (code, stdout) = run(&[
+        "scan",
+        "--output-format",
+        "json",
+        "--set",
+        "scan_interval=30",
+    ]);
+    assert_eq!(code, Some(0), "{}", stdout);
+}
+
+#[test]
+fn test_config_validation_diagnostics() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let config_path = temp_dir.path().join("config.toml");
+    let load = |content: &str| {
+        fs::write(&config_path, content).unwrap();
+        Config::from_file(config_path.to_str().unwrap())
+    };
+    let output = "\n[output]\nrecording_message = \"a\"\nnot_recording_message = \"b\"\n";
+
+    let err = load(&format!(
+        "[monitor]\nroot_path = \"/data\"\ncheck_hours = 0\nscan_interval = 60\nparralel_mode = \"async\"\nparallel_mode = \"fast\"\ntime_type = \"modifed\"\n{}",
+        output
+    ))
+    .unwrap_err();
+    let errors = err
+        .downcast_ref::<ConfigErrors>()
+        .expect("应当报告全部问题");
+    let messages: Vec<String> = errors.0.iter().map(|issue| issue.to_string()).collect();
+    assert_eq!(messages.len(), 4, "{:#?}", messages);
+    assert_eq!(
+        messages[0],
+        "第 3 行第 15 列: 'monitor.check_hours' 必须大于 0"
+    );
+    assert_eq!(
+        messages[1],
+        "第 5 行第 1 列: 未知的配置项 'monitor.parralel_mode'，是否应为 'parallel_mode'？"
+    );
+    assert!(
+        messages[2].starts_with("第 6 行第 17 列: 'monitor.parallel_mode' 的值 \"fast\" 无效"),
+        "{}",
+        messages[2]
+    );
+    assert!(
+        messages[3].ends_with("是否应为 \"modified\"？"),
+        "{}",
+        messages[3]
+    );
+
+    // 表数组、目录覆盖和顶层表中的未知配置项
+    let err = load(&format!(
+        "[monitor]\ncheck_hours = 1\nscan_interval = 60\nbatch_size = 0\n\n[[monitor.roots]]\nroot_path = \"/a\"\nchek_hours = 1\n{}\n[directories.\"hls_*\"]\ntime_type = \"ctime\"\n\n[histroy]\nenabled = false\n",
+        output
+    ))
+    .unwrap_err();
+    let text = format!("{:#}", err);
+    assert!(
+        text.contains("第 4 行第 14 列: 'monitor.batch_size' 必须大于 0"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains(
+            "第 8 行第 1 列: 未知的配置项 'monitor.roots.0.chek_hours'，是否应为 'check_hours'？"
+        ),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("第 15 行第 13 列: 'directories.hls_*.time_type' 的值 \"ctime\" 无效"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("第 17 行第 2 列: 未知的配置项 'histroy'，是否应为 'history'？"),
+        "{}",
+        text
+    );
+
+    // 语法和类型错误同样给出位置
+    let err = load(&format!(
+        "[monitor]\nroot_path = \"/data\"\ncheck_hours = \"abc\"\nscan_interval = 60\n{}",
+        output
+    ))
+    .unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("第 3 行第 15 列: invalid type"),
+        "{:#}",
+        err
+    );
+
+    // --set 设置的值没有文件位置
+    fs::write(
+        &config_path,
+        format!(
+            "[monitor]\nroot_path = \"/data\"\ncheck_hours = 1\nscan_interval = 60\n{}",
+            output
+        ),
+    )
+    .unwrap();
+    let err = Config::load(
+        config_path.to_str().unwrap(),
+        &["scan_interval=0".to_string()],
+    )
+    .unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("'monitor.scan_interval' 必须大于 0（来自 --set）"),
+        "{:#}",
+        err
+    );
+
+    // 仓库中的示例配置必须通过校验
+    Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml")).expect("示例配置无效");
+}
+
+#[test]
+fn test_monitor_config_apply_setting() {
+    let mut config = MonitorConfig::new("/data");
+    config.apply_setting("check_hours = 6").unwrap();
+    config.apply_setting("monitor.time_type=created").unwrap();
+    config.apply_setting("root_path=/mnt/recordings").unwrap();
+    config
+        .apply_setting("exclude=[\"*.tmp\", \"logs\"]")
+        .unwrap();
+    config.apply_setting("find_newest=true").unwrap();
+    assert_eq!(config.check_hours, 6);
+    assert_eq!(config.time_type.as_deref(), Some("created"));
+    assert_eq!(config.root_path, "/mnt/recordings");
+    assert_eq!(
+        config.exclude,
+        Some(vec!["*.tmp".to_string(), "logs".to_string()])
+    );
+    assert_eq!(config.find_newest, Some(true));
+
+    let before = config.clone();
+    assert!(config.apply_setting("check_hours").is_err());
+    assert!(config.apply_setting("check_hours=many").is_err());
+    assert!(config.apply_setting("unknown_key=1").is_err());
+    assert_eq!(config, before);
+}
+
 #[test]
 fn test_invalid_configuration() {
     let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -366,3 +657,2483 @@ not_recording_message = "未录制"
         );
     }
 }
+
+/// 创建两个有新文件的目录和一个只有旧文件的目录
+fn create_library_test_structure(base_dir: &Path) -> std::io::Result<()> {
+    for name in ["active_a", "active_b"] {
+        let subdir = base_dir.join(name).join("session");
+        fs::create_dir_all(&subdir)?;
+        fs::write(subdir.join("new_file.txt"), "new content")?;
+    }
+
+    let idle_dir = base_dir.join("idle");
+    let subdir = idle_dir.join("session");
+    fs::create_dir_all(&subdir)?;
+    let old_file = subdir.join("old_file.txt");
+    fs::write(&old_file, "old content")?;
+
+    // 将旧文件及其目录的时间设置为3天前
+    let old_time =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3 * 86400));
+    filetime::set_file_mtime(&old_file, old_time)?;
+    filetime::set_file_mtime(&subdir, old_time)?;
+    filetime::set_file_mtime(&idle_dir, old_time)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_scanner_library_api() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    for mode in ["sync", "async", "parallel"] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.check_hours = 3;
+        config.parallel_mode = Some(mode.to_string());
+
+        let report = Scanner::new(config).scan().await.expect("扫描失败");
+
+        let names: Vec<_> = report.directories.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["active_a", "active_b", "idle"], "模式: {}", mode);
+        assert!(report.get("active_a").unwrap().is_recording());
+        assert!(report.get("active_b").unwrap().is_recording());
+        assert!(!report.get("idle").unwrap().is_recording());
+        assert_eq!(report.recording_count(), 2);
+        assert_eq!(report.parallel_mode, mode);
+    }
+}
+
+#[tokio::test]
+async fn test_max_parallel_tasks_bounds_scan() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    for i in 0..20 {
+        let dir = temp_dir.path().join(format!("channel_{:02}", i));
+        fs::create_dir_all(&dir).unwrap();
+        if i % 2 == 0 {
+            fs::write(dir.join("segment.ts"), "data").unwrap();
+        }
+    }
+
+    for mode in ["async", "parallel"] {
+        for max_tasks in [1, 3] {
+            let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+            config.parallel_mode = Some(mode.to_string());
+            config.max_parallel_tasks = Some(max_tasks);
+
+            let running = Arc::new(AtomicUsize::new(0));
+            let peak = Arc::new(AtomicUsize::new(0));
+            let (hook_running, hook_peak) = (Arc::clone(&running), Arc::clone(&peak));
+            let scanner = Scanner::new(config).with_directory_hook(move |_| {
+                let current = hook_running.fetch_add(1, Ordering::SeqCst) + 1;
+                hook_peak.fetch_max(current, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(20));
+                hook_running.fetch_sub(1, Ordering::SeqCst);
+            });
+
+            let report = scanner.scan().await.expect("扫描失败");
+            assert_eq!(report.directories.len(), 20, "模式: {}", mode);
+            assert_eq!(report.recording_count(), 10, "模式: {}", mode);
+            assert!(report.get("channel_00").unwrap().is_recording());
+            assert!(!report.get("channel_01").unwrap().is_recording());
+
+            let peak = peak.load(Ordering::SeqCst);
+            assert!(peak <= max_tasks, "模式: {}, 并发峰值: {}", mode, peak);
+            if max_tasks > 1 {
+                assert!(peak > 1, "模式: {} 未并发扫描", mode);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_panicked_directory_scan_is_reported() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    for name in ["channel_a", "channel_b"] {
+        let dir = temp_dir.path().join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("segment.ts"), "data").unwrap();
+    }
+
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.parallel_mode = Some("async".to_string());
+    let scanner = Scanner::new(config).with_directory_hook(|path| {
+        if path.ends_with("channel_b") {
+            panic!("模拟扫描异常");
+        }
+    });
+
+    let report = scanner.scan().await.expect("扫描失败");
+    assert_eq!(report.directories.len(), 2);
+    assert!(report.get("channel_a").unwrap().is_recording());
+    assert!(matches!(
+        report.get("channel_b").unwrap().status,
+        DirectoryStatus::Error(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_incremental_scan_cache() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("recordings");
+    let archive = root.join("archive").join("2024").join("05");
+    fs::create_dir_all(&archive).unwrap();
+    let old_time =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3 * 86400));
+    for i in 0..5 {
+        let file = archive.join(format!("segment_{}.ts", i));
+        fs::write(&file, "old").unwrap();
+        filetime::set_file_mtime(&file, old_time).unwrap();
+    }
+    for dir in [
+        &archive,
+        &archive.parent().unwrap().to_path_buf(),
+        &root.join("archive"),
+    ] {
+        filetime::set_file_mtime(dir, old_time).unwrap();
+    }
+
+    let mut config = MonitorConfig::new(root.display().to_string());
+    config.incremental = Some(true);
+    let cache_path = temp_dir.path().join("scan_cache.json");
+    let scanner =
+        Scanner::new(config.clone()).with_scan_cache(ScanCache::load(&cache_path, &config));
+
+    // 第一次扫描读取全部文件并建立缓存
+    let report = scanner.scan().await.expect("扫描失败");
+    let archive_result = report.get("archive").unwrap();
+    assert!(!archive_result.is_recording());
+    assert_eq!(archive_result.files_examined, 5);
+    assert_eq!(scanner.scan_cache().unwrap().len(), 3);
+    assert!(cache_path.exists());
+
+    // 目录没有变化：只重新检查缓存中的最新文件
+    let report = scanner.scan().await.expect("扫描失败");
+    assert_eq!(report.get("archive").unwrap().files_examined, 1);
+    assert!(!report.get("archive").unwrap().is_recording());
+
+    // 对缓存中最新文件的追加写入不会改变目录时间，但仍会被发现
+    let newest_path = &report
+        .get("archive")
+        .unwrap()
+        .newest_file
+        .as_ref()
+        .unwrap()
+        .path;
+    let newest = root.join("archive").join(newest_path);
+    filetime::set_file_mtime(&newest, filetime::FileTime::now()).unwrap();
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.get("archive").unwrap().is_recording());
+    filetime::set_file_mtime(&newest, old_time).unwrap();
+
+    // 新文件改变目录的修改时间，目录会被重新读取
+    fs::write(archive.join("segment_new.ts"), "new").unwrap();
+    let report = scanner.scan().await.expect("扫描失败");
+    let archive_result = report.get("archive").unwrap();
+    assert!(archive_result.is_recording());
+    assert!(archive_result
+        .newest_file
+        .as_ref()
+        .unwrap()
+        .path
+        .ends_with("segment_new.ts"));
+
+    // 缓存保存到文件，重新启动后继续使用
+    fs::remove_file(archive.join("segment_new.ts")).unwrap();
+    filetime::set_file_mtime(&archive, old_time).unwrap();
+    scanner.scan().await.expect("扫描失败");
+    let reloaded = ScanCache::load(&cache_path, &config);
+    assert_eq!(reloaded.len(), 3);
+    let report = Scanner::new(config.clone())
+        .with_scan_cache(reloaded)
+        .scan()
+        .await
+        .expect("扫描失败");
+    assert_eq!(report.get("archive").unwrap().files_examined, 1);
+
+    // 过滤规则变化后缓存作废
+    config.exclude = Some(vec!["*.tmp".to_string()]);
+    assert!(ScanCache::load(&cache_path, &config).is_empty());
+}
+
+#[test]
+fn test_scan_backoff_after_timeouts() {
+    let backoff = Arc::new(ScanBackoff::new());
+    let stuck = Path::new("/mnt/nfs/stuck");
+    assert_eq!(backoff.skip(stuck), None);
+
+    // 第 n 次连续超时后跳过 2^(n-1) 次扫描
+    assert_eq!(backoff.record_timeout(stuck), 1);
+    let status = backoff.skip(stuck).unwrap();
+    assert!(matches!(status, DirectoryStatus::Unavailable(_)));
+    assert!(status.reason().unwrap().contains("连续 1 次扫描超时"));
+    assert_eq!(backoff.skip(stuck), None);
+    assert_eq!(backoff.record_timeout(stuck), 2);
+    assert!(backoff.skip(stuck).is_some());
+    assert!(backoff.skip(stuck).is_some());
+    assert_eq!(backoff.skip(stuck), None);
+    for _ in 0..10 {
+        backoff.record_timeout(stuck);
+    }
+    assert_eq!(backoff.record_timeout(stuck), MAX_BACKOFF_SCANS);
+
+    // 按时完成后恢复正常扫描
+    backoff.record_success(stuck);
+    assert_eq!(backoff.skip(stuck), None);
+
+    // 超时的扫描线程结束之前不会再次扫描同一目录
+    let running = backoff.start(stuck);
+    let status = backoff.skip(stuck).unwrap();
+    assert!(matches!(status, DirectoryStatus::Unavailable(_)));
+    assert!(status.reason().unwrap().contains("仍未结束"));
+    drop(running);
+    assert_eq!(backoff.skip(stuck), None);
+
+    // 只是被截止时间打断的目录报告为状态未知，而不是不可用
+    let running = backoff.start(stuck);
+    backoff.record_deadline(stuck);
+    let status = backoff.skip(stuck).unwrap();
+    assert!(matches!(status, DirectoryStatus::Unknown(_)));
+    assert!(status.reason().unwrap().contains("截止时间"));
+    drop(running);
+    assert_eq!(backoff.skip(stuck), None);
+}
+
+#[tokio::test]
+async fn test_scan_timeout_in_all_modes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    for mode in ["sync", "async", "parallel"] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.check_hours = 3;
+        config.parallel_mode = Some(mode.to_string());
+        config.max_parallel_tasks = Some(1);
+        config.scan_timeout = Some(30);
+
+        let report = Scanner::new(config).scan().await.expect("扫描失败");
+        let names: Vec<_> = report.directories.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["active_a", "active_b", "idle"], "模式: {}", mode);
+        assert_eq!(report.recording_count(), 2, "模式: {}", mode);
+        assert_eq!(
+            report.get("idle").unwrap().status,
+            DirectoryStatus::Idle,
+            "模式: {}",
+            mode
+        );
+    }
+}
+
+/// 记录各目录开始扫描的顺序，并可以让下一个开始扫描的目录（或列出中的根目录）阻塞，模拟挂起的挂载点
+#[derive(Default)]
+struct ScanProbe {
+    visited: Mutex<Vec<String>>,
+    block_next: AtomicBool,
+    block_dir: Mutex<Option<String>>,
+    blocked: Mutex<bool>,
+    unblocked: Condvar,
+}
+
+impl ScanProbe {
+    fn attach(self: &Arc<Self>, scanner: Scanner) -> Scanner {
+        let probe = Arc::clone(self);
+        let roots: Vec<PathBuf> = scanner
+            .root_paths()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+        scanner.with_directory_hook(move |path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            // 列出根目录时同样调用，只记录二级目录的扫描顺序
+            let is_root = roots.iter().any(|root| root == path);
+            if !is_root {
+                probe.visited.lock().unwrap().push(name.clone());
+            }
+            let block_dir = probe.block_dir.lock().unwrap().take_if(|dir| *dir == name);
+            let block_next = !is_root && probe.block_next.swap(false, Ordering::SeqCst);
+            if block_next || block_dir.is_some() {
+                let mut blocked = probe.blocked.lock().unwrap();
+                *blocked = true;
+                // 最多阻塞 10 秒，断言失败时测试不会一直等待阻塞线程
+                let _ = probe
+                    .unblocked
+                    .wait_timeout_while(blocked, Duration::from_secs(10), |blocked| *blocked)
+                    .unwrap();
+            }
+        })
+    }
+
+    fn block_next(&self) {
+        self.block_next.store(true, Ordering::SeqCst);
+    }
+
+    /// 让指定目录下次开始扫描时阻塞
+    fn block(&self, name: &str) {
+        *self.block_dir.lock().unwrap() = Some(name.to_string());
+    }
+
+    /// 放行被阻塞的目录，并等待它的扫描线程结束
+    fn release(&self) {
+        *self.blocked.lock().unwrap() = false;
+        self.unblocked.notify_all();
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    fn take_visited(&self) -> Vec<String> {
+        std::mem::take(&mut *self.visited.lock().unwrap())
+    }
+}
+
+fn reason(report: &ScanReport, name: &str) -> String {
+    report
+        .get(name)
+        .unwrap()
+        .status
+        .reason()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_hung_directory_times_out_in_all_modes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    for mode in ["sync", "async", "parallel"] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.check_hours = 3;
+        config.parallel_mode = Some(mode.to_string());
+        config.max_parallel_tasks = Some(1);
+        config.scan_timeout = Some(1);
+        let probe = Arc::new(ScanProbe::default());
+        let scanner = probe.attach(Scanner::new(config));
+
+        // 挂起的目录超时后报告为不可用，只有一个并发名额时其余目录仍能完成扫描
+        probe.block_next();
+        let report = scanner.scan().await.expect("扫描失败");
+        let hung = probe.take_visited()[0].clone();
+        assert!(
+            matches!(
+                report.get(&hung).unwrap().status,
+                DirectoryStatus::Unavailable(_)
+            ),
+            "模式: {}",
+            mode
+        );
+        assert!(
+            reason(&report, &hung).contains("扫描超时"),
+            "模式: {}",
+            mode
+        );
+        assert_eq!(report.directories.len(), 3, "模式: {}", mode);
+        assert!(
+            report
+                .directories
+                .iter()
+                .filter(|d| d.name != hung)
+                .all(|d| !d.status.is_failure()),
+            "模式: {}",
+            mode
+        );
+
+        // 扫描线程仍未结束时不再扫描该目录
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(
+            reason(&report, &hung).contains("仍未结束"),
+            "模式: {}",
+            mode
+        );
+        assert!(!probe.take_visited().contains(&hung), "模式: {}", mode);
+
+        // 线程结束后按退避跳过一次，之后恢复扫描
+        probe.release();
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(
+            reason(&report, &hung).contains("连续 1 次扫描超时"),
+            "模式: {}",
+            mode
+        );
+        assert!(!probe.take_visited().contains(&hung), "模式: {}", mode);
+
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(probe.take_visited().contains(&hung), "模式: {}", mode);
+        assert_eq!(report.recording_count(), 2, "模式: {}", mode);
+        assert_eq!(
+            report.get("idle").unwrap().status,
+            DirectoryStatus::Idle,
+            "模式: {}",
+            mode
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_hung_root_listing_times_out() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("recordings");
+    create_library_test_structure(&root).expect("Failed to create test structure");
+
+    let mut config = MonitorConfig::new(root.display().to_string());
+    config.check_hours = 3;
+    config.scan_timeout = Some(1);
+    let probe = Arc::new(ScanProbe::default());
+    let scanner = probe.attach(Scanner::new(config));
+
+    // 列出根目录时挂起，按 scan_timeout 报告根目录不可访问，而不是一直等待
+    probe.block("recordings");
+    let started = std::time::Instant::now();
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(report.directories.is_empty());
+    let error = report.roots[0].error.clone().unwrap_or_default();
+    assert!(error.contains("列出监控目录超时"), "{}", error);
+    assert!(probe.take_visited().is_empty());
+
+    // 列出线程仍未结束时不再列出，之后按退避跳过一次
+    let report = scanner.scan().await.expect("扫描失败");
+    let error = report.roots[0].error.clone().unwrap_or_default();
+    assert!(error.contains("仍未结束"), "{}", error);
+    probe.release();
+    let report = scanner.scan().await.expect("扫描失败");
+    let error = report.roots[0].error.clone().unwrap_or_default();
+    assert!(error.contains("连续 1 次扫描超时"), "{}", error);
+
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.roots.iter().all(|root| root.error.is_none()));
+    assert_eq!(report.directories.len(), 3);
+    assert_eq!(report.recording_count(), 2);
+}
+
+#[tokio::test]
+async fn test_scan_deadline_in_all_modes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // 截止时间足够长时所有目录都能完成扫描，多次扫描结果保持一致
+    for mode in ["sync", "async", "parallel"] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.check_hours = 3;
+        config.parallel_mode = Some(mode.to_string());
+        config.max_parallel_tasks = Some(1);
+        config.scan_deadline = Some(60);
+        config.scan_timeout = Some(30);
+
+        let scanner = Scanner::new(config);
+        for _ in 0..2 {
+            let report = scanner.scan().await.expect("扫描失败");
+            let names: Vec<_> = report.directories.iter().map(|d| d.name.as_str()).collect();
+            assert_eq!(names, ["active_a", "active_b", "idle"], "模式: {}", mode);
+            assert_eq!(report.recording_count(), 2, "模式: {}", mode);
+            assert!(
+                report
+                    .directories
+                    .iter()
+                    .all(|d| !matches!(d.status, DirectoryStatus::Unknown(_))),
+                "模式: {}",
+                mode
+            );
+        }
+    }
+
+    // 未完成的目录报告为状态未知，不计入录制或未录制
+    let status = DirectoryStatus::Unknown("超过扫描截止时间".to_string());
+    assert_eq!(status.as_str(), "unknown");
+    assert!(status.is_failure());
+    assert_eq!(status.reason(), Some("超过扫描截止时间"));
+    let output = OutputConfig::default();
+    assert_eq!(output.message(&status), "状态未知");
+    assert_eq!(output.icon(&status), "[???]");
+
+    let config_path = temp_dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        "[monitor]\nroot_path = \"/data\"\ncheck_hours = 1\nscan_interval = 60\nscan_deadline = 0\n\n[output]\nrecording_message = \"a\"\nnot_recording_message = \"b\"\n",
+    )
+    .unwrap();
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("'monitor.scan_deadline' 必须大于 0"),
+        "{:#}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_slow_directory_with_deadline_in_all_modes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    for mode in ["sync", "async", "parallel"] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.check_hours = 3;
+        config.parallel_mode = Some(mode.to_string());
+        config.max_parallel_tasks = Some(1);
+        config.scan_deadline = Some(1);
+        let probe = Arc::new(ScanProbe::default());
+        let scanner = probe.attach(Scanner::new(config));
+
+        // 截止时间到时正在扫描和尚未开始的目录都报告为状态未知
+        probe.block_next();
+        let report = scanner.scan().await.expect("扫描失败");
+        let visited = probe.take_visited();
+        assert_eq!(visited.len(), 1, "模式: {}", mode);
+        let slow = visited[0].clone();
+        assert_eq!(report.directories.len(), 3, "模式: {}", mode);
+        for dir in &report.directories {
+            assert!(
+                matches!(dir.status, DirectoryStatus::Unknown(_)),
+                "模式: {}",
+                mode
+            );
+        }
+        assert!(
+            reason(&report, &slow).contains("扫描未完成"),
+            "模式: {}",
+            mode
+        );
+
+        // 只设置截止时间时，扫描线程仍未结束的目录同样不再扫描，状态仍为未知
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(
+            matches!(
+                report.get(&slow).unwrap().status,
+                DirectoryStatus::Unknown(_)
+            ),
+            "模式: {}",
+            mode
+        );
+        assert!(
+            reason(&report, &slow).contains("仍未结束"),
+            "模式: {}",
+            mode
+        );
+        assert_eq!(probe.take_visited().len(), 2, "模式: {}", mode);
+        assert!(
+            report
+                .directories
+                .iter()
+                .filter(|d| d.name != slow)
+                .all(|d| !d.status.is_failure()),
+            "模式: {}",
+            mode
+        );
+        probe.release();
+
+        // 上次扫描线程仍未结束的目录仍算未完成，下次扫描时排在最前面
+        let report = scanner.scan().await.expect("扫描失败");
+        let natural = probe.take_visited();
+        assert_eq!(natural.len(), 3, "模式: {}", mode);
+        assert_eq!(natural[0], slow, "模式: {}", mode);
+        assert!(
+            report.directories.iter().all(|d| !d.status.is_failure()),
+            "模式: {}",
+            mode
+        );
+
+        // 扫描到一半被截止的目录同样优先，即使它原本排在最后
+        let tail = natural.last().unwrap().clone();
+        probe.block(&tail);
+        let report = scanner.scan().await.expect("扫描失败");
+        assert_eq!(probe.take_visited(), natural, "模式: {}", mode);
+        assert!(
+            reason(&report, &tail).contains("扫描未完成"),
+            "模式: {}",
+            mode
+        );
+        probe.release();
+
+        let report = scanner.scan().await.expect("扫描失败");
+        let visited = probe.take_visited();
+        assert_eq!(visited.len(), 3, "模式: {}", mode);
+        assert_eq!(visited[0], tail, "模式: {}", mode);
+        assert_eq!(report.recording_count(), 2, "模式: {}", mode);
+        assert!(
+            report.directories.iter().all(|d| !d.status.is_failure()),
+            "模式: {}",
+            mode
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_newest_first_traversal() {
+    assert_eq!(date_key("2024-05-17"), Some(vec![2024, 5, 17]));
+    assert_eq!(date_key("20240517_1200"), Some(vec![20240517, 1200]));
+    assert_eq!(date_key("session_1"), None);
+    assert_eq!(date_key("05"), Some(vec![5]));
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let old_time =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3 * 86400));
+
+    // 按日期分层：只有最新的日期目录中有新文件
+    let dated = temp_dir.path().join("dated");
+    for year in ["2022", "2023", "2024"] {
+        for month in ["1", "5", "12"] {
+            for day in ["01", "09", "17"] {
+                let leaf = dated.join(year).join(month).join(day);
+                fs::create_dir_all(&leaf).unwrap();
+                if (year, month, day) == ("2024", "12", "17") {
+                    fs::write(leaf.join("live.ts"), "new").unwrap();
+                    continue;
+                }
+                for i in 0..3 {
+                    let file = leaf.join(format!("segment_{}.ts", i));
+                    fs::write(&file, "old").unwrap();
+                    filetime::set_file_mtime(&file, old_time).unwrap();
+                }
+            }
+        }
+    }
+
+    // 非日期命名的会话目录按修改时间排序
+    let sessions = temp_dir.path().join("sessions");
+    for name in ["alpha", "beta", "gamma"] {
+        let session = sessions.join(name);
+        fs::create_dir_all(&session).unwrap();
+        let file = session.join("part.ts");
+        fs::write(&file, "data").unwrap();
+        if name != "beta" {
+            filetime::set_file_mtime(&file, old_time).unwrap();
+            filetime::set_file_mtime(&session, old_time).unwrap();
+        }
+    }
+
+    for incremental in [false, true] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.traversal = Some("newest_first".to_string());
+        config.incremental = Some(incremental);
+
+        let report = Scanner::new(config).scan().await.expect("扫描失败");
+        let dated_result = report.get("dated").unwrap();
+        assert!(dated_result.is_recording());
+        assert_eq!(
+            dated_result.files_examined, 1,
+            "incremental: {}",
+            incremental
+        );
+        assert_eq!(
+            dated_result.newest_file.as_ref().unwrap().path,
+            Path::new("2024/12/17/live.ts")
+        );
+        let sessions_result = report.get("sessions").unwrap();
+        assert!(sessions_result.is_recording());
+        assert_eq!(
+            sessions_result.files_examined, 1,
+            "incremental: {}",
+            incremental
+        );
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_follow_links_symlink_loop_terminates() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let channel = temp_dir.path().join("channel");
+    let nested = channel.join("nested");
+    fs::create_dir_all(&nested).unwrap();
+    let file = nested.join("old.ts");
+    fs::write(&file, "old").unwrap();
+    let old_time =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3 * 86400));
+    filetime::set_file_mtime(&file, old_time).unwrap();
+    std::os::unix::fs::symlink("..", nested.join("loop_a")).unwrap();
+    std::os::unix::fs::symlink("..", nested.join("loop_b")).unwrap();
+
+    for incremental in [false, true] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.follow_links = Some(true);
+        config.incremental = Some(incremental);
+
+        let report = tokio::time::timeout(Duration::from_secs(10), Scanner::new(config).scan())
+            .await
+            .expect("符号链接循环导致扫描未结束")
+            .expect("扫描失败");
+        let result = report.get("channel").unwrap();
+        assert!(!result.is_recording(), "incremental: {}", incremental);
+        assert_eq!(result.files_examined, 1, "incremental: {}", incremental);
+    }
+}
+
+#[tokio::test]
+async fn test_scanner_search_latest_subdir_only() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.search_latest_subdir_only = Some(true);
+    config.batch_size = Some(1);
+    let scanner = Scanner::new(config);
+
+    let threshold = scanner.threshold_time();
+    assert!(scanner
+        .directory_status(&temp_dir.path().join("active_a"), threshold)
+        .is_recording());
+    assert!(!scanner
+        .directory_status(&temp_dir.path().join("idle"), threshold)
+        .is_recording());
+}
+
+#[tokio::test]
+async fn test_scanner_include_exclude_filters() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+    let old_time =
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3 * 86400));
+
+    // 只有系统文件、临时文件和日志目录有新内容的频道
+    let noise = temp_dir.path().join("noise");
+    let session = noise.join("session");
+    fs::create_dir_all(&session).unwrap();
+    fs::write(session.join("old.ts"), "old").unwrap();
+    filetime::set_file_mtime(session.join("old.ts"), old_time).unwrap();
+    filetime::set_file_mtime(&session, old_time).unwrap();
+    fs::create_dir_all(noise.join("logs")).unwrap();
+    fs::write(noise.join("logs/recent.ts"), "log").unwrap();
+    fs::write(noise.join(".DS_Store"), "").unwrap();
+    fs::write(noise.join("segment.tmp"), "").unwrap();
+    // 被排除的二级目录不出现在报告中
+    fs::create_dir_all(temp_dir.path().join("logs")).unwrap();
+
+    let exclude = vec![
+        ".DS_Store".to_string(),
+        "*.tmp".to_string(),
+        "logs".to_string(),
+    ];
+
+    for (latest_only, async_io, batch_size) in [
+        (false, false, None),
+        (false, false, Some(1)),
+        (false, true, None),
+        (false, true, Some(2)),
+        (true, false, None),
+        (true, false, Some(1)),
+    ] {
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.search_latest_subdir_only = Some(latest_only);
+        config.use_async_io = Some(async_io);
+        config.batch_size = batch_size;
+
+        // 未设置过滤时噪声文件会被误判为录制
+        let report = Scanner::new(config.clone()).scan().await.expect("扫描失败");
+        assert!(report.get("noise").unwrap().is_recording());
+
+        config.exclude = Some(exclude.clone());
+        let report = Scanner::new(config.clone()).scan().await.expect("扫描失败");
+        let names: Vec<_> = report.directories.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["active_a", "active_b", "idle", "noise"]);
+        assert!(
+            !report.get("noise").unwrap().is_recording(),
+            "latest_only={} async_io={} batch_size={:?}",
+            latest_only,
+            async_io,
+            batch_size
+        );
+        assert!(report.get("active_a").unwrap().is_recording());
+
+        // include 只计入匹配的文件
+        config.exclude = None;
+        config.include = Some(vec!["*.ts".to_string()]);
+        let report = Scanner::new(config).scan().await.expect("扫描失败");
+        assert!(!report.get("active_a").unwrap().is_recording());
+    }
+}
+
+#[tokio::test]
+async fn test_scanner_growth_detection() {
+    // 增长检测的样本来自同一次遍历，批处理和最新优先都适用；增长检测时不使用增量扫描
+    for variant in ["default", "batch_newest_first", "incremental"] {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+        let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+        config.detection_mode = Some("growth".to_string());
+        config.min_growth_bytes = Some(10);
+        match variant {
+            "batch_newest_first" => {
+                config.batch_size = Some(1);
+                config.traversal = Some("newest_first".to_string());
+            }
+            "incremental" => config.incremental = Some(true),
+            _ => {}
+        }
+        let scanner = Scanner::new(config);
+        let is_recording =
+            |report: &ScanReport, name: &str| report.get(name).unwrap().is_recording();
+
+        // 第一次扫描建立基线，沿用时间阈值的结果；每个文件只读取一次时间戳
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(is_recording(&report, "active_a"), "{}", variant);
+        assert!(!is_recording(&report, "idle"), "{}", variant);
+        assert_eq!(report.files_examined(), 3, "{}", variant);
+
+        // 文件没有变大：不再视为录制
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(!is_recording(&report, "active_a"), "{}", variant);
+        assert!(!is_recording(&report, "active_b"), "{}", variant);
+
+        let append = |path: std::path::PathBuf, bytes: usize| {
+            let mut content = fs::read(&path).unwrap_or_default();
+            content.extend(std::iter::repeat_n(b'x', bytes));
+            fs::write(path, content).unwrap();
+        };
+        append(temp_dir.path().join("active_a/session/new_file.txt"), 20);
+        // 增长不足 min_growth_bytes
+        append(temp_dir.path().join("active_b/session/new_file.txt"), 5);
+
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(is_recording(&report, "active_a"), "{}", variant);
+        assert!(!is_recording(&report, "active_b"), "{}", variant);
+
+        // 上一次扫描之后新出现的文件从 0 字节算起
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        append(temp_dir.path().join("idle/session/segment_2.ts"), 100);
+        let report = scanner.scan().await.expect("扫描失败");
+        assert!(is_recording(&report, "idle"), "{}", variant);
+        assert!(!is_recording(&report, "active_a"), "{}", variant);
+    }
+}
+
+#[tokio::test]
+async fn test_growth_baselines_dropped_for_removed_directories() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("recordings");
+    create_library_test_structure(&root).expect("Failed to create test structure");
+
+    let mut config = MonitorConfig::new(root.display().to_string());
+    config.detection_mode = Some("growth".to_string());
+    let scanner = Scanner::new(config);
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.get("active_a").unwrap().is_recording());
+
+    // 目录被移走后丢弃它的基线；移回来时重新建立基线，沿用时间阈值的结果
+    let moved = temp_dir.path().join("active_a");
+    fs::rename(root.join("active_a"), &moved).unwrap();
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.get("active_a").is_none());
+    fs::rename(&moved, root.join("active_a")).unwrap();
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.get("active_a").unwrap().is_recording());
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(!report.get("active_a").unwrap().is_recording());
+}
+
+#[tokio::test]
+async fn test_scanner_state_survives_reload() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.incremental = Some(true);
+    let scanner = Scanner::new(config.clone());
+    scanner.scan().await.expect("扫描失败");
+    let cached = scanner.scan_cache().unwrap().len();
+    assert!(cached > 0);
+
+    // 修改无关配置后沿用扫描缓存
+    config.check_hours = 3;
+    let reloaded = Scanner::new(config.clone()).with_state_from(&scanner);
+    assert_eq!(reloaded.scan_cache().unwrap().len(), cached);
+
+    // 影响目录读取结果的配置变化后不沿用缓存
+    config.exclude = Some(vec!["*.tmp".to_string()]);
+    let reloaded = Scanner::new(config.clone()).with_state_from(&reloaded);
+    assert!(reloaded.scan_cache().unwrap().is_empty());
+
+    // 增长检测时不使用增量扫描缓存
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.detection_mode = Some("growth".to_string());
+    config.incremental = Some(true);
+    let scanner = Scanner::new(config.clone());
+    assert!(scanner.scan_cache().is_none());
+    let report = scanner.scan().await.expect("扫描失败");
+    assert!(report.get("active_a").unwrap().is_recording());
+
+    // 修改无关配置后沿用增长基准：文件没有变大，不会重新从基线开始
+    config.check_hours = 3;
+    let reloaded = Scanner::new(config.clone()).with_state_from(&scanner);
+    let report = reloaded.scan().await.expect("扫描失败");
+    assert!(!report.get("active_a").unwrap().is_recording());
+
+    // 增长阈值变化后重新建立基线
+    config.min_growth_bytes = Some(10);
+    let reloaded = Scanner::new(config).with_state_from(&reloaded);
+    let report = reloaded.scan().await.expect("扫描失败");
+    assert!(report.get("active_a").unwrap().is_recording());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_detect_open_writers() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // 以追加方式打开旧文件但不写入，修改时间保持在3天前
+    let old_file = temp_dir.path().join("idle/session/old_file.txt");
+    let mut child = Command::new("sh")
+        .args(["-c", "exec 3>>\"$1\"; sleep 10", "sh"])
+        .arg(&old_file)
+        .spawn()
+        .expect("无法启动写入进程");
+
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    let scanner = Scanner::new(config.clone());
+    config.detect_open_writers = Some(true);
+    let detecting_scanner = Scanner::new(config);
+
+    let mut report = None;
+    for _ in 0..50 {
+        let scan = detecting_scanner.scan().await.expect("扫描失败");
+        if !scan.get("idle").unwrap().writers.is_empty() {
+            report = Some(scan);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let report = report.expect("未检测到写入进程");
+    child.kill().ok();
+    child.wait().ok();
+
+    let idle = report.get("idle").unwrap();
+    assert!(idle.is_recording());
+    assert!(idle.writers.iter().any(|w| w.pid == child.id()));
+    assert!(idle
+        .writers
+        .iter()
+        .all(|w| w.path.ends_with("session/old_file.txt")));
+    assert!(report.get("active_a").unwrap().writers.is_empty());
+
+    // 未启用时只看时间戳
+    let plain = scanner.scan().await.expect("扫描失败");
+    assert!(!plain.get("idle").unwrap().is_recording());
+    assert!(plain.get("idle").unwrap().writers.is_empty());
+
+    let mut writer = ReportWriter::new(OutputFormat::Text, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(
+        text.contains(&format!("[{}]", idle.writers[0].pid)),
+        "{}",
+        text
+    );
+}
+
+#[tokio::test]
+async fn test_scanner_reports_newest_file() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // 多个新文件：默认在第一个新文件处停止，find_newest 时找到真正最新的文件
+    let cam = temp_dir.path().join("cam");
+    for (name, minutes, size) in [("a.ts", 50, 10), ("b.ts", 5, 2048), ("c.ts", 30, 10)] {
+        let dir = cam.join(name.trim_end_matches(".ts"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(name), vec![0u8; size]).unwrap();
+        filetime::set_file_mtime(
+            dir.join(name),
+            filetime::FileTime::from_system_time(
+                SystemTime::now() - Duration::from_secs(minutes * 60),
+            ),
+        )
+        .unwrap();
+    }
+
+    let config = MonitorConfig::new(temp_dir.path().display().to_string());
+    let report = Scanner::new(config.clone()).scan().await.expect("扫描失败");
+    let first = report.get("cam").unwrap().newest_file.clone().unwrap();
+    assert!(first.time > report.threshold_time);
+
+    let mut newest_config = config;
+    newest_config.find_newest = Some(true);
+    let report = Scanner::new(newest_config).scan().await.expect("扫描失败");
+    let newest = report.get("cam").unwrap().newest_file.clone().unwrap();
+    assert_eq!(newest.path, Path::new("b/b.ts"));
+    assert_eq!(newest.size, 2048);
+
+    // 没有新文件的目录同样报告最新文件，便于判断已停止多久
+    let idle = report.get("idle").unwrap().newest_file.clone().unwrap();
+    assert_eq!(idle.path, Path::new("session/old_file.txt"));
+    assert!(report.scanned_at - idle.time > chrono::Duration::days(2));
+
+    let mut writer = ReportWriter::new(OutputFormat::Text, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(text.contains("最新文件: b/b.ts, "), "{}", text);
+    assert!(text.contains("5分钟前, 2.0 KB"), "{}", text);
+
+    let mut writer = ReportWriter::new(OutputFormat::Json, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let json: serde_json::Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+    let cam = &json["directories"][2];
+    assert_eq!(cam["directory"], "cam");
+    assert_eq!(cam["newest_file"]["path"], "b/b.ts");
+    assert_eq!(cam["newest_file"]["size"], 2048);
+    assert!(cam["newest_file"]["age_seconds"].as_i64().unwrap() >= 300);
+}
+
+#[tokio::test]
+async fn test_scanner_multiple_roots() {
+    let disk1 = TempDir::new().expect("Failed to create temp directory");
+    let disk2 = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(disk1.path()).expect("Failed to create test structure");
+    create_library_test_structure(disk2.path()).expect("Failed to create test structure");
+    let missing = disk2.path().join("unmounted");
+
+    let mut config = MonitorConfig::new(String::new());
+    let mut first = RootConfig::new(disk1.path().display().to_string());
+    first.label = Some("disk1".to_string());
+    // 第二个根目录放宽时间窗口，idle 目录的旧文件也算录制中
+    let mut second = RootConfig::new(disk2.path().display().to_string());
+    second.label = Some("disk2".to_string());
+    second.check_hours = Some(24 * 7);
+    let mut third = RootConfig::new(missing.display().to_string());
+    third.label = Some("disk3".to_string());
+    config.roots = Some(vec![first, second, third]);
+
+    let scanner = Scanner::new(config);
+    assert_eq!(scanner.root_paths().len(), 3);
+    let report = scanner.scan().await.expect("扫描失败");
+
+    assert_eq!(report.directories.len(), 6);
+    assert!(report.get("disk1/active_a").unwrap().is_recording());
+    assert!(!report.get("disk1/idle").unwrap().is_recording());
+    assert!(report.get("disk2/idle").unwrap().is_recording());
+    assert_eq!(
+        report.get("disk2/active_b").unwrap().root.as_deref(),
+        Some("disk2")
+    );
+    assert_eq!(report.directories_in("disk1").count(), 3);
+    assert_eq!(report.roots.len(), 3);
+    assert!(report.roots[0].error.is_none());
+    assert!(report.roots[2].error.is_some());
+
+    // 不可访问的根目录使检查结果为 UNKNOWN
+    let result = CheckRules::default().evaluate(&report);
+    assert_eq!(result.state, CheckState::Unknown);
+    assert!(result.summary.contains("disk3"), "{}", result.summary);
+
+    // 健康根目录上的必需目录未在录制时为 CRITICAL，不被不可访问的根目录掩盖
+    let rules = CheckRules {
+        required: vec!["disk1/idle".to_string(), "disk3/cam".to_string()],
+        ..CheckRules::default()
+    };
+    let result = rules.evaluate(&report);
+    assert_eq!(result.state, CheckState::Critical, "{}", result);
+    assert!(result.summary.contains("disk3"), "{}", result.summary);
+    assert!(
+        result.summary.contains("目录 'disk1/idle' 未在录制"),
+        "{}",
+        result.summary
+    );
+    assert!(
+        result
+            .summary
+            .contains("目录 'disk3/cam' 所在的根目录不可访问"),
+        "{}",
+        result.summary
+    );
+    let rules = CheckRules {
+        required: vec!["disk3/cam".to_string()],
+        ..CheckRules::default()
+    };
+    assert_eq!(rules.evaluate(&report).state, CheckState::Unknown);
+
+    let mut writer = ReportWriter::new(OutputFormat::Text, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(text.contains("--- [根目录] disk1 ("), "{}", text);
+    assert!(text.contains("--- [根目录] disk2 ("), "{}", text);
+    assert!(text.contains("[警告] 根目录不可访问"), "{}", text);
+
+    let mut writer = ReportWriter::new(OutputFormat::Json, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let json: serde_json::Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+    assert_eq!(json["roots"][1]["label"], "disk2");
+    assert_eq!(json["directories"][0]["root"], "disk1");
+
+    // CSV 中根目录标签单独成列
+    let mut writer = ReportWriter::new(OutputFormat::Csv, Vec::new());
+    writer
+        .write(&report, &OutputConfig::default())
+        .expect("输出失败");
+    let csv = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(csv.starts_with("directory,root,status,"), "{}", csv);
+    assert!(csv.contains("\ndisk2/idle,disk2,recording,"), "{}", csv);
+}
+
+#[test]
+fn test_multiple_roots_config_validation() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let config_path = temp_dir.path().join("config.toml");
+    let write_config = |roots: &str| {
+        fs::write(
+            &config_path,
+            format!(
+                "[monitor]\ncheck_hours = 1\nscan_interval = 60\n{}\n\n[output]\nrecording_message = \"正在录制\"\nnot_recording_message = \"未录制\"\n",
+                roots
+            ),
+        )
+        .unwrap();
+    };
+
+    write_config(
+        "\n[[monitor.roots]]\nroot_path = \"/mnt/a\"\nlabel = \"a\"\n\n[[monitor.roots]]\nroot_path = \"/mnt/b\"\ncheck_hours = 2",
+    );
+    let config = Config::from_file(config_path.to_str().unwrap()).expect("配置应当有效");
+    assert_eq!(config.monitor.roots().len(), 2);
+    assert_eq!(config.monitor.roots()[1].label(), "/mnt/b");
+    let root_config = config.monitor.for_root(&config.monitor.roots()[1]);
+    assert_eq!(root_config.root_path, "/mnt/b");
+    assert_eq!(root_config.check_hours, 2);
+
+    write_config(
+        "\n[[monitor.roots]]\nroot_path = \"/mnt/a\"\nlabel = \"x\"\n\n[[monitor.roots]]\nroot_path = \"/mnt/b\"\nlabel = \"x\"",
+    );
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("重复的根目录标签"),
+        "{:#}",
+        err
+    );
+
+    write_config("");
+    assert!(Config::from_file(config_path.to_str().unwrap()).is_err());
+}
+
+#[tokio::test]
+async fn test_directory_overrides() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+    let config_path = temp_dir.path().join("config.toml");
+    let write_config = |directories: &str| {
+        fs::write(
+            &config_path,
+            format!(
+                "[monitor]\nroot_path = {:?}\ncheck_hours = 1\nscan_interval = 60\n\n[output]\nrecording_message = \"正在录制\"\nnot_recording_message = \"未录制\"\n{}",
+                temp_dir.path().display().to_string(),
+                directories
+            ),
+        )
+        .unwrap();
+    };
+
+    write_config(
+        "\n[directories.\"active_*\"]\nrecording_message = \"切片正常\"\n\n[directories.active_b]\nrecording_message = \"归档正常\"\n\n[directories.idle]\ncheck_hours = 168\nmax_depth = 1\n",
+    );
+    let config = Config::from_file(config_path.to_str().unwrap()).expect("配置应当有效");
+    let report = Scanner::from_config(&config)
+        .scan()
+        .await
+        .expect("扫描失败");
+
+    // idle 的旧文件在 session/ 下，max_depth = 1 时不会被扫描到
+    let idle = report.get("idle").unwrap();
+    assert!(idle.overrides.is_some());
+    assert_eq!(idle.status, DirectoryStatus::Idle);
+
+    let output = &config.output;
+    assert_eq!(
+        output.message_for(report.get("active_a").unwrap()),
+        "切片正常"
+    );
+    // 完全相同的目录名优先于通配符
+    assert_eq!(
+        output.message_for(report.get("active_b").unwrap()),
+        "归档正常"
+    );
+
+    write_config("\n[directories.idle]\ncheck_hours = 168\n");
+    let config = Config::from_file(config_path.to_str().unwrap()).expect("配置应当有效");
+    let report = Scanner::from_config(&config)
+        .scan()
+        .await
+        .expect("扫描失败");
+    assert!(report.get("idle").unwrap().is_recording());
+    assert!(report.get("active_a").unwrap().overrides.is_none());
+    assert_eq!(
+        config.output.message_for(report.get("idle").unwrap()),
+        "正在录制"
+    );
+
+    let mut writer = ReportWriter::new(OutputFormat::Text, Vec::new());
+    writer.write(&report, &config.output).expect("输出失败");
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(text.contains("目录 'idle': 正在录制"), "{}", text);
+
+    // 多个根目录时可以用 "标签/目录名" 只匹配某个根目录下的目录
+    let mut directories = std::collections::BTreeMap::new();
+    directories.insert("disk1/idle".to_string(), DirectoryOverride::default());
+    let overrides = DirectoryOverrides::new(Some(&directories)).unwrap();
+    assert!(overrides.find("idle").is_none());
+    assert!(overrides.for_root("disk1").find("idle").is_some());
+    assert!(overrides.for_root("disk2").find("idle").is_none());
+
+    write_config("\n[directories.\"active_[\"]\ncheck_hours = 2\n");
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(format!("{:#}", err).contains("active_["), "{:#}", err);
+}
+
+#[tokio::test]
+async fn test_directory_status_states() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // 30分钟前写入的文件：在 check_hours 内，但超过了软阈值
+    let quiet = temp_dir.path().join("quiet/session");
+    fs::create_dir_all(&quiet).unwrap();
+    fs::write(quiet.join("segment.ts"), "data").unwrap();
+    filetime::set_file_mtime(
+        quiet.join("segment.ts"),
+        filetime::FileTime::from_system_time(SystemTime::now() - Duration::from_secs(30 * 60)),
+    )
+    .unwrap();
+
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.stale_minutes = Some(10);
+    let scanner = Scanner::new(config);
+    let report = scanner.scan().await.expect("扫描失败");
+
+    assert_eq!(
+        report.get("active_a").unwrap().status,
+        DirectoryStatus::Recording
+    );
+    assert_eq!(report.get("quiet").unwrap().status, DirectoryStatus::Stale);
+    assert_eq!(report.get("idle").unwrap().status, DirectoryStatus::Idle);
+    assert_eq!(report.recording_count(), 2);
+
+    let missing =
+        scanner.directory_status(&temp_dir.path().join("missing"), scanner.threshold_time());
+    assert!(matches!(missing, DirectoryStatus::Unavailable(_)));
+    assert!(missing.is_failure());
+
+    // 每种状态有独立的图标和提示信息，失败原因附在行尾
+    let mut report = report;
+    report.directories.push(DirectoryResult::new(
+        "broken",
+        temp_dir.path().join("broken"),
+        DirectoryStatus::Error("权限不足".to_string()),
+    ));
+    let output = OutputConfig {
+        stale_icon: Some("[~~~]".to_string()),
+        error_message: Some("出错了".to_string()),
+        ..OutputConfig::default()
+    };
+    let mut writer = ReportWriter::new(OutputFormat::Text, Vec::new());
+    writer.write(&report, &output).expect("输出失败");
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert!(text.contains("[REC] 目录 'active_a': 正在录制"), "{}", text);
+    assert!(
+        text.contains("[~~~] 目录 'quiet': 录制可能已停止"),
+        "{}",
+        text
+    );
+    assert!(text.contains("[---] 目录 'idle': 未录制"), "{}", text);
+    assert!(
+        text.contains("[ERR] 目录 'broken': 出错了 (权限不足)"),
+        "{}",
+        text
+    );
+
+    let mut writer = ReportWriter::new(OutputFormat::Json, Vec::new());
+    writer.write(&report, &output).expect("输出失败");
+    let scan: serde_json::Value = serde_json::from_slice(&writer.into_inner()).unwrap();
+    let statuses: Vec<_> = scan["directories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        ["recording", "recording", "idle", "stale", "error"]
+    );
+    assert_eq!(scan["directories"][4]["reason"], "权限不足");
+}
+
+#[test]
+fn test_transition_tracker_ignores_failed_scans() {
+    let root = Path::new("/recordings");
+    let at = chrono::Local::now();
+    let mut tracker = TransitionTracker::new();
+
+    let output = OutputConfig::default();
+
+    let mut report = history_report(root, at, &[("a", true)]);
+    tracker.observe(&mut report, &output);
+
+    // 扫描出错不等于停止录制
+    let mut report = history_report(root, at, &[("a", true)]);
+    report.directories[0].status = DirectoryStatus::Error("超时".to_string());
+    assert!(tracker.observe(&mut report, &output).is_empty());
+
+    // 变化事件带有变化后的状态和按目录覆盖的提示信息
+    let mut report = history_report(root, at, &[("a", false)]);
+    report.directories[0].status = DirectoryStatus::Stale;
+    report.directories[0].overrides = Some(Arc::new(DirectoryOverride {
+        stale_message: Some("信号可能中断".to_string()),
+        ..DirectoryOverride::default()
+    }));
+    let transitions = tracker.observe(&mut report, &output);
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].kind, TransitionKind::RecordingStopped);
+    assert_eq!(transitions[0].status, DirectoryStatus::Stale);
+    assert_eq!(transitions[0].message, "信号可能中断");
+
+    // 目录消失时为未录制，仍使用该目录的覆盖设置
+    let mut report = history_report(root, at, &[("a", true)]);
+    report.directories[0].overrides = Some(Arc::new(DirectoryOverride {
+        not_recording_message: Some("频道已下线".to_string()),
+        ..DirectoryOverride::default()
+    }));
+    tracker.observe(&mut report, &output);
+    let mut report = history_report(root, at, &[]);
+    let transitions = tracker.observe(&mut report, &output);
+    assert_eq!(transitions[0].status, DirectoryStatus::Idle);
+    assert_eq!(transitions[0].message, "频道已下线");
+}
+
+#[test]
+fn test_invalid_filter_pattern_rejected() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let config_path = temp_dir.path().join("config.toml");
+    fs::write(
+        &config_path,
+        r#"
+[monitor]
+root_path = "/tmp"
+check_hours = 2
+scan_interval = 60
+exclude = ["[unclosed"]
+
+[output]
+recording_message = "正在录制"
+not_recording_message = "未录制"
+"#,
+    )
+    .unwrap();
+
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(format!("{:#}", err).contains("exclude"), "{:#}", err);
+}
+
+#[tokio::test]
+async fn test_event_watcher_marks_directory_recording() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let scanner = Scanner::new(MonitorConfig::new(temp_dir.path().display().to_string()));
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(!report.get("idle").unwrap().is_recording());
+
+    let mut watcher = EventWatcher::new(temp_dir.path()).expect("无法创建事件监听器");
+    fs::write(temp_dir.path().join("idle/session/segment.ts"), "data").unwrap();
+
+    let activity = tokio::time::timeout(Duration::from_secs(10), watcher.next_activity())
+        .await
+        .expect("等待事件超时")
+        .expect("事件通道已关闭");
+    assert_eq!(activity.name, "idle");
+
+    assert_eq!(watcher.apply(&mut report), ["idle"]);
+    assert!(report.get("idle").unwrap().is_recording());
+    // 已经是录制状态的目录不会重复报告变化
+    assert!(watcher.apply(&mut report).is_empty());
+
+    // 扫描开始之前的活动已反映在扫描结果中，不会改写之后的报告
+    let mut rescanned = scanner.scan().await.expect("扫描失败");
+    rescanned.directories[2].status = DirectoryStatus::Idle;
+    assert!(watcher.apply(&mut rescanned).is_empty());
+
+    // 全量扫描之后清除的活动不会再被合并
+    watcher.clear_activity();
+    report.directories[2].status = DirectoryStatus::Idle;
+    assert!(watcher.apply(&mut report).is_empty());
+}
+
+#[tokio::test]
+async fn test_event_watcher_applies_filter_and_overrides() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // 阈值设在未来：只有 check_hours 被覆盖为更长时间的目录能接受现在的活动
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.check_hours = 1;
+    let scanner = Scanner::new(config);
+    let threshold = chrono::Local::now() + chrono::Duration::minutes(30);
+    let mut report = scanner
+        .scan_with_threshold(threshold)
+        .await
+        .expect("扫描失败");
+    assert_eq!(report.recording_count(), 0);
+
+    let directories = [(
+        "idle".to_string(),
+        DirectoryOverride {
+            check_hours: Some(2),
+            ..DirectoryOverride::default()
+        },
+    )]
+    .into();
+    let mut watcher = EventWatcher::new(temp_dir.path())
+        .expect("无法创建事件监听器")
+        .with_filter(file_monitor::PathFilter::new(&[], &["*.tmp".to_string()]).unwrap())
+        .with_overrides(DirectoryOverrides::new(Some(&directories)).unwrap(), 1);
+
+    // 被排除的文件和目录本身的事件不算活动
+    fs::write(temp_dir.path().join("active_a/session/upload.tmp"), "data").unwrap();
+    fs::create_dir(temp_dir.path().join("active_a/next")).unwrap();
+    fs::write(temp_dir.path().join("idle/session/segment.ts"), "data").unwrap();
+    fs::write(temp_dir.path().join("active_b/session/segment.ts"), "data").unwrap();
+
+    let mut names = Vec::new();
+    while !names.contains(&"active_b".to_string()) {
+        let activity = tokio::time::timeout(Duration::from_secs(10), watcher.next_activity())
+            .await
+            .expect("等待事件超时")
+            .expect("事件通道已关闭");
+        names.push(activity.name);
+    }
+    assert!(!names.contains(&"active_a".to_string()), "{:?}", names);
+
+    assert_eq!(watcher.apply(&mut report), ["idle"]);
+    assert!(report.get("idle").unwrap().is_recording());
+    assert!(!report.get("active_b").unwrap().is_recording());
+}
+
+#[tokio::test]
+async fn test_event_watcher_respects_stale_minutes_and_failures() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    // stale_minutes = 0：任何已经发生的活动都早于软阈值，只能算作 Stale
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.stale_minutes = Some(0);
+    let scanner = Scanner::new(config.clone());
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert_eq!(
+        report.get("active_a").unwrap().status,
+        DirectoryStatus::Stale
+    );
+    assert_eq!(report.get("idle").unwrap().status, DirectoryStatus::Idle);
+    report.directories[1].status = DirectoryStatus::Unavailable("超时".to_string());
+    let mut growth_report = report.clone();
+    growth_report.directories.retain(|d| d.name != "idle");
+
+    let mut watcher = EventWatcher::new(temp_dir.path())
+        .expect("无法创建事件监听器")
+        .with_detection(&config);
+    for name in ["active_a", "active_b", "idle"] {
+        fs::write(
+            temp_dir.path().join(name).join("session/segment.ts"),
+            "data",
+        )
+        .unwrap();
+    }
+    let mut names = Vec::new();
+    while names.len() < 3 {
+        let activity = tokio::time::timeout(Duration::from_secs(10), watcher.next_activity())
+            .await
+            .expect("等待事件超时")
+            .expect("事件通道已关闭");
+        if !names.contains(&activity.name) {
+            names.push(activity.name);
+        }
+    }
+
+    // Idle 只提升到 Stale；Stale 不会因为过期的活动变为 Recording；扫描失败的状态保持不变
+    assert_eq!(watcher.apply(&mut report), ["idle"]);
+    assert_eq!(report.get("idle").unwrap().status, DirectoryStatus::Stale);
+    assert_eq!(
+        report.get("active_a").unwrap().status,
+        DirectoryStatus::Stale
+    );
+    assert!(report.get("active_b").unwrap().status.is_failure());
+
+    // growth 模式下已有的判断保持不变，报告中没有的目录以 Idle 加入，由下一次扫描判断
+    let mut growth = config.clone();
+    growth.detection_mode = Some("growth".to_string());
+    let watcher = watcher.with_detection(&growth);
+    assert_eq!(watcher.apply(&mut growth_report), ["idle"]);
+    assert_eq!(
+        growth_report.get("idle").unwrap().status,
+        DirectoryStatus::Idle
+    );
+    assert_eq!(
+        growth_report.get("active_a").unwrap().status,
+        DirectoryStatus::Stale
+    );
+}
+
+#[test]
+fn test_machine_readable_output_formats() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path().join("root");
+    fs::create_dir_all(&test_path).unwrap();
+    create_library_test_structure(&test_path).expect("Failed to create test structure");
+
+    let safe_path = test_path.display().to_string().replace('\\', "/");
+    let config_content = format!(
+        r#"
+[monitor]
+root_path = "{}"
+check_hours = 3
+scan_interval = 60
+
+[output]
+recording_message = "正在录制"
+not_recording_message = "未录制"
+format = "csv"
+"#,
+        safe_path
+    );
+    let config_path = temp_dir.path().join("test_config.toml");
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    // 配置文件中的 csv 格式
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--config",
+            config_path.to_str().unwrap(),
+            "--once",
+        ])
+        .output()
+        .expect("Failed to run program");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "directory,root,status,reason,scanned_at,scan_duration_ms,transition,writers,newest_file,newest_file_time,newest_file_age_seconds,newest_file_size"
+    );
+    assert_eq!(lines.len(), 4, "CSV 输出: {}", stdout);
+    assert!(lines[1].starts_with("active_a,,recording,"));
+    assert!(lines[3].starts_with("idle,,idle,"));
+
+    // 命令行参数覆盖配置文件
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--config",
+            config_path.to_str().unwrap(),
+            "--once",
+            "--output-format",
+            "ndjson",
+        ])
+        .output()
+        .expect("Failed to run program");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1, "NDJSON 输出: {}", stdout);
+    let scan: serde_json::Value = serde_json::from_str(stdout.trim()).expect("无效的 JSON");
+    let directories = scan["directories"].as_array().unwrap();
+    assert_eq!(directories.len(), 3);
+    assert_eq!(directories[2]["directory"], "idle");
+    assert_eq!(directories[2]["status"], "idle");
+    assert!(directories[0]["scanned_at"].is_string());
+    assert!(directories[0]["scan_duration_ms"].is_number());
+}
+
+#[tokio::test]
+async fn test_report_writer_csv_header_written_once() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let scanner = Scanner::new(MonitorConfig::new(temp_dir.path().display().to_string()));
+    let mut writer = ReportWriter::new(OutputFormat::Csv, Vec::new());
+    for _ in 0..2 {
+        let report = scanner.scan().await.expect("扫描失败");
+        writer.write(&report, &OutputConfig::default()).unwrap();
+    }
+
+    let output = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(output.lines().count(), 7);
+    assert_eq!(output.matches("directory,root,status").count(), 1);
+}
+
+/// 发送一个简单的 HTTP GET 请求，返回状态码和响应体
+async fn http_get(addr: std::net::SocketAddr, path: &str) -> (u16, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+#[tokio::test]
+async fn test_status_server_endpoints() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let store = StatusStore::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server::serve(listener, store.clone()));
+
+    // 首次扫描完成之前
+    assert_eq!(http_get(addr, "/healthz").await.0, 200);
+    assert_eq!(http_get(addr, "/status").await.0, 503);
+
+    let scanner = Scanner::new(MonitorConfig::new(temp_dir.path().display().to_string()));
+    store.update(scanner.scan().await.expect("扫描失败")).await;
+
+    let (status, body) = http_get(addr, "/status").await;
+    assert_eq!(status, 200);
+    let scan: serde_json::Value = serde_json::from_str(&body).expect("无效的 JSON");
+    assert_eq!(scan["directories"].as_array().unwrap().len(), 3);
+
+    let (status, body) = http_get(addr, "/status/active_a").await;
+    assert_eq!(status, 200);
+    let dir: serde_json::Value = serde_json::from_str(&body).expect("无效的 JSON");
+    assert_eq!(dir["status"], "recording");
+
+    assert_eq!(http_get(addr, "/status/missing").await.0, 404);
+
+    let (_, body) = http_get(addr, "/healthz").await;
+    let health: serde_json::Value = serde_json::from_str(&body).expect("无效的 JSON");
+    assert!(health["last_scan"].is_string());
+}
+
+#[tokio::test]
+async fn test_prometheus_metrics() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("root");
+    create_library_test_structure(&root).expect("Failed to create test structure");
+
+    let scanner = Scanner::new(MonitorConfig::new(root.display().to_string()));
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(report.files_examined() >= 3);
+    report.directories.push(DirectoryResult::new(
+        "broken",
+        root.join("broken"),
+        DirectoryStatus::Error("权限不足".to_string()),
+    ));
+
+    let store = StatusStore::new();
+    let metrics = store.metrics().clone();
+    // 同一次扫描重复发布时计数器只累计一次
+    metrics.observe(&report);
+    metrics.observe(&report);
+    metrics.observe(&scanner.scan().await.expect("扫描失败"));
+
+    let text = metrics.render();
+    assert!(text.contains("file_monitor_directory_recording{dir=\"active_a\"} 1"));
+    assert!(text.contains("file_monitor_directory_recording{dir=\"idle\"} 0"));
+    assert!(text.contains("# TYPE file_monitor_scan_duration_seconds gauge"));
+    assert!(text.contains("file_monitor_scans_total 2"), "{}", text);
+    assert!(
+        text.contains("file_monitor_scan_errors_total 1"),
+        "{}",
+        text
+    );
+    assert!(text.contains(&format!(
+        "file_monitor_files_examined_total {}",
+        report.files_examined() * 2
+    )));
+
+    // textfile collector 输出：写入后不留下临时文件
+    let prom = temp_dir.path().join("file_monitor.prom");
+    metrics.write_textfile(&prom).expect("写入指标文件失败");
+    assert_eq!(fs::read_to_string(&prom).unwrap(), text);
+    assert!(!temp_dir.path().join("file_monitor.prom.tmp").exists());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(server::serve(listener, store));
+    let (status, body) = http_get(addr, "/metrics").await;
+    assert_eq!(status, 200);
+    assert!(body.contains("file_monitor_scans_total 2"), "{}", body);
+
+    // 根目录无法访问也计入扫描失败
+    metrics.observe(&ScanReport::unavailable(
+        &root,
+        chrono::Local::now(),
+        "监控目录不存在",
+    ));
+    let text = metrics.render();
+    assert!(
+        text.contains("file_monitor_scan_errors_total 2"),
+        "{}",
+        text
+    );
+}
+
+#[tokio::test]
+async fn test_transition_tracker_detects_start_and_stop() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+
+    let scanner = Scanner::new(MonitorConfig::new(temp_dir.path().display().to_string()));
+    let mut tracker = TransitionTracker::new();
+
+    // 第一次扫描只作为基线
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(tracker
+        .observe(&mut report, &OutputConfig::default())
+        .is_empty());
+
+    fs::write(temp_dir.path().join("idle/session/segment.ts"), "data").unwrap();
+    fs::remove_dir_all(temp_dir.path().join("active_b")).unwrap();
+
+    let mut report = scanner.scan().await.expect("扫描失败");
+    let transitions = tracker.observe(&mut report, &OutputConfig::default());
+    let summary: Vec<_> = transitions
+        .iter()
+        .map(|t| (t.directory.as_str(), t.kind))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("idle", TransitionKind::RecordingStarted),
+            ("active_b", TransitionKind::RecordingStopped),
+        ]
+    );
+    assert_eq!(report.transitions, transitions);
+    assert_eq!(
+        report.transition_for("idle").map(|t| t.kind),
+        Some(TransitionKind::RecordingStarted)
+    );
+
+    // 状态未变化时不再产生事件
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(tracker
+        .observe(&mut report, &OutputConfig::default())
+        .is_empty());
+    assert!(report.transitions.is_empty());
+}
+
+#[tokio::test]
+async fn test_transition_tracker_keeps_state_while_root_unavailable() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let root = temp_dir.path().join("disk");
+    let moved = temp_dir.path().join("moved");
+    create_library_test_structure(&root).expect("Failed to create test structure");
+    let output = OutputConfig::default();
+
+    // 单个根目录：根目录被移走后恢复，不产生停止和开始事件
+    let scanner = Scanner::new(MonitorConfig::new(root.display().to_string()));
+    let mut tracker = TransitionTracker::new();
+    tracker.observe(&mut scanner.scan().await.expect("扫描失败"), &output);
+    fs::rename(&root, &moved).unwrap();
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(report.directories.is_empty());
+    assert!(tracker.observe(&mut report, &output).is_empty());
+    fs::rename(&moved, &root).unwrap();
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(tracker.observe(&mut report, &output).is_empty());
+
+    // 多个根目录：只有不可访问的根目录保持原状态
+    let other = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(other.path()).expect("Failed to create test structure");
+    let mut config = MonitorConfig::new(String::new());
+    let mut first = RootConfig::new(root.display().to_string());
+    first.label = Some("disk1".to_string());
+    let mut second = RootConfig::new(other.path().display().to_string());
+    second.label = Some("disk2".to_string());
+    config.roots = Some(vec![first, second]);
+    let scanner = Scanner::new(config);
+    let mut tracker = TransitionTracker::new();
+    tracker.observe(&mut scanner.scan().await.expect("扫描失败"), &output);
+
+    fs::rename(&root, &moved).unwrap();
+    fs::remove_dir_all(other.path().join("active_b")).unwrap();
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(report.roots[0].error.is_some());
+    let summary: Vec<_> = tracker
+        .observe(&mut report, &output)
+        .into_iter()
+        .map(|t| (t.directory, t.kind))
+        .collect();
+    assert_eq!(
+        summary,
+        [(
+            "disk2/active_b".to_string(),
+            TransitionKind::RecordingStopped
+        )]
+    );
+
+    fs::rename(&moved, &root).unwrap();
+    let mut report = scanner.scan().await.expect("扫描失败");
+    assert!(tracker.observe(&mut report, &output).is_empty());
+}
+
+/// 本地 Webhook 替身：依次用给定状态码响应请求，并返回收到的原始请求
+async fn spawn_webhook_stand_in(
+    statuses: Vec<u16>,
+) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for status in statuses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // 读取到请求头结束且请求体完整为止
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            requests.push(String::from_utf8_lossy(&request).to_string());
+        }
+        requests
+    });
+
+    (url, handle)
+}
+
+fn sample_transition(kind: TransitionKind) -> StatusTransition {
+    StatusTransition {
+        directory: "频道\"1\"".to_string(),
+        path: "/recordings/channel1".into(),
+        kind,
+        at: chrono::Local::now(),
+        status: match kind {
+            TransitionKind::RecordingStarted => DirectoryStatus::Recording,
+            TransitionKind::RecordingStopped => DirectoryStatus::Idle,
+        },
+        message: match kind {
+            TransitionKind::RecordingStarted => "正在录制".to_string(),
+            TransitionKind::RecordingStopped => "未录制".to_string(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_webhook_sends_templated_payload_with_retry() {
+    let (url, stand_in) = spawn_webhook_stand_in(vec![500, 200]).await;
+
+    let mut webhook = WebhookConfig::new(url);
+    webhook.headers = Some([("X-Token".to_string(), "secret".to_string())].into());
+    webhook.payload_template =
+        Some(r#"{"dir": "{{directory}}", "text": "{{message}}", "event": "{{event}}"}"#.into());
+    webhook.retries = Some(1);
+    webhook.retry_delay = Some(0);
+    webhook.timeout = Some(5);
+
+    let notifier = WebhookNotifier::new(vec![webhook]);
+    let results = notifier
+        .notify(&sample_transition(TransitionKind::RecordingStarted))
+        .await;
+    assert!(results[0].is_ok(), "{:?}", results);
+
+    let requests = stand_in.await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let request = &requests[1];
+    assert!(request.starts_with("POST /hook"));
+    assert!(request.to_lowercase().contains("x-token: secret"));
+
+    let body = request.split_once("\r\n\r\n").unwrap().1;
+    let payload: serde_json::Value = serde_json::from_str(body).expect("无效的 JSON");
+    assert_eq!(payload["dir"], "频道\"1\"");
+    assert_eq!(payload["text"], "正在录制");
+    assert_eq!(payload["event"], "recording_started");
+}
+
+#[test]
+fn test_webhook_payload_values_are_not_expanded_again() {
+    let mut transition = sample_transition(TransitionKind::RecordingStopped);
+    transition.directory = "cam{{event}}".to_string();
+    transition.message = "{{status}} {{unknown}}".to_string();
+
+    let body = render_payload(
+        r#"{"dir": "{{directory}}", "text": "{{message}}", "status": "{{status}}", "raw": "{{unknown}}"}"#,
+        &transition,
+    );
+    let payload: serde_json::Value = serde_json::from_str(&body).expect("无效的 JSON");
+    assert_eq!(payload["dir"], "cam{{event}}");
+    assert_eq!(payload["text"], "{{status}} {{unknown}}");
+    assert_eq!(payload["status"], "idle");
+    assert_eq!(payload["raw"], "{{unknown}}");
+}
+
+#[tokio::test]
+async fn test_webhook_reports_failure_after_retries() {
+    let (url, stand_in) = spawn_webhook_stand_in(vec![503, 503]).await;
+
+    let mut webhook = WebhookConfig::new(url);
+    webhook.retries = Some(1);
+    webhook.retry_delay = Some(0);
+
+    let notifier = WebhookNotifier::new(vec![webhook]);
+    let results = notifier
+        .notify(&sample_transition(TransitionKind::RecordingStopped))
+        .await;
+    assert!(results[0].is_err());
+
+    // 默认模板同样是合法的 JSON
+    let requests = stand_in.await.unwrap();
+    let body = requests[0].split_once("\r\n\r\n").unwrap().1;
+    let payload: serde_json::Value = serde_json::from_str(body).expect("无效的 JSON");
+    assert_eq!(payload["status"], "idle");
+    assert_eq!(payload["message"], "未录制");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_hooks_receive_environment_and_time_out() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let marker = temp_dir.path().join("hook_output.txt");
+
+    let runner = HookRunner::new(HooksConfig {
+        on_start: Some(format!(
+            "echo \"$FM_DIR|$FM_PATH|$FM_STATUS|$FM_EVENT|$FM_TIMESTAMP\" > '{}'",
+            marker.display()
+        )),
+        on_stop: Some("sleep 5".to_string()),
+        on_stop_timeout: Some(1),
+        ..Default::default()
+    });
+
+    let status = runner
+        .run(&sample_transition(TransitionKind::RecordingStarted))
+        .await
+        .expect("钩子执行失败")
+        .expect("未执行 on_start 钩子");
+    assert!(status.success());
+
+    let output = fs::read_to_string(&marker).unwrap();
+    let fields: Vec<_> = output.trim().split('|').collect();
+    assert_eq!(
+        fields[..4],
+        [
+            "频道\"1\"",
+            "/recordings/channel1",
+            "recording",
+            "recording_started"
+        ]
+    );
+    assert!(chrono::DateTime::parse_from_rfc3339(fields[4]).is_ok());
+
+    // on_stop 超时后返回错误，而不是一直等待
+    let started = std::time::Instant::now();
+    let result = runner
+        .run(&sample_transition(TransitionKind::RecordingStopped))
+        .await;
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(4));
+}
+
+fn history_report(
+    root: &Path,
+    scanned_at: chrono::DateTime<chrono::Local>,
+    statuses: &[(&str, bool)],
+) -> ScanReport {
+    let mut report = ScanReport::empty(root, scanned_at);
+    report.scanned_at = scanned_at;
+    report.directories = statuses
+        .iter()
+        .map(|(name, recording)| {
+            let status = if *recording {
+                DirectoryStatus::Recording
+            } else {
+                DirectoryStatus::Idle
+            };
+            DirectoryResult::new(*name, root.join(name), status)
+        })
+        .collect();
+    report
+}
+
+#[test]
+fn test_history_store_sessions_and_uptime() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let store = HistoryStore::open(&temp_dir.path().join("history.db")).expect("无法打开历史");
+
+    let start = chrono::Local::now() - chrono::Duration::days(1);
+    let hour = chrono::Duration::hours(1);
+    let scans = [
+        (start - hour, [("a", true), ("b", false)]),
+        (start, [("a", true), ("b", false)]),
+        (start + hour, [("a", true), ("b", true)]),
+        (start + hour * 2, [("a", false), ("b", true)]),
+        (start + hour * 4, [("a", false), ("b", true)]),
+    ];
+    for (at, statuses) in &scans {
+        store
+            .record(&history_report(temp_dir.path(), *at, statuses))
+            .expect("无法记录历史");
+    }
+
+    let histories = store
+        .summarize(start, start + hour * 5, None)
+        .expect("无法统计历史");
+    assert_eq!(histories.len(), 2);
+
+    // 范围开始前已在录制，会话从范围起点算起
+    let a = &histories[0];
+    assert_eq!(a.directory, "a");
+    assert_eq!(a.sessions.len(), 1);
+    assert!(!a.sessions[0].ongoing);
+    assert_eq!(a.recorded, hour * 2);
+    assert_eq!(a.observed, hour * 4);
+    assert!((a.uptime_percent() - 50.0).abs() < 1e-9);
+
+    let b = &histories[1];
+    assert_eq!(b.sessions.len(), 1);
+    assert!(b.sessions[0].ongoing);
+    assert_eq!(b.recorded, hour * 3);
+    assert!((b.uptime_percent() - 75.0).abs() < 1e-9);
+
+    let only_b = store
+        .summarize(start, start + hour * 5, Some("b"))
+        .expect("无法统计历史");
+    assert_eq!(only_b.len(), 1);
+    assert_eq!(only_b[0].directory, "b");
+}
+
+#[test]
+fn test_history_gap_ends_session() {
+    let store = HistoryStore::open_in_memory()
+        .expect("无法打开历史")
+        .with_scan_interval(3600);
+
+    // 监控中断两天：中断期间既不算录制也不算观测
+    let start = chrono::Local::now() - chrono::Duration::days(3);
+    let hour = chrono::Duration::hours(1);
+    let scans = [
+        (start, true),
+        (start + hour, true),
+        (start + hour * 49, true),
+        (start + hour * 50, false),
+        (start + hour * 51, false),
+    ];
+    for (at, recording) in scans {
+        store
+            .record(&history_report(
+                Path::new("/recordings"),
+                at,
+                &[("a", recording)],
+            ))
+            .expect("无法记录历史");
+    }
+
+    let histories = store
+        .summarize(start, start + hour * 52, None)
+        .expect("无法统计历史");
+    let a = &histories[0];
+    assert_eq!(a.sessions.len(), 2);
+    assert_eq!(a.sessions[0].end.timestamp(), (start + hour).timestamp());
+    assert!(!a.sessions[0].ongoing);
+    assert_eq!(
+        a.sessions[1].start.timestamp(),
+        (start + hour * 49).timestamp()
+    );
+    assert_eq!(a.recorded, hour * 2);
+    assert_eq!(a.observed, hour * 3);
+    assert!((a.uptime_percent() - 200.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_history_retention_and_rescan() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let store = HistoryStore::open(&temp_dir.path().join("history.db"))
+        .expect("无法打开历史")
+        .with_retention_days(Some(7));
+
+    let now = chrono::Local::now();
+    let old = now - chrono::Duration::days(10);
+    store
+        .record(&history_report(temp_dir.path(), old, &[("a", true)]))
+        .expect("无法记录历史");
+    let recent = now - chrono::Duration::hours(1);
+    store
+        .record(&history_report(temp_dir.path(), recent, &[("a", true)]))
+        .expect("无法记录历史");
+    // 同一次扫描再次记录时替换之前的记录
+    store
+        .record(&history_report(temp_dir.path(), now, &[("a", true)]))
+        .expect("无法记录历史");
+    store
+        .record(&history_report(temp_dir.path(), now, &[("a", false)]))
+        .expect("无法记录历史");
+
+    // 超过保留天数的记录已删除，范围开始时不再视为录制中
+    let histories = store
+        .summarize(old - chrono::Duration::hours(1), now, None)
+        .expect("无法统计历史");
+    assert_eq!(histories.len(), 1);
+    let a = &histories[0];
+    assert_eq!(a.sessions.len(), 1);
+    assert_eq!(a.sessions[0].start.timestamp(), recent.timestamp());
+    assert_eq!(a.sessions[0].end.timestamp(), now.timestamp());
+    assert!(!a.sessions[0].ongoing);
+}
+
+#[test]
+fn test_history_subcommand() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path().join("root");
+    fs::create_dir_all(&test_path).unwrap();
+    create_library_test_structure(&test_path).expect("Failed to create test structure");
+
+    let safe_path = test_path.display().to_string().replace('\\', "/");
+    let config_content = format!(
+        r#"
+[monitor]
+root_path = "{}"
+check_hours = 3
+scan_interval = 60
+
+[output]
+recording_message = "正在录制"
+not_recording_message = "未录制"
+
+[history]
+enabled = true
+"#,
+        safe_path
+    );
+    let config_path = temp_dir.path().join("test_config.toml");
+    fs::write(&config_path, config_content).expect("Failed to write config");
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--config",
+            config_path.to_str().unwrap(),
+            "--once",
+        ])
+        .output()
+        .expect("Failed to run program");
+    assert!(output.status.success());
+    // 启用后历史数据库默认保存在配置文件旁边
+    assert!(temp_dir.path().join("file_monitor_history.db").exists());
+
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--",
+            "--config",
+            config_path.to_str().unwrap(),
+            "history",
+            "--dir",
+            "active_a",
+        ])
+        .output()
+        .expect("Failed to run program");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("录制统计"), "输出: {}", stdout);
+    assert!(
+        stdout.contains("目录 'active_a': 录制 1 次"),
+        "输出: {}",
+        stdout
+    );
+    assert!(!stdout.contains("目录 'idle'"), "输出: {}", stdout);
+}
+
+#[tokio::test]
+async fn test_check_rules() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    create_library_test_structure(temp_dir.path()).expect("Failed to create test structure");
+    let mut config = MonitorConfig::new(temp_dir.path().display().to_string());
+    config.check_hours = 3;
+    let report = Scanner::new(config).scan().await.expect("扫描失败");
+
+    let result = CheckRules::default().evaluate(&report);
+    assert_eq!(result.state, CheckState::Ok);
+    assert_eq!(
+        result.to_string().split(" | ").next().unwrap(),
+        "FILE_MONITOR OK - 3 个目录中 2 个正在录制"
+    );
+    assert!(result.perfdata[0].starts_with("recording=2;;;0;3"));
+
+    let rules = CheckRules {
+        warning_below: Some(3),
+        critical_below: Some(1),
+        ..CheckRules::default()
+    };
+    let result = rules.evaluate(&report);
+    assert_eq!(result.state, CheckState::Warning);
+    assert_eq!(result.state.exit_code(), 1);
+    assert!(result.perfdata[0].starts_with("recording=2;3:;1:;0;3"));
+
+    let rules = CheckRules {
+        warning_below: Some(1),
+        required: vec!["active_a".to_string(), "idle".to_string()],
+        ..CheckRules::default()
+    };
+    let result = rules.evaluate(&report);
+    assert_eq!(result.state, CheckState::Critical);
+    assert!(
+        result.summary.contains("目录 'idle' 未在录制"),
+        "{}",
+        result
+    );
+
+    // 必需目录扫描失败时无法判断，结果为 UNKNOWN
+    let mut failed = report.clone();
+    failed.directories.push(DirectoryResult::new(
+        "broken",
+        temp_dir.path().join("broken"),
+        DirectoryStatus::Unavailable("权限不足".to_string()),
+    ));
+    let rules = CheckRules {
+        required: vec!["broken".to_string()],
+        ..CheckRules::default()
+    };
+    assert_eq!(rules.evaluate(&failed).state, CheckState::Unknown);
+
+    // 另一个必需目录确实未在录制时结果为 CRITICAL，不被扫描失败掩盖
+    let rules = CheckRules {
+        required: vec!["broken".to_string(), "idle".to_string()],
+        ..CheckRules::default()
+    };
+    let result = rules.evaluate(&failed);
+    assert_eq!(result.state, CheckState::Critical, "{}", result);
+    assert!(
+        result.summary.contains("目录 'broken' 状态未知"),
+        "{}",
+        result
+    );
+
+    // 根目录不可访问记录在扫描结果中
+    let missing_root = temp_dir.path().join("missing");
+    let missing = Scanner::new(MonitorConfig::new(missing_root.display().to_string()))
+        .scan()
+        .await
+        .expect("扫描失败");
+    assert!(missing.directories.is_empty());
+    assert_eq!(missing.roots.len(), 1);
+    assert_eq!(missing.roots[0].path, missing_root);
+    let result = CheckRules::default().evaluate(&missing);
+    assert_eq!(result.state, CheckState::Unknown);
+    assert!(result.summary.contains("无法读取监控目录"), "{}", result);
+}
+
+#[test]
+fn test_check_subcommand_exit_codes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path().join("root");
+    fs::create_dir_all(&test_path).unwrap();
+    create_library_test_structure(&test_path).expect("Failed to create test structure");
+
+    let safe_path = test_path.display().to_string().replace('\\', "/");
+    let config_path = temp_dir.path().join("test_config.toml");
+    fs::write(
+        &config_path,
+        format!(
+            r#"
+[monitor]
+root_path = "{}"
+check_hours = 3
+scan_interval = 60
+incremental = true
+scan_cache = "scan_cache.json"
+
+[output]
+recording_message = "正在录制"
+not_recording_message = "未录制"
+"#,
+            safe_path
+        ),
+    )
+    .expect("Failed to write config");
+
+    let run_check = |extra: &[&str]| {
+        let mut args = vec![
+            "run",
+            "--",
+            "--config",
+            config_path.to_str().unwrap(),
+            "check",
+        ];
+        args.extend_from_slice(extra);
+        let output = Command::new("cargo")
+            .args(&args)
+            .output()
+            .expect("Failed to run program");
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        (output.status.code(), stdout)
+    };
+
+    let (code, stdout) = run_check(&["--warning-below", "2"]);
+    assert_eq!(code, Some(0), "{}", stdout);
+    // 只输出一行状态和性能数据
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    assert!(stdout.starts_with("FILE_MONITOR OK - "), "{}", stdout);
+    assert!(stdout.contains("| recording=2;2:;;0;3"), "{}", stdout);
+
+    let (code, stdout) = run_check(&["--warning-below", "3"]);
+    assert_eq!(code, Some(1), "{}", stdout);
+
+    let (code, stdout) = run_check(&["--require", "idle"]);
+    assert_eq!(code, Some(2), "{}", stdout);
+    assert!(stdout.starts_with("FILE_MONITOR CRITICAL - "), "{}", stdout);
+
+    // 检查模式不记录历史，也不读写扫描缓存文件
+    assert!(!temp_dir.path().join("file_monitor_history.db").exists());
+    assert!(!temp_dir.path().join("scan_cache.json").exists());
+
+    let (code, stdout) = Command::new("cargo")
+        .args(["run", "--", "--config", "missing.toml", "check"])
+        .output()
+        .map(|o| {
+            (
+                o.status.code(),
+                String::from_utf8_lossy(&o.stdout).to_string(),
+            )
+        })
+        .expect("Failed to run program");
+    assert_eq!(code, Some(3), "{}", stdout);
+    assert!(stdout.starts_with("FILE_MONITOR UNKNOWN - "), "{}", stdout);
+}
+
+#[tokio::test]
+async fn test_config_watcher_detects_changes() {
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let config_path = temp_dir.path().join("config.toml");
+    fs::write(&config_path, "# v1").unwrap();
+
+    let mut watcher = ConfigWatcher::new(&config_path);
+    // 同目录下的其他文件不触发重载
+    fs::write(temp_dir.path().join("other.toml"), "x").unwrap();
+    fs::write(&config_path, "# v2").unwrap();
+
+    let trigger = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+        .await
+        .expect("未检测到配置文件变化");
+    assert_eq!(trigger, ReloadTrigger::FileChanged);
+
+    // 以重命名方式保存（编辑器常用）同样会触发
+    let temp_path = temp_dir.path().join("config.toml.tmp");
+    fs::write(&temp_path, "# v3").unwrap();
+    fs::rename(&temp_path, &config_path).unwrap();
+    let trigger = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+        .await
+        .expect("未检测到配置文件替换");
+    assert_eq!(trigger, ReloadTrigger::FileChanged);
+}
+
+#[test]
+fn test_config_hot_reload() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp directory");
+    let test_path = temp_dir.path().join("root");
+    create_library_test_structure(&test_path).expect("Failed to create test structure");
+
+    let safe_path = test_path.display().to_string().replace('\\', "/");
+    let config_for = |message: &str| {
+        format!(
+            r#"
+[monitor]
+root_path = "{}"
+check_hours = 3
+scan_interval = 3600
+
+[output]
+recording_message = "{}"
+not_recording_message = "未录制"
+
+[history]
+enabled = false
+"#,
+            safe_path, message
+        )
+    };
+    let config_path = temp_dir.path().join("test_config.toml");
+    fs::write(&config_path, config_for("正在录制")).expect("Failed to write config");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_file_monitor"))
+        .args(["--config", config_path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run program");
+
+    let (tx, rx) = mpsc::channel();
+    for stream in [
+        Box::new(child.stdout.take().unwrap()) as Box<dyn std::io::Read + Send>,
+        Box::new(child.stderr.take().unwrap()),
+    ] {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                let _ = tx.send(line);
+            }
+        });
+    }
+    let wait_for = |pattern: &str| {
+        let deadline = std::time::Instant::now() + Duration::from_secs(20);
+        while let Some(timeout) = deadline.checked_duration_since(std::time::Instant::now()) {
+            match rx.recv_timeout(timeout) {
+                Ok(line) if line.contains(pattern) => return true,
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        false
+    };
+
+    // 断言失败时也要结束子进程
+    let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        assert!(wait_for("目录 'active_a': 正在录制"), "首次扫描没有输出");
+
+        // 无效配置不会替换当前配置
+        fs::write(&config_path, "[monitor\nroot_path = ").unwrap();
+        assert!(wait_for("继续使用原配置"), "无效配置没有记录原因");
+
+        // 修改提示信息后无需重启即生效
+        fs::write(&config_path, config_for("录制中(新)")).unwrap();
+        assert!(wait_for("目录 'active_a': 录制中(新)"), "新配置没有生效");
+    }));
+    let _ = child.kill();
+    let _ = child.wait();
+    if let Err(panic) = outcome {
+        std::panic::resume_unwind(panic);
+    }
+}